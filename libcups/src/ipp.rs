@@ -0,0 +1,152 @@
+use crate::result::cups_error;
+use libcups_sys::*;
+use std::{
+    ffi::{c_char, CStr},
+    io,
+    mem::ManuallyDrop,
+    ptr::{null, null_mut},
+};
+
+/// IPP request to CUPS server.
+pub(crate) struct Request(*mut ipp_t);
+
+/// IPP response from CUPS server.
+pub(crate) struct Response(*mut ipp_t);
+
+impl Request {
+    /// Create a new request with `attributes-charset` and `attributes-natural-language`.
+    pub fn new(operation: ipp_op_t) -> Self {
+        Self(unsafe { ippNewRequest(operation) })
+    }
+
+    /// Add URI attribute to the operation group.
+    pub fn uri(self, name: &CStr, value: &CStr) -> Self {
+        unsafe {
+            ippAddString(
+                self.0,
+                ipp_tag_e_IPP_TAG_OPERATION,
+                ipp_tag_e_IPP_TAG_URI,
+                name.as_ptr(),
+                null(),
+                value.as_ptr(),
+            )
+        };
+
+        self
+    }
+
+    /// Add integer attribute to the operation group.
+    pub fn integer(self, name: &CStr, value: i32) -> Self {
+        unsafe {
+            ippAddInteger(
+                self.0,
+                ipp_tag_e_IPP_TAG_OPERATION,
+                ipp_tag_e_IPP_TAG_INTEGER,
+                name.as_ptr(),
+                value,
+            )
+        };
+
+        self
+    }
+
+    /// Add keywords attribute to the operation group, e.g. `requested-attributes`.
+    pub fn keywords(self, name: &CStr, values: &[&CStr]) -> Self {
+        let values: Vec<*const c_char> = values.iter().map(|value| value.as_ptr()).collect();
+
+        unsafe {
+            ippAddStrings(
+                self.0,
+                ipp_tag_e_IPP_TAG_OPERATION,
+                ipp_tag_e_IPP_TAG_KEYWORD,
+                name.as_ptr(),
+                values.len() as i32,
+                null(),
+                values.as_ptr(),
+            )
+        };
+
+        self
+    }
+
+    /// Send the request to CUPS server and wait for the response.
+    ///
+    /// # Error
+    ///
+    /// Return error if there is no response or its status is an error.
+    pub fn send(self, resource: &CStr) -> io::Result<Response> {
+        // The request is freed by `cupsDoRequest`.
+        let request = ManuallyDrop::new(self);
+
+        let response = unsafe { cupsDoRequest(null_mut(), request.0, resource.as_ptr()) };
+        if response.is_null() {
+            return Err(io::Error::other(cups_error().unwrap()));
+        }
+
+        let response = Response(response);
+        if unsafe { cupsLastError() } >= ipp_status_e_IPP_STATUS_ERROR_BAD_REQUEST {
+            return Err(io::Error::other(cups_error().unwrap()));
+        }
+
+        Ok(response)
+    }
+}
+
+impl Drop for Request {
+    fn drop(&mut self) {
+        unsafe { ippDelete(self.0) }
+    }
+}
+
+impl Response {
+    /// First value of boolean attribute.
+    pub fn boolean(&self, name: &CStr) -> Option<bool> {
+        let attribute = self.find(name)?;
+        Some(unsafe { ippGetBoolean(attribute, 0) } != 0)
+    }
+
+    /// First value of integer or enum attribute.
+    pub fn integer(&self, name: &CStr) -> Option<i32> {
+        self.integers(name).first().copied()
+    }
+
+    /// All values of integer or enum attribute.
+    pub fn integers(&self, name: &CStr) -> Vec<i32> {
+        let Some(attribute) = self.find(name) else {
+            return Vec::new();
+        };
+
+        let count = unsafe { ippGetCount(attribute) };
+        (0..count)
+            .map(|idx| unsafe { ippGetInteger(attribute, idx) })
+            .collect()
+    }
+
+    /// All values of string attribute, e.g. keywords, names or text.
+    pub fn strings(&self, name: &CStr) -> Vec<String> {
+        let Some(attribute) = self.find(name) else {
+            return Vec::new();
+        };
+
+        let count = unsafe { ippGetCount(attribute) };
+        (0..count)
+            .filter_map(|idx| unsafe { ippGetString(attribute, idx, null_mut()).as_ref() })
+            .map(|value| {
+                unsafe { CStr::from_ptr(value) }
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect()
+    }
+
+    fn find(&self, name: &CStr) -> Option<*mut ipp_attribute_t> {
+        let attribute = unsafe { ippFindAttribute(self.0, name.as_ptr(), ipp_tag_e_IPP_TAG_ZERO) };
+        (!attribute.is_null()).then_some(attribute)
+    }
+}
+
+impl Drop for Response {
+    fn drop(&mut self) {
+        unsafe { ippDelete(self.0) }
+    }
+}
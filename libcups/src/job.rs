@@ -1,6 +1,145 @@
 use crate::options::OptionsValues;
+use libcups_sys::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    ffi::{c_char, CStr},
+    fmt,
+    time::{Duration, SystemTime},
+};
 
 pub struct Job {
     pub id: i32,
     pub options: OptionsValues,
 }
+
+/// Identifier of the job on CUPS server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct JobId(pub i32);
+
+/// State of the job, see `job-state` in RFC 8011.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobState {
+    Pending,
+    Held,
+    Processing,
+    Stopped,
+    Canceled,
+    Aborted,
+    Completed,
+}
+
+/// Which jobs are listed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WhichJobs {
+    Active,
+    Completed,
+    All,
+}
+
+/// Job from the list of jobs of the printer.
+#[derive(Clone, Debug)]
+pub struct JobInfo {
+    pub id: JobId,
+    pub title: String,
+
+    /// User of CUPS, which created the job.
+    pub user: String,
+
+    pub state: JobState,
+
+    /// Size of the job in kilobytes.
+    pub size: u32,
+
+    /// Pages, which are already printed, if CUPS still keeps the attribute.
+    pub pages: Option<u32>,
+
+    pub created: SystemTime,
+    pub completed: Option<SystemTime>,
+}
+
+/// Status of the job from `Get-Job-Attributes` request.
+#[derive(Clone, Debug)]
+pub struct JobStatus {
+    pub state: JobState,
+
+    /// Keywords from `job-state-reasons`, e.g. `job-printing`.
+    pub reasons: Vec<String>,
+
+    /// Keywords from `job-printer-state-reasons`, e.g. `media-empty-error`.
+    pub printer_reasons: Vec<String>,
+
+    /// Pages, which are already printed.
+    pub impressions_completed: Option<u32>,
+
+    /// Total pages of the job, if printer reports it.
+    pub impressions: Option<u32>,
+}
+
+impl fmt::Display for JobId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl WhichJobs {
+    pub(crate) fn value(self) -> i32 {
+        match self {
+            Self::Active => CUPS_WHICHJOBS_ACTIVE,
+            Self::Completed => CUPS_WHICHJOBS_COMPLETED,
+            Self::All => CUPS_WHICHJOBS_ALL,
+        }
+    }
+}
+
+impl JobInfo {
+    /// Copy the job from the list returned by [`cupsGetJobs2`].
+    ///
+    /// Pages are not listed by CUPS, so they are left unknown.
+    // `time_t` is `i32` on some 32-bit platforms.
+    #[allow(clippy::useless_conversion)]
+    pub(crate) fn from_raw(job: &cups_job_t) -> Option<Self> {
+        Some(Self {
+            id: JobId(job.id),
+            title: unsafe { string(job.title) },
+            user: unsafe { string(job.user) },
+            state: JobState::from_ipp(job.state as i32)?,
+            size: job.size.try_into().unwrap_or_default(),
+            pages: None,
+            created: system_time(job.creation_time.into()).unwrap_or(SystemTime::UNIX_EPOCH),
+            completed: system_time(job.completed_time.into()),
+        })
+    }
+}
+
+impl JobState {
+    pub(crate) fn from_ipp(value: i32) -> Option<Self> {
+        let state = match value as ipp_jstate_t {
+            ipp_jstate_e_IPP_JSTATE_PENDING => Self::Pending,
+            ipp_jstate_e_IPP_JSTATE_HELD => Self::Held,
+            ipp_jstate_e_IPP_JSTATE_PROCESSING => Self::Processing,
+            ipp_jstate_e_IPP_JSTATE_STOPPED => Self::Stopped,
+            ipp_jstate_e_IPP_JSTATE_CANCELED => Self::Canceled,
+            ipp_jstate_e_IPP_JSTATE_ABORTED => Self::Aborted,
+            ipp_jstate_e_IPP_JSTATE_COMPLETED => Self::Completed,
+            _ => return None,
+        };
+
+        Some(state)
+    }
+
+    /// Job will not change its state anymore.
+    pub fn is_finished(self) -> bool {
+        matches!(self, Self::Canceled | Self::Aborted | Self::Completed)
+    }
+}
+
+fn system_time(secs: i64) -> Option<SystemTime> {
+    (secs > 0).then(|| SystemTime::UNIX_EPOCH + Duration::from_secs(secs as u64))
+}
+
+unsafe fn string(value: *const c_char) -> String {
+    match value.as_ref() {
+        Some(value) => CStr::from_ptr(value).to_string_lossy().into_owned(),
+        None => String::new(),
+    }
+}
@@ -1,5 +1,6 @@
 pub mod document;
-mod job;
+mod ipp;
+pub mod job;
 pub mod options;
 pub mod printer;
 mod result;
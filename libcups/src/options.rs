@@ -21,8 +21,12 @@ pub struct Options {
 /// and must lived until job is completed.
 pub struct OptionsValues {
     copies: Option<CString>,
+    page_ranges: Option<CString>,
 }
 
+/// Name of the option, which CUPS doesn't define as a constant.
+const PAGE_RANGES: &CStr = c"page-ranges";
+
 impl Options {
     /// Set paper size for print.
     pub fn media_format(mut self, format: MediaFormat) -> Self {
@@ -83,6 +87,32 @@ impl Options {
         self
     }
 
+    /// Set ranges of pages to print, e.g. `[(1, 3), (5, 5)]`.
+    ///
+    /// Pages are numbered from one and both ends of the range are included.
+    pub fn page_ranges(mut self, ranges: &[(usize, usize)]) -> Self {
+        let ranges = ranges
+            .iter()
+            .map(|(first, last)| format!("{first}-{last}"))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let ranges = self.values.page_ranges.insert(unsafe {
+            CString::from_vec_with_nul_unchecked(format!("{ranges}\0").into_bytes())
+        });
+
+        self.count = unsafe {
+            cupsAddOption(
+                PAGE_RANGES.as_ptr(),
+                ranges.as_ptr(),
+                self.count,
+                &mut self.ptr,
+            )
+        };
+
+        self
+    }
+
     /// Create a new job on CUPS server.
     ///
     /// # Error
@@ -120,7 +150,10 @@ impl Default for Options {
         Self {
             ptr: null_mut(),
             count: 0,
-            values: OptionsValues { copies: None },
+            values: OptionsValues {
+                copies: None,
+                page_ranges: None,
+            },
         }
     }
 }
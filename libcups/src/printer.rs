@@ -1,13 +1,61 @@
-use crate::{document::Document, job::Job, options::Options, utils::cstring_wrapper};
+use crate::{
+    document::Document,
+    ipp,
+    job::{Job, JobId, JobInfo, JobState, JobStatus, WhichJobs},
+    options::Options,
+    result::cups_error,
+    utils::cstring_wrapper,
+};
 use libcups_sys::*;
 use std::{
-    ffi::CStr,
+    ffi::{CStr, CString},
     io,
     ptr::{null, null_mut},
+    slice,
 };
 
 pub struct Printer(&'static mut cups_dest_t);
 
+/// State of the printer, see `printer-state` in RFC 8011.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrinterState {
+    Idle,
+    Processing,
+    Stopped,
+}
+
+/// Supply of the printer, e.g. toner cartridge.
+#[derive(Clone, Debug)]
+pub struct Marker {
+    pub name: String,
+
+    /// Level in percents, if printer reports it.
+    pub level: Option<u8>,
+
+    /// Level in percents, which printer considers low.
+    pub low_level: Option<u8>,
+}
+
+/// Attributes of the printer from `Get-Printer-Attributes` request.
+#[derive(Clone, Debug)]
+pub struct PrinterAttributes {
+    pub state: PrinterState,
+
+    /// Keywords from `printer-state-reasons`, e.g. `media-empty-error`.
+    pub reasons: Vec<String>,
+
+    /// Human readable `printer-state-message`.
+    pub message: Option<String>,
+
+    pub accepting_jobs: bool,
+
+    /// Number of jobs in the queue.
+    pub queued_jobs: u32,
+
+    /// Supplies from `marker-names`, `marker-levels` and `marker-low-levels`.
+    pub markers: Vec<Marker>,
+}
+
 cstring_wrapper! { pub DeviceName }
 cstring_wrapper! { pub JobTitle }
 
@@ -27,15 +75,42 @@ impl Printer {
         unsafe { CStr::from_ptr(self.0.name) }
     }
 
-    /// Send documents for printing.
+    /// Printer URI for IPP requests.
+    pub fn uri(&self) -> CString {
+        let uri = unsafe {
+            cupsGetOption(
+                c"printer-uri-supported".as_ptr(),
+                self.0.num_options,
+                self.0.options,
+            )
+        };
+
+        match unsafe { uri.as_ref() } {
+            Some(uri) => unsafe { CStr::from_ptr(uri) }.to_owned(),
+            None => {
+                let name = self.name().to_string_lossy();
+                CString::new(format!("ipp://localhost/printers/{name}"))
+                    .expect("printer name should not contain nul")
+            }
+        }
+    }
+
+    /// Send documents for printing and return id of the created job.
+    ///
+    /// # Error
+    ///
+    /// Return error if `documents` is empty or the job can't be created.
     pub fn print_documents(
         &self,
         title: JobTitle,
         options: Options,
         documents: Vec<Document>,
-    ) -> io::Result<()> {
+    ) -> io::Result<JobId> {
         if documents.is_empty() {
-            return Ok(());
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no documents to print",
+            ));
         }
 
         let job = self.create_job(&title, options)?;
@@ -61,9 +136,166 @@ impl Printer {
             }
         }
 
+        Ok(JobId(job.id))
+    }
+
+    /// List jobs of the printer with [`cupsGetJobs2`], newest first.
+    ///
+    /// Only `limit` jobs are returned, because printed pages are requested for each job.
+    pub fn jobs(&self, which: WhichJobs, limit: usize) -> io::Result<Vec<JobInfo>> {
+        let mut jobs: *mut cups_job_t = null_mut();
+        let count = unsafe {
+            cupsGetJobs2(
+                null_mut(),
+                &mut jobs,
+                self.name().as_ptr(),
+                0,
+                which.value(),
+            )
+        };
+
+        if count < 0 {
+            return Err(io::Error::other(cups_error().unwrap()));
+        }
+
+        let mut list: Vec<JobInfo> = if count == 0 || jobs.is_null() {
+            Vec::new()
+        } else {
+            unsafe { slice::from_raw_parts(jobs, count as usize) }
+                .iter()
+                .filter_map(JobInfo::from_raw)
+                .collect()
+        };
+
+        unsafe { cupsFreeJobs(count, jobs) };
+
+        list.sort_by_key(|job| std::cmp::Reverse(job.id.0));
+        list.truncate(limit);
+
+        for job in &mut list {
+            job.pages = self
+                .job_status(job.id)
+                .ok()
+                .and_then(|status| status.impressions_completed);
+        }
+
+        Ok(list)
+    }
+
+    /// Query state and supplies of the printer with `Get-Printer-Attributes` request.
+    pub fn attributes(&self) -> io::Result<PrinterAttributes> {
+        let response = ipp::Request::new(ipp_op_e_IPP_OP_GET_PRINTER_ATTRIBUTES)
+            .uri(c"printer-uri", &self.uri())
+            .keywords(
+                c"requested-attributes",
+                &[
+                    c"printer-state",
+                    c"printer-state-reasons",
+                    c"printer-state-message",
+                    c"printer-is-accepting-jobs",
+                    c"queued-job-count",
+                    c"marker-names",
+                    c"marker-levels",
+                    c"marker-low-levels",
+                ],
+            )
+            .send(c"/")?;
+
+        let state = match response
+            .integer(c"printer-state")
+            .map(|s| s as ipp_pstate_t)
+        {
+            Some(ipp_pstate_e_IPP_PSTATE_IDLE) => PrinterState::Idle,
+            Some(ipp_pstate_e_IPP_PSTATE_PROCESSING) => PrinterState::Processing,
+            Some(ipp_pstate_e_IPP_PSTATE_STOPPED) => PrinterState::Stopped,
+            _ => return Err(io::Error::other("printer has no valid state")),
+        };
+
+        // Negative levels mean that the level is unknown or unavailable.
+        let percents = |name| -> Vec<Option<u8>> {
+            response
+                .integers(name)
+                .into_iter()
+                .map(|level| u8::try_from(level).ok())
+                .collect()
+        };
+        let levels = percents(c"marker-levels");
+        let low_levels = percents(c"marker-low-levels");
+
+        let markers = response
+            .strings(c"marker-names")
+            .into_iter()
+            .enumerate()
+            .map(|(idx, name)| Marker {
+                name,
+                level: levels.get(idx).copied().flatten(),
+                low_level: low_levels.get(idx).copied().flatten(),
+            })
+            .collect();
+
+        Ok(PrinterAttributes {
+            state,
+            reasons: response.strings(c"printer-state-reasons"),
+            message: response
+                .strings(c"printer-state-message")
+                .into_iter()
+                .next()
+                .filter(|message| !message.is_empty()),
+            accepting_jobs: response
+                .boolean(c"printer-is-accepting-jobs")
+                .unwrap_or(false),
+            queued_jobs: response
+                .integer(c"queued-job-count")
+                .and_then(|count| u32::try_from(count).ok())
+                .unwrap_or_default(),
+            markers,
+        })
+    }
+
+    /// Cancel the job, which is not finished yet.
+    pub fn cancel_job(&self, job: JobId) -> io::Result<()> {
+        let status = unsafe { cupsCancelJob2(null_mut(), self.name().as_ptr(), job.0, 0) };
+
+        if status >= ipp_status_e_IPP_STATUS_ERROR_BAD_REQUEST {
+            return Err(io::Error::other(cups_error().unwrap()));
+        }
+
         Ok(())
     }
 
+    /// Query state of the job with `Get-Job-Attributes` request.
+    pub fn job_status(&self, job: JobId) -> io::Result<JobStatus> {
+        let response = ipp::Request::new(ipp_op_e_IPP_OP_GET_JOB_ATTRIBUTES)
+            .uri(c"printer-uri", &self.uri())
+            .integer(c"job-id", job.0)
+            .keywords(
+                c"requested-attributes",
+                &[
+                    c"job-state",
+                    c"job-state-reasons",
+                    c"job-printer-state-reasons",
+                    c"job-impressions",
+                    c"job-impressions-completed",
+                ],
+            )
+            .send(c"/")?;
+
+        let state = response
+            .integer(c"job-state")
+            .and_then(JobState::from_ipp)
+            .ok_or_else(|| io::Error::other(format!("job #{job} has no valid state")))?;
+
+        let impressions = |name| response.integer(name).and_then(|n| u32::try_from(n).ok());
+
+        Ok(JobStatus {
+            state,
+            reasons: response.strings(c"job-state-reasons"),
+            printer_reasons: response.strings(c"job-printer-state-reasons"),
+            impressions_completed: impressions(c"job-impressions-completed"),
+            impressions: impressions(c"job-impressions"),
+        })
+    }
+
     fn create_job(&self, title: &CStr, options: Options) -> io::Result<Job> {
         options.create_job(self.name(), title)
     }
@@ -30,10 +30,11 @@ pub(crate) use cstring_wrapper;
 /// Generate new enum with a `value()` method that returns a c-like values.
 ///
 /// The generated enum implements [`std::clone::Clone`], [`std::marker::Copy`],
-/// [`std::fmt::Debug`], [`serde::Serialize`], [`serde::Deserialize`].
+/// [`std::fmt::Debug`], [`std::cmp::PartialEq`], [`std::cmp::Eq`],
+/// [`serde::Serialize`], [`serde::Deserialize`].
 macro_rules! c_enum {
     ($visibility:vis enum $enum_name:ident { $($n:ident: $v:ident),* $(,)? }) => {
-        #[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
         $visibility enum $enum_name {
             $($n),*
         }
@@ -15,6 +15,8 @@ fn bindgen_jpeg() {
 
     bindgen::builder()
         .header("ffi/wrapper.h")
+        // Error handler in the `libjpeg` crate unwinds through these functions.
+        .override_abi(bindgen::Abi::CUnwind, "jpeg_.*")
         .generate()
         .expect("Failed to generate bindings")
         .write_to_file(bindings_path)
@@ -1,8 +1,17 @@
 use libjpeg_sys::{
-    jpeg_CreateCompress, jpeg_common_struct, jpeg_compress_struct, jpeg_destination_mgr,
-    jpeg_destroy_compress, jpeg_error_mgr, jpeg_finish_compress, jpeg_set_defaults,
-    jpeg_set_quality, jpeg_start_compress, jpeg_std_error, jpeg_write_scanlines, JPEG_LIB_VERSION,
-    JPOOL_PERMANENT, J_COLOR_SPACE_JCS_GRAYSCALE, J_COLOR_SPACE_JCS_RGB,
+    j_common_ptr, jpeg_CreateCompress, jpeg_CreateDecompress, jpeg_common_struct,
+    jpeg_compress_struct, jpeg_decompress_struct, jpeg_destination_mgr, jpeg_destroy_compress,
+    jpeg_destroy_decompress, jpeg_error_mgr, jpeg_finish_compress, jpeg_finish_decompress,
+    jpeg_mem_src, jpeg_read_header, jpeg_read_scanlines, jpeg_set_defaults, jpeg_set_quality,
+    jpeg_start_compress, jpeg_start_decompress, jpeg_std_error, jpeg_write_scanlines,
+    JMSG_LENGTH_MAX, JPEG_LIB_VERSION, JPOOL_PERMANENT, J_COLOR_SPACE_JCS_GRAYSCALE,
+    J_COLOR_SPACE_JCS_RGB,
+};
+use std::{
+    ffi::CStr,
+    fmt,
+    os::raw::c_char,
+    panic::{self, AssertUnwindSafe},
 };
 
 pub struct RawImage {
@@ -18,6 +27,18 @@ pub enum RawImageFormat {
     Gray,
 }
 
+/// Ошибка libjpeg, например, при распаковке повреждённого изображения.
+#[derive(Debug)]
+pub struct JpegError(String);
+
+impl fmt::Display for JpegError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "libjpeg: {}", self.0)
+    }
+}
+
+impl std::error::Error for JpegError {}
+
 const JPEG_BLOCK_SIZE: usize = 16 * 1024;
 
 pub fn compress_to_jpeg(image: &RawImage, quality: u8) -> Vec<u8> {
@@ -41,6 +62,44 @@ pub fn compress_to_jpeg(image: &RawImage, quality: u8) -> Vec<u8> {
     jpeg
 }
 
+pub fn decompress_jpeg(jpeg: &[u8]) -> Result<RawImage, JpegError> {
+    let mut jerr = initialize_error_manager();
+    let mut dinfo = initialize_decoder(&mut jerr);
+
+    // Ошибки в данных приходят из `error_exit`, который раскручивает стек до этого места.
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        // SAFETY: `jpeg` живёт дольше, чем `dinfo`.
+        unsafe { jpeg_mem_src(&mut dinfo, jpeg.as_ptr(), jpeg.len() as _) };
+
+        unsafe { jpeg_read_header(&mut dinfo, true as _) };
+
+        let format = if dinfo.num_components == 1 {
+            dinfo.out_color_space = J_COLOR_SPACE_JCS_GRAYSCALE;
+            RawImageFormat::Gray
+        } else {
+            dinfo.out_color_space = J_COLOR_SPACE_JCS_RGB;
+            RawImageFormat::Rgb
+        };
+
+        let pixels = decompress_pixels(&mut dinfo);
+
+        RawImage {
+            pixels,
+            width: dinfo.output_width as usize,
+            height: dinfo.output_height as usize,
+            format,
+        }
+    }));
+
+    // SAFETY: `jpeg_destroy_decompress` освобождает память и после ошибки.
+    unsafe { jpeg_destroy_decompress(&mut dinfo) };
+
+    result.map_err(|payload| match payload.downcast::<JpegError>() {
+        Ok(err) => *err,
+        Err(payload) => panic::resume_unwind(payload),
+    })
+}
+
 fn initialize_error_manager() -> jpeg_error_mgr {
     // SAFETY: `jerr` будет корректно проинициализированно через `jpeg_std_error`.
     let mut jerr: jpeg_error_mgr = unsafe { std::mem::zeroed() };
@@ -48,9 +107,36 @@ fn initialize_error_manager() -> jpeg_error_mgr {
     // SAFETY: Указатель на jpeg_error_mgr корректен.
     unsafe { jpeg_std_error(&mut jerr) };
 
+    // Стандартный `error_exit` завершает весь процесс через `exit()`.
+    // SAFETY: Функции libjpeg объявлены с ABI "C-unwind", поэтому через них можно раскручивать стек.
+    jerr.error_exit = Some(unsafe {
+        std::mem::transmute::<
+            unsafe extern "C-unwind" fn(j_common_ptr),
+            unsafe extern "C" fn(j_common_ptr),
+        >(error_exit)
+    });
+
     jerr
 }
 
+/// Вместо завершения процесса раскручивает стек с `JpegError`.
+///
+/// При сжатии ошибка становится паникой, а при распаковке перехватывается в `decompress_jpeg`.
+unsafe extern "C-unwind" fn error_exit(cinfo: j_common_ptr) {
+    let mut buffer = [0 as c_char; JMSG_LENGTH_MAX as usize];
+
+    let err = cinfo.as_ref().unwrap().err.as_ref().unwrap();
+    if let Some(format_message) = err.format_message {
+        format_message(cinfo, buffer.as_mut_ptr());
+    }
+
+    let message = CStr::from_ptr(buffer.as_ptr())
+        .to_string_lossy()
+        .into_owned();
+
+    panic::resume_unwind(Box::new(JpegError(message)));
+}
+
 fn initialize_encoder(jerr: &mut jpeg_error_mgr, jpeg: &mut Vec<u8>) -> jpeg_compress_struct {
     // SAFETY: `cinfo` будет корректно проинициализировано через `jpeg_CreateCompress`.
     let mut cinfo: jpeg_compress_struct = unsafe { std::mem::zeroed() };
@@ -72,6 +158,24 @@ fn initialize_encoder(jerr: &mut jpeg_error_mgr, jpeg: &mut Vec<u8>) -> jpeg_com
     cinfo
 }
 
+fn initialize_decoder(jerr: &mut jpeg_error_mgr) -> jpeg_decompress_struct {
+    // SAFETY: `dinfo` будет корректно проинициализировано через `jpeg_CreateDecompress`.
+    let mut dinfo: jpeg_decompress_struct = unsafe { std::mem::zeroed() };
+
+    dinfo.err = jerr;
+
+    // SAFETY: Указатель на `jpeg_decompress_struct` корректен.
+    unsafe {
+        jpeg_CreateDecompress(
+            &mut dinfo,
+            JPEG_LIB_VERSION as _,
+            std::mem::size_of::<jpeg_decompress_struct>(),
+        )
+    };
+
+    dinfo
+}
+
 fn setup_destination(cinfo: &mut jpeg_compress_struct) {
     // SAFETY: поле `mem` должно быть корректно проинициализированно до вызова функции.
     let mem = unsafe { cinfo.mem.as_ref() }.expect("jpeg_compress_struct.mem should be non-null");
@@ -165,3 +269,20 @@ fn compress_pixels(cinfo: &mut jpeg_compress_struct, pixels: &[u8]) {
 
     unsafe { jpeg_finish_compress(cinfo) };
 }
+
+fn decompress_pixels(dinfo: &mut jpeg_decompress_struct) -> Vec<u8> {
+    unsafe { jpeg_start_decompress(dinfo) };
+
+    let row_size = (dinfo.output_width * dinfo.output_components as u32) as usize;
+    let mut pixels = vec![0u8; row_size * dinfo.output_height as usize];
+
+    while dinfo.output_scanline < dinfo.output_height {
+        let mut row = pixels[dinfo.output_scanline as usize * row_size..].as_mut_ptr();
+
+        unsafe { jpeg_read_scanlines(dinfo, &mut row, 1) };
+    }
+
+    unsafe { jpeg_finish_decompress(dinfo) };
+
+    pixels
+}
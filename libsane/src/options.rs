@@ -71,9 +71,8 @@ pub enum Constraint<'a> {
         range: ops::RangeInclusive<i32>,
         quant: i32,
     },
-    WordList {
-        // TODO: Support constraint
-    },
+    /// Values of `Int` or `Fixed` option. Values of `Fixed` option are not converted.
+    WordList(Vec<i32>),
     StringList(Vec<&'a BStr>),
 }
 
@@ -253,7 +252,13 @@ impl<'a> Constraint<'a> {
                 }
             }
             SANE_Constraint_Type_SANE_CONSTRAINT_WORD_LIST => {
-                Self::WordList { /* TODO: Unsupported constraint */ }
+                // The first word of the list is the number of values.
+                let list = unsafe { constraint.word_list };
+                let len = unsafe { *list } as usize;
+
+                let values = (1..=len).map(|i| unsafe { *list.add(i) }).collect();
+
+                Self::WordList(values)
             }
             SANE_Constraint_Type_SANE_CONSTRAINT_STRING_LIST => {
                 let mut values = (0..usize::MAX)
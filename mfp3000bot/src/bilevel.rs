@@ -0,0 +1,113 @@
+use crate::color::to_gray;
+use anyhow::bail;
+use fax::{encoder::Encoder, Color, VecWriter};
+use libjpeg::{RawImage, RawImageFormat};
+
+/// Black-and-white image compressed with CCITT Group 4.
+pub struct Bilevel {
+    pub bytes: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Gray level that splits pixels of binarized image into black and white.
+const MIDDLE_GRAY: u8 = 128;
+
+/// Convert image to black-and-white gray image.
+///
+/// Threshold is chosen with Otsu's method, so it adapts to the paper and ink brightness.
+pub fn binarize(image: &mut RawImage) {
+    to_gray(image);
+
+    let threshold = otsu_threshold(&image.pixels);
+    log::debug!("Binarize page with threshold {threshold}");
+
+    for pixel in &mut image.pixels {
+        *pixel = if *pixel > threshold { u8::MAX } else { 0 };
+    }
+}
+
+/// Check, if the image is already binarized gray image.
+pub fn is_bilevel(image: &RawImage) -> bool {
+    let RawImageFormat::Gray = image.format else {
+        return false;
+    };
+
+    image
+        .pixels
+        .iter()
+        .all(|&pixel| pixel == 0 || pixel == u8::MAX)
+}
+
+/// Compress binarized gray image with CCITT Group 4.
+pub fn compress_g4(image: &RawImage) -> anyhow::Result<Bilevel> {
+    let Ok(width) = u16::try_from(image.width) else {
+        bail!("page width {} is too large for CCITT G4", image.width);
+    };
+
+    let mut encoder = Encoder::new(VecWriter::new());
+
+    for row in image.pixels.chunks_exact(image.width) {
+        let pels = row.iter().map(|&pixel| {
+            if pixel < MIDDLE_GRAY {
+                Color::Black
+            } else {
+                Color::White
+            }
+        });
+
+        encoder
+            .encode_line(pels, width)
+            .expect("writing to vector should not fail");
+    }
+
+    let writer = encoder.finish().expect("writing to vector should not fail");
+
+    Ok(Bilevel {
+        bytes: writer.finish(),
+        width: image.width,
+        height: image.height,
+    })
+}
+
+fn otsu_threshold(pixels: &[u8]) -> u8 {
+    let mut histogram = [0u64; 256];
+    for &pixel in pixels {
+        histogram[pixel as usize] += 1;
+    }
+
+    let total = pixels.len() as f64;
+    let sum: f64 = (0..256).map(|i| i as f64 * histogram[i] as f64).sum();
+
+    let mut background_weight = 0.0;
+    let mut background_sum = 0.0;
+    let mut best_variance = 0.0;
+    let mut threshold = MIDDLE_GRAY;
+
+    for (level, &count) in histogram.iter().enumerate() {
+        background_weight += count as f64;
+        if background_weight == 0.0 {
+            continue;
+        }
+
+        let foreground_weight = total - background_weight;
+        if foreground_weight == 0.0 {
+            break;
+        }
+
+        background_sum += level as f64 * count as f64;
+
+        let background_mean = background_sum / background_weight;
+        let foreground_mean = (sum - background_sum) / foreground_weight;
+
+        let variance =
+            background_weight * foreground_weight * (background_mean - foreground_mean).powi(2);
+
+        if variance > best_variance {
+            best_variance = variance;
+            threshold = level as u8;
+        }
+    }
+
+    threshold
+}
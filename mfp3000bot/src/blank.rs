@@ -0,0 +1,49 @@
+use crate::color::{components, luminance};
+use libjpeg::RawImage;
+
+/// Share of the page near each edge, which is not analyzed,
+/// because it often has shadows from the page border.
+const MARGIN: f32 = 0.05;
+
+/// Pixels darker than this share of the paper brightness are ink.
+const INK_LEVEL: f32 = 0.6;
+
+/// Share of the ink pixels on the page without margins.
+///
+/// Blank pages have only a few dark pixels from dust and JPEG noise.
+pub fn ink_coverage(image: &RawImage) -> f32 {
+    let components = components(image.format);
+
+    let margin_x = (image.width as f32 * MARGIN) as usize;
+    let margin_y = (image.height as f32 * MARGIN) as usize;
+
+    let mut histogram = [0usize; 256];
+    for y in margin_y..image.height - margin_y {
+        let row = &image.pixels[y * image.width * components..(y + 1) * image.width * components];
+        for pixel in row[margin_x * components..(image.width - margin_x) * components]
+            .chunks_exact(components)
+        {
+            histogram[luminance(pixel) as usize] += 1;
+        }
+    }
+
+    let total: usize = histogram.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
+
+    // Paper is the brightest large part of the page.
+    let mut count = 0;
+    let paper = (0..256)
+        .rev()
+        .find(|&level| {
+            count += histogram[level];
+            count * 2 >= total
+        })
+        .unwrap_or(0);
+
+    let ink_level = (paper as f32 * INK_LEVEL) as usize;
+    let ink: usize = histogram[..ink_level].iter().sum();
+
+    ink as f32 / total as f32
+}
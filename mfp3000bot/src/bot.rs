@@ -1,28 +1,59 @@
 use crate::{
+    bilevel,
     bot_data::*,
+    bot_storage::{self, DialogueStorage, PrintJobOwners},
     bot_utils::*,
-    config::Config,
-    pdf_builder::PdfBuilder,
-    print::{self, DocumentFormat},
-    scan::{self, Jpeg, ScanState},
+    config::{self, Config, PdfConformance, ScanPreset, ScanProfile},
+    contact_sheet::ContactSheet,
+    destination::{self, Content, Destination, ScanInfo},
+    enhance,
+    ocr::{self, PageText},
+    orientation::{self, Rotation},
+    pdf_builder::{PageImage, PdfBuilder},
+    png,
+    print::{self, DocumentFormat, PrintOptions},
+    scan::{self, Jpeg, OptionChoices, PageProcessing, ScanSettings, ScanState, ScannedPage},
+    spool::{self, Pages, SpooledPage},
+    supplies::SupplyMonitor,
+    tiff::TiffWriter,
 };
+use anyhow::Context;
+use libcups::{
+    job::{JobId, JobInfo, JobState, JobStatus, WhichJobs},
+    printer::PrinterAttributes,
+};
+use libjpeg::RawImage;
 use reqwest::Url;
-use std::{future::Future, io, str::FromStr, sync::Arc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    future::Future,
+    io::{self, Cursor, Write},
+    path::PathBuf,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use teloxide::{
-    dispatching::{
-        dialogue::{self, InMemStorage},
-        UpdateHandler,
-    },
+    dispatching::{dialogue, UpdateHandler},
     prelude::*,
-    types::{Document, InputFile},
+    types::{Document, InputFile, InputMedia, InputMediaPhoto, PhotoSize, User},
     utils::command::BotCommands,
 };
 use tokio::sync::{oneshot, Mutex};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
 
-pub type BotDialogue = Dialogue<BotState, InMemStorage<BotState>>;
+pub type BotDialogue = Dialogue<BotState, DialogueStorage>;
 
 pub struct Globals {
     config: Config,
+
+    /// Шрифт текстового слоя загружается, поэтому можно собрать PDF с текстом.
+    text_layer: bool,
+
+    /// Пользователи, отправившие задачи печати.
+    print_jobs: PrintJobOwners,
 }
 
 #[derive(BotCommands, Clone)]
@@ -31,148 +62,730 @@ pub enum BotCommand {
     Start,
     Help,
     Print,
-    Scan,
+    Queue,
+    Status,
+    Scan(String),
+    Copy,
 }
 
-#[derive(Clone, Default)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub enum BotState {
     #[default]
     Empty,
 
+    /// Выбор настроек печати отправленного документа.
+    SelectPrintOptions {
+        dialogue_message: Message,
+        document: PrintDocument,
+        options: PrintOptions,
+    },
+
+    /// Получение диапазона страниц для печати.
+    ReceivePrintPageRanges {
+        dialogue_message: Message,
+        document: PrintDocument,
+        options: PrintOptions,
+    },
+
+    /// Выбор настроек печати отсканированной копии.
+    SelectCopyOptions {
+        dialogue_message: Message,
+        pages: Pages,
+        settings: ScanSettings,
+        options: PrintOptions,
+    },
+
+    /// Выбор пресета сканирования из конфига.
+    SelectScanPreset { dialogue_message: Message },
+
     /// Выбор режима сканирования: страницу или многостраничный документ.  
-    SelectScanMode { dialogue_message: Message },
+    SelectScanMode {
+        dialogue_message: Message,
+        settings: ScanSettings,
+    },
 
     /// Выбор первого действия при сканировании.
     /// Это состояние универсально для всех режимов сканирования.
     SelectFirstScanAction {
         dialogue_message: Message,
         mode: ScanMode,
+        settings: ScanSettings,
+    },
+
+    /// Меню настроек сканирования.
+    SelectScanSetting {
+        dialogue_message: Message,
+        mode: ScanMode,
+        settings: ScanSettings,
+        choices: OptionChoices,
+    },
+
+    /// Выбор значения настройки сканирования.
+    SelectScanSettingValue {
+        dialogue_message: Message,
+        mode: ScanMode,
+        settings: ScanSettings,
+        choices: OptionChoices,
+        setting: ScanSetting,
     },
 
     /// Сканирование страницы.
     /// Это состояние универсально для всех режимов сканирования.
-    ScanningPage { cancel: ScanCancellationToken },
+    ScanningPage {
+        /// Сканирование не переживает перезапуск бота.
+        #[serde(skip)]
+        cancel: ScanCancellationToken,
+
+        /// Документ, к которому вернётся диалог после перезапуска бота.
+        document: Option<(Pages, ScanSettings)>,
+    },
 
     /// Получение имени для отсканированной страницы.
+    /// Страница хранится на диске в отдельном спуле.
     ReceiveScannedPageName {
         dialogue_message: Message,
-        page: Page,
+        page: Pages,
+        format: OutputFormat,
+        destination: Option<String>,
     },
 
     /// Выбор действия для документа.
     SelectDocumentAction {
         dialogue_message: Message,
         pages: Pages,
+        settings: ScanSettings,
+    },
+
+    /// Выбор страницы документа для изменения.
+    SelectDocumentPage {
+        dialogue_message: Message,
+        pages: Pages,
+        settings: ScanSettings,
+    },
+
+    /// Выбор действия для страницы документа.
+    SelectPageAction {
+        dialogue_message: Message,
+        pages: Pages,
+        settings: ScanSettings,
+        page: usize,
     },
 
     /// Подтверждение отмены сканирования и удаления отсканированных страниц.
     ConfirmDropScannedDocument {
         dialogue_message: Message,
         pages: Pages,
+        settings: ScanSettings,
+    },
+
+    /// Выбор обработки страниц документа.
+    SelectDocumentEnhancement {
+        dialogue_message: Message,
+        pages: Pages,
+        settings: ScanSettings,
+        with_text: bool,
     },
 
     /// Получение имени для отсканированного документа.
     ReceiveScannedDocumentName {
         dialogue_message: Message,
         pages: Pages,
+        settings: ScanSettings,
+        choice: DocumentChoice,
     },
 }
 
+impl BotState {
+    /// Сообщение с кнопками текущего состояния.
+    fn dialogue_message(&self) -> Option<&Message> {
+        match self {
+            BotState::Empty | BotState::ScanningPage { .. } => None,
+            BotState::SelectPrintOptions {
+                dialogue_message, ..
+            }
+            | BotState::ReceivePrintPageRanges {
+                dialogue_message, ..
+            }
+            | BotState::SelectCopyOptions {
+                dialogue_message, ..
+            }
+            | BotState::SelectScanPreset { dialogue_message }
+            | BotState::SelectScanMode {
+                dialogue_message, ..
+            }
+            | BotState::SelectFirstScanAction {
+                dialogue_message, ..
+            }
+            | BotState::SelectScanSetting {
+                dialogue_message, ..
+            }
+            | BotState::SelectScanSettingValue {
+                dialogue_message, ..
+            }
+            | BotState::ReceiveScannedPageName {
+                dialogue_message, ..
+            }
+            | BotState::SelectDocumentAction {
+                dialogue_message, ..
+            }
+            | BotState::SelectDocumentPage {
+                dialogue_message, ..
+            }
+            | BotState::SelectPageAction {
+                dialogue_message, ..
+            }
+            | BotState::ConfirmDropScannedDocument {
+                dialogue_message, ..
+            }
+            | BotState::SelectDocumentEnhancement {
+                dialogue_message, ..
+            }
+            | BotState::ReceiveScannedDocumentName {
+                dialogue_message, ..
+            } => Some(dialogue_message),
+        }
+    }
+
+    /// Спул со страницами, которые хранятся в состоянии.
+    fn pages(&self) -> Option<&Pages> {
+        match self {
+            BotState::SelectCopyOptions { pages, .. }
+            | BotState::SelectDocumentAction { pages, .. }
+            | BotState::SelectDocumentPage { pages, .. }
+            | BotState::SelectPageAction { pages, .. }
+            | BotState::ConfirmDropScannedDocument { pages, .. }
+            | BotState::SelectDocumentEnhancement { pages, .. }
+            | BotState::ReceiveScannedDocumentName { pages, .. }
+            | BotState::ReceiveScannedPageName { page: pages, .. }
+            | BotState::ScanningPage {
+                document: Some((pages, _)),
+                ..
+            } => Some(pages),
+            _ => None,
+        }
+    }
+}
+
 pub type ScanCancellationToken = Arc<Mutex<Option<oneshot::Sender<()>>>>;
-pub type Page = Jpeg;
-pub type Pages = Vec<Jpeg>;
+pub type Page = ScannedPage;
+
+/// Документ из чата, который ожидает печати.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PrintDocument {
+    name: String,
+    format: DocumentFormat,
+    url: String,
+}
+
+/// Выбор пользователя для итогового документа.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DocumentChoice {
+    with_text: bool,
+    archival: bool,
+    enhancement: Enhancement,
+    format: OutputFormat,
+    destination: Option<String>,
+}
+
+/// Параметры итогового документа.
+struct DocumentOptions {
+    name: String,
+    author: String,
+    user: String,
+    with_text: bool,
+    archival: bool,
+    enhancement: Enhancement,
+    format: OutputFormat,
+    destination: Option<String>,
+    by_email: bool,
+}
+
+/// Куда доставляется скан.
+enum Delivery<'a> {
+    /// В чат и, если выбрано, в место сохранения из конфига.
+    Chat(Option<(&'a str, &'a config::Destination)>),
+
+    /// Только на почту пользователя.
+    Email(&'a config::Smtp),
+}
+
+impl DocumentOptions {
+    fn new(name: &str, user: Option<&User>, choice: DocumentChoice) -> Self {
+        Self {
+            name: name.to_owned(),
+            author: user.map(|user| user.full_name()).unwrap_or_default(),
+            user: user.map(user_name).unwrap_or_default(),
+            with_text: choice.with_text,
+            archival: choice.archival,
+            enhancement: choice.enhancement,
+            format: choice.format,
+            destination: choice.destination,
+            by_email: false,
+        }
+    }
+}
 
 enum ScanResult {
     Done(Page),
+    Blank,
     Cancelled,
     Error(anyhow::Error),
 }
 
-pub async fn start(config: Config) {
+pub async fn start(config: Config) -> anyhow::Result<()> {
     let bot = Bot::new(&config.telegram.token);
 
-    let globals = Arc::new(Globals { config });
+    let (storage, chats) = bot_storage::open(config.telegram.dialogues.as_deref())
+        .await
+        .context("opening dialogue storage")?;
+
+    // Спулы диалогов, потерянных при перезапуске, больше никто не удалит.
+    let mut used_spools = HashSet::new();
+    for &chat_id in &chats {
+        let dialogue = BotDialogue::new(storage.clone(), chat_id);
+        if let Some(pages) = dialogue.get().await?.as_ref().and_then(BotState::pages) {
+            used_spools.insert(pages.dir().to_owned());
+        }
+    }
+    spool::remove_unused(&config.scan.spool_dir, &used_spools);
+
+    let print_jobs = PrintJobOwners::open(config.telegram.dialogues.as_deref())
+        .await
+        .context("opening print job owners")?;
+
+    // Задачи, завершившиеся пока бот был остановлен, больше не отслеживаются.
+    if let Some(printer) = config.devices.printer.clone() {
+        let jobs: Vec<JobId> = print_jobs.lock().await.keys().copied().collect();
+        let finished: HashSet<JobId> = tokio::task::spawn_blocking(move || {
+            jobs.into_iter()
+                .filter(|&job| match print::job_status(&printer, job) {
+                    Ok(status) => status.state.is_finished(),
+                    Err(_) => false,
+                })
+                .collect()
+        })
+        .await
+        .unwrap();
+
+        print_jobs.retain(|job| !finished.contains(job)).await;
+    }
+
+    let text_layer = match &config.scan.ocr_font {
+        Some(font) => match PdfBuilder::new("").text_font(font) {
+            Ok(_) => true,
+            Err(err) => {
+                log::error!("Failed to load font for text layer: {err:#}");
+                false
+            }
+        },
+        None => false,
+    };
+
+    let globals = Arc::new(Globals {
+        config,
+        text_layer,
+        print_jobs,
+    });
+
+    for chat_id in chats {
+        let dialogue = BotDialogue::new(storage.clone(), chat_id);
+        if let Err(err) = resume_dialogue(globals.clone(), bot.clone(), dialogue).await {
+            log::error!("Failed to resume dialogue in chat {chat_id}: {err:#}");
+        }
+    }
+
+    let config = &globals.config;
+    if let (Some(supplies), Some(printer)) = (&config.supplies, &config.devices.printer) {
+        if config.telegram.admin_chats.is_empty() {
+            log::warn!("Supplies are not monitored, because admin chats are not specified");
+        } else {
+            tokio::spawn(monitor_supplies(
+                bot.clone(),
+                printer.clone(),
+                supplies.clone(),
+                config
+                    .telegram
+                    .admin_chats
+                    .iter()
+                    .copied()
+                    .map(ChatId)
+                    .collect(),
+            ));
+        }
+    }
 
     Dispatcher::builder(bot, schema())
-        .dependencies(dptree::deps![InMemStorage::<BotState>::new(), globals])
+        .dependencies(dptree::deps![storage, globals])
         .enable_ctrlc_handler()
         .build()
         .dispatch()
         .await;
+
+    Ok(())
+}
+
+/// Периодически читает уровень расходных материалов принтера
+/// и предупреждает администраторов, когда он становится низким.
+async fn monitor_supplies(
+    bot: Bot,
+    printer: String,
+    config: config::Supplies,
+    admin_chats: Vec<ChatId>,
+) {
+    let mut monitor = SupplyMonitor::new(config.threshold);
+    // Tokio panics on zero period.
+    let period = Duration::from_secs(config.interval_minutes.max(1) * 60);
+    let mut interval = tokio::time::interval(period);
+
+    loop {
+        interval.tick().await;
+
+        let attributes = {
+            let printer = printer.clone();
+            tokio::task::spawn_blocking(move || print::printer_attributes(&printer))
+                .await
+                .unwrap()
+        };
+
+        let attributes = match attributes {
+            Ok(attributes) => attributes,
+            Err(err) => {
+                log::error!("Failed to read supply levels: {err:#}");
+                continue;
+            }
+        };
+
+        for marker in monitor.check(&attributes.markers) {
+            let level = marker.level.unwrap_or_default();
+            log::info!("Supply '{}' is low: {level}%", marker.name);
+
+            let text = SUPPLY_LOW(&marker.name, level);
+            for &chat_id in &admin_chats {
+                if let Err(err) = send_msg(&bot, chat_id, &text).await {
+                    log::error!("Failed to send supply alert to chat {chat_id}: {err:#}");
+                }
+            }
+        }
+    }
+}
+
+/// Продолжает диалог, сохранённый до перезапуска бота, новым сообщением
+/// с текущими кнопками, так как кнопки в старых сообщениях могли устареть.
+async fn resume_dialogue(
+    globals: Arc<Globals>,
+    bot: Bot,
+    dialogue: BotDialogue,
+) -> anyhow::Result<()> {
+    let Some(state) = dialogue.get().await? else {
+        return Ok(());
+    };
+
+    let message = match state {
+        BotState::Empty => return Ok(()),
+        BotState::ScanningPage { document: None, .. } => {
+            dialogue.update(BotState::Empty).await?;
+            return send_msg(&bot, dialogue.chat_id(), SCAN_INTERRUPTED).await;
+        }
+        _ => {
+            // Кнопки старого сообщения остались от состояния до перезапуска.
+            if let Some(old_message) = state.dialogue_message() {
+                let result = bot
+                    .edit_message_reply_markup(old_message.chat.id, old_message.id)
+                    .await;
+
+                if let Err(err) = result {
+                    log::warn!("Failed to remove buttons of the old dialogue message: {err:#}");
+                }
+            }
+
+            bot.send_message(dialogue.chat_id(), SESSION_RESTORED)
+                .await?
+        }
+    };
+
+    log::info!("Resume dialogue in chat {}", dialogue.chat_id());
+
+    match state {
+        BotState::Empty => unreachable!(),
+        BotState::SelectPrintOptions {
+            document, options, ..
+        }
+        | BotState::ReceivePrintPageRanges {
+            document, options, ..
+        } => {
+            show_print_options(bot, dialogue, Some(message), document, options).await?;
+        }
+        BotState::SelectCopyOptions {
+            pages,
+            settings,
+            options,
+            ..
+        } => {
+            show_copy_options(bot, dialogue, message, (pages, settings), options).await?;
+        }
+        BotState::SelectScanPreset { .. } => {
+            show_scan_preset_selector(&globals, bot, dialogue, message).await?;
+        }
+        BotState::SelectScanMode { settings, .. } => {
+            show_scan_mode_selector(bot, dialogue, Some(message), settings).await?;
+        }
+        BotState::SelectFirstScanAction { mode, settings, .. } => {
+            show_scan_action_selector(bot, dialogue, Some(message), mode, settings).await?;
+        }
+        BotState::SelectScanSetting {
+            mode,
+            settings,
+            choices,
+            ..
+        }
+        | BotState::SelectScanSettingValue {
+            mode,
+            settings,
+            choices,
+            ..
+        } => {
+            show_scan_settings_menu(bot, dialogue, message, mode, settings, choices).await?;
+        }
+        BotState::ScanningPage { document, .. } => {
+            let (pages, settings) = document.expect("scan without document is interrupted");
+            show_document_action_selector(&globals, bot, dialogue, Some(message), pages, settings)
+                .await?;
+        }
+        BotState::ReceiveScannedPageName {
+            page,
+            format,
+            destination,
+            ..
+        } => {
+            show_rename_page_dialog(&globals, bot, dialogue, message, page, format, destination)
+                .await?;
+        }
+        BotState::SelectDocumentAction {
+            pages, settings, ..
+        }
+        | BotState::SelectDocumentPage {
+            pages, settings, ..
+        }
+        | BotState::SelectPageAction {
+            pages, settings, ..
+        }
+        | BotState::ConfirmDropScannedDocument {
+            pages, settings, ..
+        } => {
+            show_document_action_selector(&globals, bot, dialogue, Some(message), pages, settings)
+                .await?;
+        }
+        BotState::SelectDocumentEnhancement {
+            pages,
+            settings,
+            with_text,
+            ..
+        } => {
+            show_enhancement_selector(&globals, bot, dialogue, message, pages, settings, with_text)
+                .await?;
+        }
+        BotState::ReceiveScannedDocumentName {
+            pages,
+            settings,
+            choice,
+            ..
+        } => {
+            show_rename_document_dialog(
+                &globals,
+                bot,
+                dialogue,
+                (message, pages, settings),
+                choice,
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
 }
 
 fn schema() -> UpdateHandler<anyhow::Error> {
     use dptree::case;
 
     let command_handler = teloxide::filter_command::<BotCommand, _>()
+        .branch(case![BotCommand::Queue].endpoint(show_print_queue))
+        .branch(case![BotCommand::Status].endpoint(show_devices_status))
         .branch(
             case![BotState::Empty]
                 .branch(case![BotCommand::Start].endpoint(hello))
                 .branch(case![BotCommand::Help].endpoint(help))
                 .branch(case![BotCommand::Print].endpoint(print_document_help))
-                .branch(case![BotCommand::Scan].endpoint(start_scan)),
+                .branch(case![BotCommand::Scan(preset)].endpoint(start_scan))
+                .branch(case![BotCommand::Copy].endpoint(start_copy)),
         )
         .endpoint(bot_busy);
 
     let message_handler = Update::filter_message()
         .filter_async(filter_users)
         .branch(command_handler)
-        .branch(dptree::filter(|msg: Message| msg.document().is_some()).endpoint(print_document))
+        .branch(
+            dptree::filter(|msg: Message| msg.document().is_some() || msg.photo().is_some())
+                .branch(case![BotState::Empty].endpoint(print_document))
+                .endpoint(bot_busy),
+        )
+        .branch(
+            case![BotState::ReceivePrintPageRanges {
+                dialogue_message,
+                document,
+                options
+            }]
+            .endpoint(receive_print_page_ranges),
+        )
         .branch(
             case![BotState::ReceiveScannedPageName {
                 dialogue_message,
-                page
+                page,
+                format,
+                destination
             }]
             .endpoint(receive_page_name),
         )
         .branch(
             case![BotState::ReceiveScannedDocumentName {
                 dialogue_message,
-                pages
+                pages,
+                settings,
+                choice
             }]
             .endpoint(receive_document_name),
         );
 
+    // Кнопка отмены печати остаётся на старых сообщениях, поэтому проверяется до состояний.
     let callback_query_handler = Update::filter_callback_query()
-        .branch(case![BotState::SelectScanMode { dialogue_message }].endpoint(select_scan_mode))
+        .branch(
+            dptree::filter_map(|q: CallbackQuery| q.data.as_deref().and_then(parse_cancel_job))
+                .endpoint(cancel_print_job),
+        )
+        .branch(
+            case![BotState::SelectCopyOptions {
+                dialogue_message,
+                pages,
+                settings,
+                options
+            }]
+            .endpoint(receive_copy_action),
+        )
+        .branch(
+            case![BotState::SelectPrintOptions {
+                dialogue_message,
+                document,
+                options
+            }]
+            .endpoint(receive_print_action),
+        )
+        .branch(
+            case![BotState::ReceivePrintPageRanges {
+                dialogue_message,
+                document,
+                options
+            }]
+            .endpoint(receive_print_page_ranges_action),
+        )
+        .branch(case![BotState::SelectScanPreset { dialogue_message }].endpoint(select_scan_preset))
+        .branch(
+            case![BotState::SelectScanMode {
+                dialogue_message,
+                settings
+            }]
+            .endpoint(select_scan_mode),
+        )
         .branch(
             case![BotState::SelectFirstScanAction {
                 dialogue_message,
-                mode
+                mode,
+                settings
             }]
             .endpoint(first_scan_action_selected),
         )
+        .branch(
+            case![BotState::SelectScanSetting {
+                dialogue_message,
+                mode,
+                settings,
+                choices
+            }]
+            .endpoint(receive_scan_setting),
+        )
+        .branch(
+            case![BotState::SelectScanSettingValue {
+                dialogue_message,
+                mode,
+                settings,
+                choices,
+                setting
+            }]
+            .endpoint(receive_scan_setting_value),
+        )
         .branch(
             case![BotState::SelectDocumentAction {
                 dialogue_message,
-                pages
+                pages,
+                settings
             }]
             .endpoint(receive_multipage_scan_action_selection),
         )
-        .branch(case![BotState::ScanningPage { cancel }].endpoint(receive_scan_cancellation))
+        .branch(
+            case![BotState::SelectDocumentPage {
+                dialogue_message,
+                pages,
+                settings
+            }]
+            .endpoint(receive_document_page),
+        )
+        .branch(
+            case![BotState::SelectPageAction {
+                dialogue_message,
+                pages,
+                settings,
+                page
+            }]
+            .endpoint(receive_page_action),
+        )
+        .branch(
+            case![BotState::ScanningPage { cancel, document }].endpoint(receive_scan_cancellation),
+        )
         .branch(
             case![BotState::ConfirmDropScannedDocument {
                 dialogue_message,
-                pages
+                pages,
+                settings
             }]
             .endpoint(receive_scan_cancel_confirmation),
         )
         .branch(
             case![BotState::ReceiveScannedPageName {
                 dialogue_message,
-                page
+                page,
+                format,
+                destination
             }]
-            .endpoint(receive_page_rename_cancel),
+            .endpoint(receive_page_rename_action),
+        )
+        .branch(
+            case![BotState::SelectDocumentEnhancement {
+                dialogue_message,
+                pages,
+                settings,
+                with_text
+            }]
+            .endpoint(receive_document_enhancement),
         )
         .branch(
             case![BotState::ReceiveScannedDocumentName {
                 dialogue_message,
-                pages
+                pages,
+                settings,
+                choice
             }]
-            .endpoint(receive_document_rename_cancel),
+            .endpoint(receive_document_rename_action),
         );
 
-    dialogue::enter::<Update, InMemStorage<BotState>, BotState, _>()
+    dialogue::enter::<Update, DialogueStorage, BotState, _>()
         .branch(message_handler)
         .branch(callback_query_handler)
 }
@@ -204,7 +817,7 @@ async fn help(bot: Bot, msg: Message) -> anyhow::Result<()> {
     send_msg(&bot, msg.chat.id, HELP).await
 }
 
-/// Любая команда, когда бот не находится в состоянии Empty.
+/// Любая команда или документ, когда бот не находится в состоянии Empty.
 async fn bot_busy(bot: Bot, msg: Message) -> anyhow::Result<()> {
     send_msg(&bot, msg.chat.id, BOT_BUSY).await
 }
@@ -214,236 +827,2011 @@ async fn print_document_help(bot: Bot, msg: Message) -> anyhow::Result<()> {
     send_msg(&bot, msg.chat.id, PRINT_COMMAND_TEXT).await
 }
 
-/// Отправка документа в чат.
-async fn print_document(globals: Arc<Globals>, bot: Bot, msg: Message) -> anyhow::Result<()> {
-    let Some(printer) = globals.config.devices.printer.as_deref() else {
+/// Команда `/queue`.
+///
+/// Команда ничего не меняет, поэтому доступна и во время сканирования.
+async fn show_print_queue(globals: Arc<Globals>, bot: Bot, msg: Message) -> anyhow::Result<()> {
+    let Some(printer) = globals.config.devices.printer.clone() else {
         return send_msg(&bot, msg.chat.id, NO_PRINTER_IN_CFG).await;
     };
 
-    let document = msg
-        .document()
-        .expect("Message must have document attachment");
+    let jobs = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
+        let active = print::list_jobs(&printer, WhichJobs::Active, QUEUE_ACTIVE_JOBS)?;
+        let completed = print::list_jobs(&printer, WhichJobs::Completed, QUEUE_COMPLETED_JOBS)?;
+        Ok((active, completed))
+    })
+    .await
+    .unwrap();
 
-    let Some((name, format, url)) = get_document(&globals, &bot, document).await? else {
-        return send_msg(&bot, msg.chat.id, UNSUPPORTED_DOCUMENT).await;
+    let (active, completed) = match jobs {
+        Ok(jobs) => jobs,
+        Err(err) => {
+            log::error!("Failed to list print jobs: {err:#}");
+            return send_msg(&bot, msg.chat.id, FAILED_TO_READ_QUEUE).await;
+        }
     };
 
-    match print::print_remote_file(printer, &name, format, &url, &globals.config.print) {
-        Ok(()) => {
-            log::debug!("Document '{name}' successfully printed");
-            send_msg(&bot, msg.chat.id, &SUCCESSFUL_PRINT(&name)).await?;
-        }
+    let text = {
+        let print_jobs = globals.print_jobs.lock().await;
+        PRINT_QUEUE(
+            &queue_text(&print_jobs, &active),
+            &queue_text(&print_jobs, &completed),
+        )
+    };
 
-        // TODO: Отправлять в сообщение человекочитаемую ошибку печати.
-        Err(err) => {
-            log::error!("Failed to print document '{name}': {err:#}");
-            send_msg(&bot, msg.chat.id, &FAILED_TO_PRINT(&name)).await?;
-        }
+    send_msg(&bot, msg.chat.id, &text).await
+}
+
+/// Список задач для `/queue`. Для отслеживаемых задач вместо пользователя CUPS
+/// указывается пользователь Telegram.
+fn queue_text(print_jobs: &HashMap<JobId, String>, jobs: &[JobInfo]) -> String {
+    if jobs.is_empty() {
+        return QUEUE_EMPTY.to_owned();
     }
 
-    Ok(())
+    jobs.iter()
+        .map(|job| {
+            let user = print_jobs.get(&job.id).unwrap_or(&job.user);
+            let state = match job.pages {
+                Some(pages) if pages > 0 => QUEUE_JOB_PAGES(JOB_STATE_NAME(job.state), pages),
+                _ => JOB_STATE_NAME(job.state).to_owned(),
+            };
+
+            QUEUE_JOB(job.id, &job.title, user, &state)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
-/// Запрашивает информацию о файле, проверяет расширение и
-/// возвращает имя документа и ссылку на него.
+/// Команда `/status`.
 ///
-/// Возвращает None, если документ не поддерживается.
-async fn get_document(
-    globals: &Globals,
-    bot: &Bot,
-    doc: &Document,
-) -> anyhow::Result<Option<(String, DocumentFormat, Url)>> {
-    let file = bot.get_file(&doc.file.id).await?;
-
-    let Some(file_name) = doc.file_name.clone() else {
-        return Ok(None);
+/// Команда ничего не меняет, поэтому доступна и во время сканирования,
+/// но сканер тогда будет недоступен, так как уже открыт.
+async fn show_devices_status(globals: Arc<Globals>, bot: Bot, msg: Message) -> anyhow::Result<()> {
+    let (printer, scanner) = tokio::task::spawn_blocking(move || {
+        let config = &globals.config;
+        let printer = config
+            .devices
+            .printer
+            .as_deref()
+            .map(print::printer_attributes);
+        let scanner = config
+            .devices
+            .scanner
+            .is_some()
+            .then(|| scan::check_scanner(config));
+
+        (printer, scanner)
+    })
+    .await
+    .unwrap();
+
+    let printer = match printer {
+        Some(Ok(attributes)) => printer_status_text(&attributes),
+        Some(Err(err)) => {
+            log::error!("Failed to get printer status: {err:#}");
+            DEVICE_UNAVAILABLE.to_owned()
+        }
+        None => DEVICE_NOT_IN_CFG.to_owned(),
     };
 
-    let Some(format) = DocumentFormat::from_document_name(&file_name) else {
-        return Ok(None);
+    let scanner = match scanner {
+        Some(Ok(())) => DEVICE_AVAILABLE,
+        Some(Err(err)) => {
+            log::error!("Failed to open scanner: {err:#}");
+            DEVICE_UNAVAILABLE
+        }
+        None => DEVICE_NOT_IN_CFG,
     };
 
-    // TODO: Оформить PR в Teloxide и убрать ручную сборку ссылки.
-    let token = &globals.config.telegram.token;
-    let file_path = file.path;
-    let file_url = bot
-        .api_url()
-        .join(&format!("file/bot{token}/{file_path}"))
-        .expect("url should be valid");
-
-    Ok(Some((file_name, format, file_url)))
+    send_msg(&bot, msg.chat.id, &DEVICES_STATUS(&printer, scanner)).await
 }
 
-/// Команда `/scan`.
-async fn start_scan(bot: Bot, dialogue: BotDialogue) -> anyhow::Result<()> {
-    let dialogue_message =
-        send_interative(&bot, &dialogue, SELECT_SCAN_MODE, &*SCAN_MODE_BUTTONS).await?;
+fn printer_status_text(attributes: &PrinterAttributes) -> String {
+    let mut lines = vec![PRINTER_STATE_NAME(attributes.state).to_owned()];
 
-    dialogue
-        .update(BotState::SelectScanMode { dialogue_message })
-        .await?;
+    if !attributes.accepting_jobs {
+        lines.push(PRINTER_NOT_ACCEPTING_JOBS.to_owned());
+    }
 
-    Ok(())
-}
+    lines.extend(
+        printer_errors(&attributes.reasons)
+            .iter()
+            .map(|problem| PRINTER_PROBLEM(problem)),
+    );
 
-async fn select_scan_mode(
-    bot: Bot,
-    dialogue: BotDialogue,
-    q: CallbackQuery,
-    dialogue_message: Message, // From `State::SelectScanMode`.
-) -> anyhow::Result<()> {
-    let Some(mode) = q.data else {
-        return Ok(());
-    };
+    if let Some(message) = &attributes.message {
+        lines.push(PRINTER_MESSAGE(message));
+    }
+
+    lines.push(PRINTER_QUEUE_LENGTH(attributes.queued_jobs));
+
+    if !attributes.markers.is_empty() {
+        lines.push(PRINTER_SUPPLIES.to_owned());
+    }
+
+    for marker in &attributes.markers {
+        let line = match (marker.level, marker.low_level) {
+            (Some(level), Some(low)) if level <= low => {
+                MARKER_LOW_LEVEL(&marker.name, &format!("{level}%"))
+            }
+            (Some(level), _) => MARKER_LEVEL(&marker.name, &format!("{level}%")),
+            (None, _) => MARKER_LEVEL(&marker.name, MARKER_LEVEL_UNKNOWN),
+        };
+        lines.push(line);
+    }
+
+    lines.join("\n")
+}
+
+/// Отправка документа в чат.
+async fn print_document(
+    globals: Arc<Globals>,
+    bot: Bot,
+    dialogue: BotDialogue,
+    msg: Message,
+) -> anyhow::Result<()> {
+    if globals.config.devices.printer.is_none() {
+        return send_msg(&bot, msg.chat.id, NO_PRINTER_IN_CFG).await;
+    }
+
+    let document = match (msg.document(), msg.photo()) {
+        (Some(document), _) => get_document(&globals, &bot, document).await?,
+        (None, Some(photo)) => get_photo(&globals, &bot, photo).await?,
+        (None, None) => unreachable!("Message must have document or photo attachment"),
+    };
+
+    let Some((name, format, url)) = document else {
+        return send_msg(&bot, msg.chat.id, UNSUPPORTED_DOCUMENT).await;
+    };
+
+    let document = PrintDocument {
+        name,
+        format,
+        url: url.into(),
+    };
+
+    let options = PrintOptions::from(&globals.config.print);
+
+    show_print_options(bot, dialogue, None, document, options).await
+}
+
+async fn show_print_options(
+    bot: Bot,
+    dialogue: BotDialogue,
+    dialogue_message: Option<Message>,
+    document: PrintDocument,
+    options: PrintOptions,
+) -> anyhow::Result<()> {
+    let value = |value: Option<&str>| value.unwrap_or(PRINTER_DEFAULT_SETTING);
+    let paper = options.paper_size.map(|paper| format!("{paper:?}"));
+    let pages = if options.page_ranges.is_empty() {
+        ALL_PAGES.to_owned()
+    } else {
+        format_page_ranges(&options.page_ranges)
+    };
+
+    let mut text = PRINT_OPTIONS(
+        &document.name,
+        options.copies,
+        value(options.sides.map(SIDES_NAME)),
+        value(options.color_mode.map(COLOR_NAME)),
+        value(paper.as_deref()),
+        value(options.quality.map(QUALITY_NAME)),
+        &pages,
+    );
+
+    let buttons: &[_] = if document.format.is_image() {
+        text = format!(
+            "{text}\n{}",
+            PRINT_IMAGE_FIT(IMAGE_FIT_NAME(options.image_fit))
+        );
+        &*PRINT_IMAGE_OPTION_BUTTONS
+    } else {
+        &*PRINT_OPTION_BUTTONS
+    };
+
+    let dialogue_message = if let Some(message) = dialogue_message {
+        edit_interative(&bot, &message, &text, buttons).await?
+    } else {
+        send_interative(&bot, &dialogue, &text, buttons).await?
+    };
+
+    dialogue
+        .update(BotState::SelectPrintOptions {
+            dialogue_message,
+            document,
+            options,
+        })
+        .await?;
+
+    Ok(())
+}
+
+async fn receive_print_action(
+    globals: Arc<Globals>,
+    bot: Bot,
+    dialogue: BotDialogue,
+    q: CallbackQuery,
+    (dialogue_message, document, mut options): (Message, PrintDocument, PrintOptions), // From `State::SelectPrintOptions`.
+) -> anyhow::Result<()> {
+    let Some(action) = q.data else {
+        return Ok(());
+    };
+
+    let Ok(action) = PrintAction::from_str(&action) else {
+        return ignore_stale_button(&bot, &q.id).await;
+    };
+
+    match action {
+        // Telegram не даёт отредактировать сообщение без изменений.
+        PrintAction::LessCopies if options.copies == 1 => return Ok(()),
+        PrintAction::MoreCopies if options.copies == MAX_COPIES => return Ok(()),
+        PrintAction::LessCopies => options.copies -= 1,
+        PrintAction::MoreCopies => options.copies += 1,
+        PrintAction::Sides => options.sides = next_choice(&SIDES_CHOICES, options.sides),
+        PrintAction::Color => {
+            options.color_mode = next_choice(&COLOR_CHOICES, options.color_mode);
+        }
+        PrintAction::Paper => {
+            options.paper_size = next_choice(&PAPER_CHOICES, options.paper_size);
+        }
+        PrintAction::Quality => {
+            options.quality = next_choice(&PRINT_QUALITY_CHOICES, options.quality);
+        }
+        PrintAction::ImageFit => {
+            options.image_fit = next_choice(&IMAGE_FIT_CHOICES, Some(options.image_fit))
+                .unwrap_or(IMAGE_FIT_CHOICES[0]);
+        }
+        PrintAction::Pages => {
+            let buttons: [(&str, (usize, &str)); 1] =
+                [(PrintAction::AllPages.into(), (0, ALL_PAGES_BUTTON))];
+            let dialogue_message =
+                edit_interative(&bot, &dialogue_message, ENTER_PAGE_RANGES, &buttons).await?;
+
+            dialogue
+                .update(BotState::ReceivePrintPageRanges {
+                    dialogue_message,
+                    document,
+                    options,
+                })
+                .await?;
+
+            return Ok(());
+        }
+        PrintAction::Cancel => {
+            edit_msg(&bot, &dialogue_message, PRINT_CANCELLED).await?;
+            dialogue.update(BotState::Empty).await?;
+            return Ok(());
+        }
+        PrintAction::Print => {
+            edit_msg(&bot, &dialogue_message, &PRINT_PROGRESS(&document.name)).await?;
+            dialogue.update(BotState::Empty).await?;
+            return send_to_printer(
+                &globals,
+                &bot,
+                (&dialogue_message, &q.from),
+                &document,
+                &options,
+            )
+            .await;
+        }
+        PrintAction::AllPages => return ignore_stale_button(&bot, &q.id).await,
+    }
+
+    show_print_options(bot, dialogue, Some(dialogue_message), document, options).await
+}
+
+async fn receive_print_page_ranges(
+    bot: Bot,
+    dialogue: BotDialogue,
+    msg: Message,
+    (dialogue_message, document, mut options): (Message, PrintDocument, PrintOptions), // From `State::ReceivePrintPageRanges`.
+) -> anyhow::Result<()> {
+    let Some(page_ranges) = msg.text().and_then(print::parse_page_ranges) else {
+        return send_msg(&bot, msg.chat.id, INVALID_PAGE_RANGES).await;
+    };
+
+    options.page_ranges = page_ranges;
+
+    show_print_options(bot, dialogue, Some(dialogue_message), document, options).await
+}
+
+async fn receive_print_page_ranges_action(
+    bot: Bot,
+    dialogue: BotDialogue,
+    q: CallbackQuery,
+    (dialogue_message, document, mut options): (Message, PrintDocument, PrintOptions), // From `State::ReceivePrintPageRanges`.
+) -> anyhow::Result<()> {
+    let Some(action) = q.data else {
+        return Ok(());
+    };
+
+    let Ok(PrintAction::AllPages) = PrintAction::from_str(&action) else {
+        return ignore_stale_button(&bot, &q.id).await;
+    };
+
+    options.page_ranges.clear();
+
+    show_print_options(bot, dialogue, Some(dialogue_message), document, options).await
+}
+
+async fn send_to_printer(
+    globals: &Arc<Globals>,
+    bot: &Bot,
+    (dialogue_message, user): (&Message, &User),
+    document: &PrintDocument,
+    options: &PrintOptions,
+) -> anyhow::Result<()> {
+    let printer = globals
+        .config
+        .devices
+        .printer
+        .as_deref()
+        .expect("printer should be checked before print dialog");
+
+    let name = &document.name;
+    let result = Url::parse(&document.url)
+        .context("parsing document url")
+        .and_then(|url| print::print_remote_file(printer, name, document.format, &url, options));
+
+    match result {
+        Ok(job) => {
+            log::debug!("Document '{name}' successfully sent to print as job #{job}");
+            start_print_tracking(globals, bot, (dialogue_message, user), printer, name, job)
+                .await?;
+        }
+
+        // TODO: Отправлять в сообщение человекочитаемую ошибку печати.
+        Err(err) => {
+            log::error!("Failed to print document '{name}': {err:#}");
+            edit_msg(bot, dialogue_message, &FAILED_TO_PRINT(name)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Показывает кнопку отмены печати и запускает отслеживание задачи.
+async fn start_print_tracking(
+    globals: &Arc<Globals>,
+    bot: &Bot,
+    (message, user): (&Message, &User),
+    printer: &str,
+    name: &str,
+    job: JobId,
+) -> anyhow::Result<()> {
+    let data = format!("{CANCEL_PRINT_JOB_PREFIX}{job}");
+    let buttons = [(data.as_str(), (0, CANCEL_PRINT_JOB_BUTTON))];
+    edit_interative(bot, message, &PRINT_JOB_QUEUED(name), &buttons).await?;
+
+    globals.print_jobs.insert(job, user_name(user)).await;
+
+    tokio::spawn(track_print_job(
+        globals.clone(),
+        bot.clone(),
+        message.clone(),
+        printer.to_owned(),
+        name.to_owned(),
+        job,
+    ));
+
+    Ok(())
+}
+
+/// Обновляет сообщение о печати, пока задача не завершится.
+async fn track_print_job(
+    globals: Arc<Globals>,
+    bot: Bot,
+    message: Message,
+    printer: String,
+    name: String,
+    job: JobId,
+) {
+    // Незавершённую задачу отправитель ещё может отменить.
+    if watch_print_job(&bot, &message, &printer, &name, job).await {
+        globals.print_jobs.remove(job).await;
+    }
+}
+
+/// Возвращает, завершилась ли задача.
+async fn watch_print_job(
+    bot: &Bot,
+    message: &Message,
+    printer: &str,
+    name: &str,
+    job: JobId,
+) -> bool {
+    let started = Instant::now();
+    let mut last_text = PRINT_JOB_QUEUED(name);
+
+    let data = format!("{CANCEL_PRINT_JOB_PREFIX}{job}");
+    let buttons = [(data.as_str(), (0, CANCEL_PRINT_JOB_BUTTON))];
+
+    let mut failures = 0;
+
+    loop {
+        // После каждой ошибки запроса интервал удваивается.
+        tokio::time::sleep(print::JOB_POLL_INTERVAL * 2u32.pow(failures)).await;
+
+        let status = {
+            let printer = printer.to_owned();
+            tokio::task::spawn_blocking(move || print::job_status(&printer, job))
+                .await
+                .unwrap()
+        };
+
+        let status = match status {
+            Ok(status) => {
+                failures = 0;
+                status
+            }
+            Err(err) if failures < print::JOB_STATUS_RETRIES => {
+                log::warn!("Failed to query print job #{job}, retrying: {err:#}");
+                failures += 1;
+                continue;
+            }
+            Err(err) => {
+                log::error!("Failed to track print job #{job}: {err:#}");
+                stop_print_tracking(bot, message, name, &buttons).await;
+                return false;
+            }
+        };
+
+        let text = print_job_text(name, &status);
+        if text != last_text {
+            // Кнопка отмены убирается, когда задача завершена.
+            let result = if status.state.is_finished() {
+                edit_msg(bot, message, &text).await
+            } else {
+                edit_interative(bot, message, &text, &buttons)
+                    .await
+                    .map(|_| ())
+            };
+
+            if let Err(err) = result {
+                log::error!("Failed to update status of print job #{job}: {err:#}");
+            }
+            last_text = text;
+        }
+
+        if status.state.is_finished() {
+            log::debug!("Print job #{job} finished with state {:?}", status.state);
+            return true;
+        }
+
+        if started.elapsed() > print::JOB_TRACKING_TIMEOUT {
+            log::info!("Stop tracking print job #{job} in state {:?}", status.state);
+            stop_print_tracking(bot, message, name, &buttons).await;
+            return false;
+        }
+    }
+}
+
+/// Сообщает, что статус задачи больше не обновляется.
+///
+/// Задача может быть ещё в очереди, поэтому кнопка отмены остаётся.
+async fn stop_print_tracking(
+    bot: &Bot,
+    message: &Message,
+    name: &str,
+    buttons: &[(&str, (usize, &str))],
+) {
+    if let Err(err) = edit_interative(bot, message, &PRINT_JOB_UNTRACKED(name), buttons).await {
+        log::error!("Failed to update status of untracked print job: {err:#}");
+    }
+}
+
+fn parse_cancel_job(data: &str) -> Option<JobId> {
+    let job = data.strip_prefix(CANCEL_PRINT_JOB_PREFIX)?;
+    job.parse().ok().map(JobId)
+}
+
+/// Кнопка отмены печати в сообщении о задаче.
+///
+/// Задачу может отменить отправивший её пользователь или администратор.
+/// Отправители хранятся вместе с диалогами, а без базы диалогов после перезапуска
+/// бота отменить задачу может только администратор.
+async fn cancel_print_job(
+    globals: Arc<Globals>,
+    bot: Bot,
+    q: CallbackQuery,
+    job: JobId,
+) -> anyhow::Result<()> {
+    let Some(message) = q.message else {
+        return Ok(());
+    };
+
+    let Some(printer) = globals.config.devices.printer.clone() else {
+        return send_msg(&bot, message.chat.id, NO_PRINTER_IN_CFG).await;
+    };
+
+    let user = user_name(&q.from);
+    let tracked = globals.print_jobs.get(job).await;
+    let is_admin = globals.config.telegram.admins.contains(&user);
+
+    if tracked.as_ref() != Some(&user) && !is_admin {
+        log::info!("User {user} is trying to cancel print job #{job}");
+        return send_msg(&bot, message.chat.id, CANCEL_PRINT_JOB_FORBIDDEN).await;
+    }
+
+    let result = tokio::task::spawn_blocking(move || print::cancel_job(&printer, job))
+        .await
+        .unwrap();
+
+    match result {
+        Ok(()) => {
+            log::info!("Print job #{job} cancelled by {user}");
+
+            // Отслеживаемое сообщение обновит задача отслеживания.
+            if tracked.is_none() {
+                edit_msg(&bot, &message, PRINT_CANCELLED).await?;
+            }
+        }
+        Err(err) => {
+            log::error!("Failed to cancel print job #{job}: {err:#}");
+            send_msg(&bot, message.chat.id, FAILED_TO_CANCEL_PRINT_JOB).await?;
+        }
+    }
+
+    Ok(())
+}
+
+fn print_job_text(name: &str, status: &JobStatus) -> String {
+    let error = printer_errors(&status.printer_reasons).into_iter().next();
+
+    match (status.state, error) {
+        (JobState::Completed, _) => PRINT_JOB_DONE(name),
+        (JobState::Canceled, _) => PRINT_JOB_CANCELED(name),
+        (JobState::Aborted, error) => {
+            PRINT_JOB_ERROR(name, error.as_deref().unwrap_or(PRINT_JOB_ABORTED))
+        }
+        (JobState::Stopped, error) => {
+            PRINT_JOB_ERROR(name, error.as_deref().unwrap_or(PRINTER_STOPPED))
+        }
+        (_, Some(error)) => PRINT_JOB_ERROR(name, &error),
+        (JobState::Pending | JobState::Held, None) => PRINT_JOB_QUEUED(name),
+        (JobState::Processing, None) => match (status.impressions_completed, status.impressions) {
+            (Some(done), Some(total)) if total > 0 => PRINT_JOB_PROGRESS(name, done, total),
+            (Some(done), _) if done > 0 => PRINT_JOB_PAGES(name, done),
+            _ => PRINT_JOB_PRINTING(name),
+        },
+    }
+}
+
+/// Описания ошибок принтера из `printer-state-reasons`.
+/// Предупреждения и уведомления пропускаются.
+fn printer_errors(reasons: &[String]) -> Vec<String> {
+    reasons
+        .iter()
+        .filter_map(|reason| {
+            if reason.ends_with("-warning") || reason.ends_with("-report") {
+                return None;
+            }
+
+            let keyword = reason.strip_suffix("-error").unwrap_or(reason);
+            match PRINTER_STATE_REASONS
+                .iter()
+                .find(|(name, _)| *name == keyword)
+            {
+                Some((_, text)) => Some((*text).to_owned()),
+                None if keyword != reason => Some(reason.clone()),
+                None => None,
+            }
+        })
+        .collect()
+}
+
+/// Диапазоны страниц для пользователя, например `1-3, 5`.
+fn format_page_ranges(ranges: &[(usize, usize)]) -> String {
+    ranges
+        .iter()
+        .map(|&(first, last)| {
+            if first == last {
+                first.to_string()
+            } else {
+                format!("{first}-{last}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Следующее значение настройки печати. После последнего значения
+/// настройка сбрасывается на значение принтера.
+fn next_choice<T: Copy + PartialEq>(choices: &[T], value: Option<T>) -> Option<T> {
+    match value.and_then(|value| choices.iter().position(|choice| *choice == value)) {
+        Some(idx) => choices.get(idx + 1).copied(),
+        None => choices.first().copied(),
+    }
+}
+
+/// Запрашивает информацию о файле, проверяет расширение и
+/// возвращает имя документа и ссылку на него.
+///
+/// Возвращает None, если документ не поддерживается.
+async fn get_document(
+    globals: &Globals,
+    bot: &Bot,
+    doc: &Document,
+) -> anyhow::Result<Option<(String, DocumentFormat, Url)>> {
+    let file = bot.get_file(&doc.file.id).await?;
+
+    let Some(file_name) = doc.file_name.clone() else {
+        return Ok(None);
+    };
+
+    let Some(format) = DocumentFormat::from_document_name(&file_name) else {
+        return Ok(None);
+    };
+
+    Ok(Some((
+        file_name,
+        format,
+        file_url(globals, bot, &file.path),
+    )))
+}
+
+/// Запрашивает информацию о фото наибольшего размера и возвращает ссылку на него.
+///
+/// Telegram всегда пересжимает фото в JPEG.
+async fn get_photo(
+    globals: &Globals,
+    bot: &Bot,
+    photo: &[PhotoSize],
+) -> anyhow::Result<Option<(String, DocumentFormat, Url)>> {
+    let Some(photo) = photo.iter().max_by_key(|photo| photo.width * photo.height) else {
+        return Ok(None);
+    };
+
+    let file = bot.get_file(&photo.file.id).await?;
+
+    let name = DEFAULT_PHOTO_NAME.to_owned();
+    Ok(Some((
+        name,
+        DocumentFormat::Jpeg,
+        file_url(globals, bot, &file.path),
+    )))
+}
+
+fn file_url(globals: &Globals, bot: &Bot, file_path: &str) -> Url {
+    // TODO: Оформить PR в Teloxide и убрать ручную сборку ссылки.
+    let token = &globals.config.telegram.token;
+    bot.api_url()
+        .join(&format!("file/bot{token}/{file_path}"))
+        .expect("url should be valid")
+}
+
+/// Команда `/scan`, после которой может быть указано имя пресета.
+async fn start_scan(
+    globals: Arc<Globals>,
+    bot: Bot,
+    dialogue: BotDialogue,
+    msg: Message,
+    preset: String,
+) -> anyhow::Result<()> {
+    let presets = &globals.config.scan_presets;
+
+    let preset = preset.trim();
+    if !preset.is_empty() {
+        if !presets.contains_key(preset) {
+            return send_msg(&bot, msg.chat.id, &UNKNOWN_SCAN_PRESET(preset)).await;
+        }
+
+        let settings = ScanSettings {
+            preset: Some(preset.to_owned()),
+            ..Default::default()
+        };
+
+        return show_scan_mode_selector(bot, dialogue, None, settings).await;
+    }
+
+    if presets.is_empty() {
+        return show_scan_mode_selector(bot, dialogue, None, ScanSettings::default()).await;
+    }
+
+    let dialogue_message = bot.send_message(msg.chat.id, SELECT_SCAN_PRESET).await?;
+
+    show_scan_preset_selector(&globals, bot, dialogue, dialogue_message).await
+}
+
+/// Команда `/copy`: сканирование без пресета с печатью вместо отправки в чат.
+async fn start_copy(
+    globals: Arc<Globals>,
+    bot: Bot,
+    dialogue: BotDialogue,
+    msg: Message,
+) -> anyhow::Result<()> {
+    if globals.config.devices.printer.is_none() {
+        return send_msg(&bot, msg.chat.id, NO_PRINTER_IN_CFG).await;
+    }
+
+    let settings = ScanSettings {
+        copy: true,
+        ..Default::default()
+    };
+
+    show_scan_mode_selector(bot, dialogue, None, settings).await
+}
+
+async fn show_copy_options(
+    bot: Bot,
+    dialogue: BotDialogue,
+    dialogue_message: Message,
+    (pages, settings): (Pages, ScanSettings),
+    options: PrintOptions,
+) -> anyhow::Result<()> {
+    let sides = options
+        .sides
+        .map(SIDES_NAME)
+        .unwrap_or(PRINTER_DEFAULT_SETTING);
+    let text = COPY_OPTIONS(pages.len(), options.copies, sides);
+
+    let dialogue_message =
+        edit_interative(&bot, &dialogue_message, &text, &*COPY_OPTION_BUTTONS).await?;
+
+    dialogue
+        .update(BotState::SelectCopyOptions {
+            dialogue_message,
+            pages,
+            settings,
+            options,
+        })
+        .await?;
+
+    Ok(())
+}
+
+async fn receive_copy_action(
+    globals: Arc<Globals>,
+    bot: Bot,
+    dialogue: BotDialogue,
+    q: CallbackQuery,
+    (dialogue_message, pages, settings, mut options): (Message, Pages, ScanSettings, PrintOptions), // From `State::SelectCopyOptions`.
+) -> anyhow::Result<()> {
+    let Some(action) = q.data else {
+        return Ok(());
+    };
+
+    let Ok(action) = PrintAction::from_str(&action) else {
+        return ignore_stale_button(&bot, &q.id).await;
+    };
+
+    match action {
+        // Telegram не даёт отредактировать сообщение без изменений.
+        PrintAction::LessCopies if options.copies == 1 => return Ok(()),
+        PrintAction::MoreCopies if options.copies == MAX_COPIES => return Ok(()),
+        PrintAction::LessCopies => options.copies -= 1,
+        PrintAction::MoreCopies => options.copies += 1,
+        PrintAction::Sides => options.sides = next_choice(&SIDES_CHOICES, options.sides),
+        PrintAction::Cancel => {
+            pages.delete();
+            edit_msg(&bot, &dialogue_message, SCAN_CANCELLED).await?;
+            dialogue.update(BotState::Empty).await?;
+            return Ok(());
+        }
+        PrintAction::Print => {
+            dialogue.update(BotState::Empty).await?;
+            return print_copy(
+                &globals,
+                &bot,
+                (&dialogue_message, &q.from),
+                (pages, settings),
+                &options,
+            )
+            .await;
+        }
+        PrintAction::Color
+        | PrintAction::Paper
+        | PrintAction::Quality
+        | PrintAction::Pages
+        | PrintAction::AllPages
+        | PrintAction::ImageFit => return ignore_stale_button(&bot, &q.id).await,
+    }
+
+    show_copy_options(bot, dialogue, dialogue_message, (pages, settings), options).await
+}
+
+/// Собирает PDF из отсканированных страниц и отправляет его на печать,
+/// продолжая показывать статус в том же сообщении.
+async fn print_copy(
+    globals: &Arc<Globals>,
+    bot: &Bot,
+    (dialogue_message, user): (&Message, &User),
+    (pages, settings): (Pages, ScanSettings),
+    options: &PrintOptions,
+) -> anyhow::Result<()> {
+    let printer = globals
+        .config
+        .devices
+        .printer
+        .clone()
+        .expect("printer should be checked before copy");
+
+    edit_msg(bot, dialogue_message, SCAN_PREPARE_PDF).await?;
+
+    let document = DocumentOptions::new(
+        DEFAULT_COPY_NAME,
+        Some(user),
+        DocumentChoice {
+            with_text: false,
+            archival: false,
+            enhancement: Enhancement::Original,
+            format: OutputFormat::Pdf,
+            destination: None,
+        },
+    );
+
+    let result = tokio::task::spawn_blocking({
+        let config = scan_config(globals, &settings);
+        let pages = pages.clone();
+        let printer = printer.clone();
+        let options = options.clone();
+        move || {
+            let path = convert_pages_to_document(&config.scan, &document, &pages, None)?;
+            print::print_pdf_file(&printer, DEFAULT_COPY_NAME, &path, &options)
+        }
+    })
+    .await
+    .unwrap();
+
+    pages.delete();
+
+    match result {
+        Ok(job) => {
+            log::debug!("Copy successfully sent to print as job #{job}");
+            start_print_tracking(
+                globals,
+                bot,
+                (dialogue_message, user),
+                &printer,
+                DEFAULT_COPY_NAME,
+                job,
+            )
+            .await?;
+        }
+        Err(err) => {
+            log::error!("Failed to print copy: {err:#}");
+            edit_msg(bot, dialogue_message, &FAILED_TO_PRINT(DEFAULT_COPY_NAME)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn show_scan_preset_selector(
+    globals: &Globals,
+    bot: Bot,
+    dialogue: BotDialogue,
+    dialogue_message: Message,
+) -> anyhow::Result<()> {
+    let presets = &globals.config.scan_presets;
+
+    let mut buttons: Vec<(&str, (usize, &str))> = presets
+        .iter()
+        .enumerate()
+        .map(|(i, (name, preset))| {
+            let title = preset.title.as_deref().unwrap_or(name);
+            (name.as_str(), (i, title))
+        })
+        .collect();
+
+    buttons.push((
+        ScanPresetAction::NoPreset.into(),
+        (presets.len(), NO_SCAN_PRESET_BUTTON),
+    ));
+
+    let dialogue_message =
+        edit_interative(&bot, &dialogue_message, SELECT_SCAN_PRESET, &buttons).await?;
+
+    dialogue
+        .update(BotState::SelectScanPreset { dialogue_message })
+        .await?;
+
+    Ok(())
+}
+
+async fn select_scan_preset(
+    globals: Arc<Globals>,
+    bot: Bot,
+    dialogue: BotDialogue,
+    q: CallbackQuery,
+    dialogue_message: Message, // From `State::SelectScanPreset`.
+) -> anyhow::Result<()> {
+    let Some(preset) = q.data else {
+        return Ok(());
+    };
+
+    let preset = if globals.config.scan_presets.contains_key(&preset) {
+        Some(preset)
+    } else if let Ok(ScanPresetAction::NoPreset) = ScanPresetAction::from_str(&preset) {
+        None
+    } else {
+        return ignore_stale_button(&bot, &q.id).await;
+    };
+
+    let settings = ScanSettings {
+        preset,
+        ..Default::default()
+    };
+
+    show_scan_mode_selector(bot, dialogue, Some(dialogue_message), settings).await
+}
+
+async fn show_scan_mode_selector(
+    bot: Bot,
+    dialogue: BotDialogue,
+    dialogue_message: Option<Message>,
+    settings: ScanSettings,
+) -> anyhow::Result<()> {
+    let mut text = match &settings.preset {
+        Some(preset) => format!("{}\n{SELECT_SCAN_MODE}", SCAN_PRESET(preset)),
+        None => SELECT_SCAN_MODE.to_owned(),
+    };
+    if settings.copy {
+        text = format!("{COPY_MODE}\n{text}");
+    }
+
+    let dialogue_message = if let Some(message) = dialogue_message {
+        edit_interative(&bot, &message, &text, &*SCAN_MODE_BUTTONS).await?
+    } else {
+        send_interative(&bot, &dialogue, &text, &*SCAN_MODE_BUTTONS).await?
+    };
+
+    dialogue
+        .update(BotState::SelectScanMode {
+            dialogue_message,
+            settings,
+        })
+        .await?;
+
+    Ok(())
+}
+
+async fn select_scan_mode(
+    bot: Bot,
+    dialogue: BotDialogue,
+    q: CallbackQuery,
+    (dialogue_message, settings): (Message, ScanSettings), // From `State::SelectScanMode`.
+) -> anyhow::Result<()> {
+    let Some(mode) = q.data else {
+        return Ok(());
+    };
+
+    let Ok(mode) = ScanMode::from_str(&mode) else {
+        return ignore_stale_button(&bot, &q.id).await;
+    };
+
+    show_scan_action_selector(bot, dialogue, Some(dialogue_message), mode, settings).await?;
+
+    Ok(())
+}
+
+async fn show_scan_action_selector(
+    bot: Bot,
+    dialogue: BotDialogue,
+    dialogue_message: Option<Message>,
+    mode: ScanMode,
+    settings: ScanSettings,
+) -> anyhow::Result<()> {
+    let dialogue_message = if let Some(message) = dialogue_message {
+        edit_interative(&bot, &message, SELECT_SCAN_ACTION, &*SCAN_ACTIONS_BUTTONS).await?
+    } else {
+        send_interative(&bot, &dialogue, SELECT_SCAN_ACTION, &*SCAN_ACTIONS_BUTTONS).await?
+    };
+
+    dialogue
+        .update(BotState::SelectFirstScanAction {
+            dialogue_message,
+            mode,
+            settings,
+        })
+        .await?;
+
+    Ok(())
+}
+
+async fn first_scan_action_selected(
+    globals: Arc<Globals>,
+    bot: Bot,
+    dialogue: BotDialogue,
+    q: CallbackQuery,
+    (dialogue_message, mode, settings): (Message, ScanMode, ScanSettings), // From `State::SelectFirstScanAction`.
+) -> anyhow::Result<()> {
+    let Some(action) = q.data else {
+        return Ok(());
+    };
+
+    let Ok(action) = ScanAction::from_str(&action) else {
+        return ignore_stale_button(&bot, &q.id).await;
+    };
+
+    match action {
+        ScanAction::Scan => {
+            scan_first_page(globals, bot, dialogue, dialogue_message, mode, settings).await?;
+        }
+        ScanAction::Preview => {
+            scan_first_page_preview(globals, bot, dialogue, dialogue_message, mode, settings)
+                .await?;
+        }
+        ScanAction::Settings => {
+            show_scan_settings(globals, bot, dialogue, dialogue_message, mode, settings).await?;
+        }
+        ScanAction::Cancel => {
+            edit_msg(&bot, &dialogue_message, SCAN_CANCELLED).await?;
+            dialogue.update(BotState::Empty).await?;
+        }
+        // Кнопки меню документа со старых сообщений.
+        _ => return ignore_stale_button(&bot, &q.id).await,
+    }
+
+    Ok(())
+}
+
+/// Открывает меню настроек, читая допустимые значения опций сканера.
+async fn show_scan_settings(
+    globals: Arc<Globals>,
+    bot: Bot,
+    dialogue: BotDialogue,
+    dialogue_message: Message,
+    mode: ScanMode,
+    settings: ScanSettings,
+) -> anyhow::Result<()> {
+    edit_msg(&bot, &dialogue_message, SCAN_SETTINGS_LOADING).await?;
+
+    let choices = tokio::task::spawn_blocking(move || scan::read_option_choices(&globals.config))
+        .await
+        .unwrap();
+
+    match choices {
+        Ok(choices) => {
+            show_scan_settings_menu(bot, dialogue, dialogue_message, mode, settings, choices)
+                .await?;
+        }
+        Err(err) => {
+            log::error!("Failed to read scanner options: {err:#}");
+            edit_msg(&bot, &dialogue_message, SCAN_ERROR).await?;
+            show_scan_action_selector(bot, dialogue, None, mode, settings).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn show_scan_settings_menu(
+    bot: Bot,
+    dialogue: BotDialogue,
+    dialogue_message: Message,
+    mode: ScanMode,
+    settings: ScanSettings,
+    choices: OptionChoices,
+) -> anyhow::Result<()> {
+    let value = |value: Option<String>| value.unwrap_or_else(|| DEFAULT_SETTING.to_owned());
+    let text = SCAN_SETTINGS(
+        &value(settings.resolution.map(|dpi| format!("{dpi} dpi"))),
+        &value(settings.mode.as_ref().map(ToString::to_string)),
+        &value(settings.source.as_ref().map(ToString::to_string)),
+        &value(settings.quality.map(|quality| format!("{quality}%"))),
+        &value(
+            settings
+                .profile
+                .map(|profile| PROFILE_NAME(profile).to_owned()),
+        ),
+    );
+
+    let dialogue_message =
+        edit_interative(&bot, &dialogue_message, &text, &*SCAN_SETTING_BUTTONS).await?;
+
+    dialogue
+        .update(BotState::SelectScanSetting {
+            dialogue_message,
+            mode,
+            settings,
+            choices,
+        })
+        .await?;
+
+    Ok(())
+}
+
+async fn receive_scan_setting(
+    bot: Bot,
+    dialogue: BotDialogue,
+    q: CallbackQuery,
+    (dialogue_message, mode, settings, choices): (Message, ScanMode, ScanSettings, OptionChoices), // From `State::SelectScanSetting`.
+) -> anyhow::Result<()> {
+    let Some(setting) = q.data else {
+        return Ok(());
+    };
+
+    let Ok(setting) = ScanSetting::from_str(&setting) else {
+        return ignore_stale_button(&bot, &q.id).await;
+    };
+
+    if let ScanSetting::Back = setting {
+        return show_scan_action_selector(bot, dialogue, Some(dialogue_message), mode, settings)
+            .await;
+    }
+
+    let values = scan_setting_values(setting, &choices);
+    if values.is_empty() {
+        // Сканер не поддерживает эту опцию, а Telegram не даёт
+        // отредактировать сообщение без изменений.
+        return Ok(());
+    }
+
+    let indices: Vec<String> = (0..values.len()).map(|i| i.to_string()).collect();
+
+    let mut buttons: Vec<(&str, (usize, &str))> = indices
+        .iter()
+        .zip(&values)
+        .enumerate()
+        .map(|(i, (index, value))| (index.as_str(), (i / SETTING_VALUES_PER_ROW, value.as_str())))
+        .collect();
+
+    let last_row = values.len().div_ceil(SETTING_VALUES_PER_ROW);
+    buttons.push((
+        SettingValue::Default.into(),
+        (last_row, DEFAULT_SETTING_BUTTON),
+    ));
+    buttons.push((
+        SettingValue::Back.into(),
+        (last_row, PAGE_SELECTOR_BACK_BUTTON),
+    ));
+
+    let dialogue_message =
+        edit_interative(&bot, &dialogue_message, SELECT_SCAN_SETTING_VALUE, &buttons).await?;
+
+    dialogue
+        .update(BotState::SelectScanSettingValue {
+            dialogue_message,
+            mode,
+            settings,
+            choices,
+            setting,
+        })
+        .await?;
+
+    Ok(())
+}
+
+async fn receive_scan_setting_value(
+    bot: Bot,
+    dialogue: BotDialogue,
+    q: CallbackQuery,
+    (dialogue_message, mode, mut settings, choices, setting): (
+        Message,
+        ScanMode,
+        ScanSettings,
+        OptionChoices,
+        ScanSetting,
+    ), // From `State::SelectScanSettingValue`.
+) -> anyhow::Result<()> {
+    let Some(data) = q.data else {
+        return Ok(());
+    };
+
+    let index = match data.parse::<usize>() {
+        Ok(index) if index < scan_setting_values(setting, &choices).len() => Some(index),
+        Ok(_) => return ignore_stale_button(&bot, &q.id).await,
+        Err(_) => match SettingValue::from_str(&data) {
+            Ok(SettingValue::Default) => None,
+            Ok(SettingValue::Back) => {
+                return show_scan_settings_menu(
+                    bot,
+                    dialogue,
+                    dialogue_message,
+                    mode,
+                    settings,
+                    choices,
+                )
+                .await;
+            }
+            Err(_) => return ignore_stale_button(&bot, &q.id).await,
+        },
+    };
+
+    match setting {
+        ScanSetting::Resolution => {
+            settings.resolution = index.map(|i| choices.resolutions[i]);
+        }
+        ScanSetting::Mode => {
+            settings.mode = index.map(|i| choices.modes[i].clone());
+        }
+        ScanSetting::Source => {
+            settings.source = index.map(|i| choices.sources[i].clone());
+        }
+        ScanSetting::Quality => {
+            settings.quality = index.map(|i| QUALITY_CHOICES[i]);
+        }
+        ScanSetting::Profile => {
+            settings.profile = index.map(|i| PROFILE_CHOICES[i]);
+        }
+        ScanSetting::Back => unreachable!(),
+    }
+
+    show_scan_settings_menu(bot, dialogue, dialogue_message, mode, settings, choices).await
+}
+
+/// Подписи кнопок для значений настройки.
+fn scan_setting_values(setting: ScanSetting, choices: &OptionChoices) -> Vec<String> {
+    match setting {
+        ScanSetting::Resolution => choices
+            .resolutions
+            .iter()
+            .map(|dpi| format!("{dpi} dpi"))
+            .collect(),
+        ScanSetting::Mode => choices.modes.iter().map(ToString::to_string).collect(),
+        ScanSetting::Source => choices.sources.iter().map(ToString::to_string).collect(),
+        ScanSetting::Quality => QUALITY_CHOICES
+            .iter()
+            .map(|quality| format!("{quality}%"))
+            .collect(),
+        ScanSetting::Profile => PROFILE_CHOICES
+            .iter()
+            .map(|&profile| PROFILE_NAME(profile).to_owned())
+            .collect(),
+        ScanSetting::Back => unreachable!(),
+    }
+}
+
+async fn scan_first_page(
+    globals: Arc<Globals>,
+    bot: Bot,
+    dialogue: BotDialogue,
+    dialogue_message: Message,
+    mode: ScanMode,
+    settings: ScanSettings,
+) -> anyhow::Result<()> {
+    let (cancel_tx, cancel_rx) = oneshot::channel();
+
+    dialogue
+        .update(BotState::ScanningPage {
+            cancel: Arc::new(Mutex::new(Some(cancel_tx))),
+            document: None,
+        })
+        .await?;
+
+    tokio::spawn(async move {
+        if let Err(err) = scan_first_page_task(
+            globals,
+            bot,
+            dialogue,
+            dialogue_message,
+            mode,
+            settings,
+            cancel_rx,
+        )
+        .await
+        {
+            log::error!("Telegram error: {err:#}");
+        }
+    });
+
+    Ok(())
+}
+
+async fn scan_first_page_task(
+    globals: Arc<Globals>,
+    bot: Bot,
+    dialogue: BotDialogue,
+    dialogue_message: Message,
+    mode: ScanMode,
+    settings: ScanSettings,
+    cancel: oneshot::Receiver<()>,
+) -> anyhow::Result<()> {
+    let config = scan_config(&globals, &settings);
+    let dpi = settings.resolution.unwrap_or(config.scan.page_dpi);
+    let processing = PageProcessing {
+        deskew: matches!(mode, ScanMode::Document) && config.scan.deskew,
+        skip_blank: false,
+    };
+    let scan_result = scan_page(
+        config.clone(),
+        &bot,
+        &dialogue_message,
+        dpi,
+        processing,
+        settings.clone(),
+        cancel,
+    )
+    .await?;
+    match scan_result {
+        ScanResult::Done(page) => {
+            let rotation = match mode {
+                ScanMode::SinglePage => Rotation::None,
+                ScanMode::Document => {
+                    detect_page_rotation(&config.scan, &bot, &dialogue_message, &page.jpeg, dpi)
+                        .await?
+                }
+            };
+
+            // Страница хранится на диске, чтобы диалог пережил перезапуск бота.
+            let pages = tokio::task::spawn_blocking({
+                let spool_dir = config.scan.spool_dir.clone();
+                move || Pages::with_page(&spool_dir, page, rotation)
+            })
+            .await
+            .unwrap();
+
+            let pages = match pages {
+                Ok(pages) => pages,
+                Err(err) => {
+                    log::error!("Failed to spool page: {err:#}");
+                    edit_msg(&bot, &dialogue_message, SCAN_ERROR).await?;
+                    return show_scan_action_selector(bot, dialogue, None, mode, settings).await;
+                }
+            };
+
+            match mode {
+                ScanMode::SinglePage if settings.copy => {
+                    let options = PrintOptions::from(&globals.config.print);
+                    show_copy_options(bot, dialogue, dialogue_message, (pages, settings), options)
+                        .await?;
+                }
+                ScanMode::SinglePage => {
+                    let format = initial_format(&globals, &settings, &PAGE_FORMATS);
+                    show_rename_page_dialog(
+                        &globals,
+                        bot,
+                        dialogue,
+                        dialogue_message,
+                        pages,
+                        format,
+                        None,
+                    )
+                    .await?;
+                }
+                ScanMode::Document => {
+                    show_document_action_selector(
+                        &globals,
+                        bot,
+                        dialogue,
+                        Some(dialogue_message),
+                        pages,
+                        settings,
+                    )
+                    .await?;
+                }
+            }
+        }
+        ScanResult::Blank => unreachable!("first page is never skipped"),
+        ScanResult::Cancelled => {
+            show_scan_action_selector(bot, dialogue, Some(dialogue_message), mode, settings)
+                .await?;
+        }
+        ScanResult::Error(err) => {
+            // TODO: Отправка человекочитаемой ошибки в сообщении.
+            log::error!("Ошибка сканирования: {err:#}");
+            edit_msg(&bot, &dialogue_message, SCAN_ERROR).await?;
+            show_scan_action_selector(bot, dialogue, None, mode, settings).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn scan_first_page_preview(
+    globals: Arc<Globals>,
+    bot: Bot,
+    dialogue: BotDialogue,
+    dialogue_message: Message,
+    scan_mode: ScanMode,
+    settings: ScanSettings,
+) -> anyhow::Result<()> {
+    let deskew = scan_config(&globals, &settings).scan.deskew;
+    let (cancel_tx, cancel_rx) = oneshot::channel();
+
+    dialogue
+        .update(BotState::ScanningPage {
+            cancel: Arc::new(Mutex::new(Some(cancel_tx))),
+            document: None,
+        })
+        .await?;
+
+    tokio::spawn(async move {
+        if let Err(err) = preview_page_task(
+            globals,
+            bot,
+            dialogue,
+            dialogue_message,
+            (
+                PageProcessing {
+                    deskew: matches!(scan_mode, ScanMode::Document) && deskew,
+                    skip_blank: false,
+                },
+                settings.clone(),
+            ),
+            cancel_rx,
+            move |bot, dialogue, message| {
+                show_scan_action_selector(bot, dialogue, message, scan_mode, settings)
+            },
+        )
+        .await
+        {
+            log::error!("Telegram error: {err:#}");
+        }
+    });
+
+    Ok(())
+}
+
+async fn preview_page_task<Fn, F>(
+    globals: Arc<Globals>,
+    bot: Bot,
+    dialogue: BotDialogue,
+    dialogue_message: Message,
+    (processing, settings): (PageProcessing, ScanSettings),
+    cancel: oneshot::Receiver<()>,
+    update_message: Fn,
+) -> anyhow::Result<()>
+where
+    Fn: FnOnce(Bot, BotDialogue, Option<Message>) -> F,
+    F: Future<Output = anyhow::Result<()>>,
+{
+    let config = scan_config(&globals, &settings);
+    let dpi = config.scan.preview_dpi;
+    let scan_result = scan_page(
+        config,
+        &bot,
+        &dialogue_message,
+        dpi,
+        processing,
+        settings,
+        cancel,
+    )
+    .await?;
+    match scan_result {
+        ScanResult::Done(page) => {
+            edit_msg(&bot, &dialogue_message, SCAN_PREVIEW_DONE).await?;
+
+            bot.send_photo(dialogue.chat_id(), InputFile::memory(page.jpeg.bytes))
+                .await?;
+
+            update_message(bot, dialogue, None).await?
+        }
+        ScanResult::Blank => unreachable!("preview is never skipped"),
+        ScanResult::Cancelled => {
+            update_message(bot, dialogue, Some(dialogue_message)).await?;
+        }
+        ScanResult::Error(err) => {
+            // TODO: Отправка человекочитаемой ошибки в сообщении.
+            log::error!("Ошибка сканирования: {err:#}");
+            edit_msg(&bot, &dialogue_message, SCAN_ERROR).await?;
+
+            update_message(bot, dialogue, None).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Пресет, выбранный для сканирования.
+fn scan_preset<'a>(globals: &'a Globals, settings: &ScanSettings) -> Option<&'a ScanPreset> {
+    settings
+        .preset
+        .as_ref()
+        .and_then(|name| globals.config.scan_presets.get(name))
+}
+
+/// Формат, который предлагается первым: из пресета, если он подходит, иначе первый из `formats`.
+fn initial_format(
+    globals: &Globals,
+    settings: &ScanSettings,
+    formats: &[OutputFormat],
+) -> OutputFormat {
+    scan_preset(globals, settings)
+        .and_then(|preset| preset.format)
+        .filter(|format| formats.contains(format))
+        .unwrap_or(formats[0])
+}
+
+fn scan_config(globals: &Globals, settings: &ScanSettings) -> Config {
+    let mut config = match &settings.preset {
+        Some(preset) => globals.config.with_preset(preset),
+        None => globals.config.clone(),
+    };
+
+    // Настройки, выбранные в меню сканирования, важнее пресета.
+    if let Some(profile) = settings.profile {
+        config.scan.profile = profile;
+    }
+    // Качество нужно и при повторном сжатии страниц, а не только при сканировании.
+    if let Some(quality) = settings.quality {
+        config.scan.page_quality = quality;
+    }
+
+    config
+}
+
+/// Читает изображение из сканера, отображая состояние сканирования в сообщении.
+///
+/// Возвращает ошибку только в случае сбоя Telegram.
+async fn scan_page(
+    config: Config,
+    bot: &Bot,
+    message: &Message,
+    dpi: u16,
+    processing: PageProcessing,
+    settings: ScanSettings,
+    cancel: oneshot::Receiver<()>,
+) -> anyhow::Result<ScanResult> {
+    let mut state_receiver = scan::start(config, dpi, processing, settings, cancel);
+    while let Some(state) = state_receiver.recv().await {
+        match state {
+            ScanState::Prepair => {
+                edit_interative(bot, message, SCAN_PREPAIR, &*SCAN_CANCEL).await?;
+            }
+            ScanState::Progress => {
+                edit_interative(bot, message, SCAN_PROGRESS, &*SCAN_CANCEL).await?;
+            }
+            ScanState::Stop => {
+                edit_msg(bot, message, STOP_SCANNER).await?;
+            }
+            ScanState::Deskew => {
+                edit_msg(bot, message, SCAN_DESKEW).await?;
+            }
+            ScanState::CompressToJpeg => {
+                edit_msg(bot, message, SCAN_COMPRESS_JPEG).await?;
+            }
+            ScanState::Done(jpeg) => {
+                return Ok(ScanResult::Done(jpeg));
+            }
+            ScanState::Blank => {
+                return Ok(ScanResult::Blank);
+            }
+            ScanState::Error(err) => {
+                return Ok(ScanResult::Error(err));
+            }
+            ScanState::Cancelled => {
+                return Ok(ScanResult::Cancelled);
+            }
+        };
+    }
+
+    Ok(ScanResult::Cancelled)
+}
+
+/// Определяет поворот страницы документа, если это включено в конфиге.
+///
+/// Ошибка определения не прерывает сканирование, страница остаётся как есть.
+async fn detect_page_rotation(
+    config: &config::Scan,
+    bot: &Bot,
+    message: &Message,
+    page: &Jpeg,
+    dpi: u16,
+) -> anyhow::Result<Rotation> {
+    if !config.auto_rotate {
+        return Ok(Rotation::None);
+    }
+
+    edit_msg(bot, message, SCAN_DETECT_ORIENTATION).await?;
+
+    let rotation = tokio::task::spawn_blocking({
+        let page = page.clone();
+        move || ocr::detect_rotation(&page, dpi)
+    })
+    .await
+    .unwrap();
+
+    Ok(rotation.unwrap_or_else(|err| {
+        log::warn!("Failed to detect page orientation: {err:#}");
+        Rotation::None
+    }))
+}
+
+async fn receive_scan_cancellation(
+    bot: Bot,
+    q: CallbackQuery,
+    (cancel, _): (ScanCancellationToken, Option<(Pages, ScanSettings)>), // From `State::ScanningPage`.
+) -> anyhow::Result<()> {
+    let Some(answer) = q.data else {
+        return Ok(());
+    };
+
+    let Ok(ScanCancel::Forget) = ScanCancel::from_str(&answer) else {
+        return ignore_stale_button(&bot, &q.id).await;
+    };
+
+    let Some(cancel) = cancel.lock().await.take() else {
+        return Ok(());
+    };
+
+    _ = cancel.send(());
+
+    Ok(())
+}
+
+async fn show_rename_page_dialog(
+    globals: &Globals,
+    bot: Bot,
+    dialogue: BotDialogue,
+    dialogue_message: Message,
+    page: Pages,
+    format: OutputFormat,
+    destination: Option<String>,
+) -> anyhow::Result<()> {
+    let format_button = OUTPUT_FORMAT_BUTTON(format);
+    let destination_button =
+        DESTINATION_BUTTON(destination_title(&globals.config, destination.as_deref()));
+
+    let mut buttons: Vec<(&str, (usize, &str))> = vec![
+        (
+            RenameDocumentAction::KeepDefault.into(),
+            (0, KEEP_DEFAULT_NAME_BUTTON),
+        ),
+        (RenameDocumentAction::NextFormat.into(), (1, &format_button)),
+    ];
+
+    if !globals.config.destinations.is_empty() {
+        buttons.push((
+            RenameDocumentAction::NextDestination.into(),
+            (2, &destination_button),
+        ));
+    }
+
+    if globals.config.smtp.is_some() {
+        buttons.push((RenameDocumentAction::SendEmail.into(), (3, EMAIL_BUTTON)));
+    }
+
+    buttons.push((RenameDocumentAction::Cancel.into(), (4, CANCEL_BUTTON)));
+
+    let dialogue_message =
+        edit_interative(&bot, &dialogue_message, RENAME_DOCUMENT, &buttons).await?;
+
+    dialogue
+        .update(BotState::ReceiveScannedPageName {
+            dialogue_message,
+            page,
+            format,
+            destination,
+        })
+        .await?;
+
+    Ok(())
+}
+
+async fn receive_page_name(
+    globals: Arc<Globals>,
+    bot: Bot,
+    dialogue: BotDialogue,
+    msg: Message,
+    (dialogue_message, page, format, destination): (Message, Pages, OutputFormat, Option<String>), // From `State::ReceiveScannedPageName`.
+) -> anyhow::Result<()> {
+    let Some(name) = msg.text() else {
+        return send_msg(&bot, msg.chat.id, INVALID_DOCUMENT_NAME).await;
+    };
+
+    edit_msg(&bot, &dialogue_message, RENAME_DOCUMENT).await?;
+
+    let user = msg.from().map(user_name).unwrap_or_default();
+    send_page(
+        &bot,
+        dialogue.chat_id(),
+        None,
+        page,
+        format,
+        Delivery::Chat(find_destination(&globals.config, destination.as_deref())),
+        ScanInfo::new(name, &user, format_extension(format)),
+    )
+    .await?;
+
+    dialogue.update(BotState::Empty).await?;
+
+    Ok(())
+}
+
+async fn receive_page_rename_action(
+    globals: Arc<Globals>,
+    bot: Bot,
+    dialogue: BotDialogue,
+    q: CallbackQuery,
+    (dialogue_message, page, format, destination): (Message, Pages, OutputFormat, Option<String>), // From `State::ReceiveScannedPageName`.
+) -> anyhow::Result<()> {
+    let Some(action) = q.data else {
+        return Ok(());
+    };
+
+    let Ok(action) = RenameDocumentAction::from_str(&action) else {
+        return ignore_stale_button(&bot, &q.id).await;
+    };
+
+    match action {
+        RenameDocumentAction::KeepDefault => {
+            send_page(
+                &bot,
+                dialogue.chat_id(),
+                Some(dialogue_message),
+                page,
+                format,
+                Delivery::Chat(find_destination(&globals.config, destination.as_deref())),
+                ScanInfo::new(
+                    DEFAULT_SINGLE_PAGE_NAME,
+                    &user_name(&q.from),
+                    format_extension(format),
+                ),
+            )
+            .await?;
+
+            dialogue.update(BotState::Empty).await?;
+        }
+        RenameDocumentAction::SendEmail => {
+            let user = user_name(&q.from);
+            let Some(smtp) = email_of(&globals.config, &user) else {
+                return send_msg(&bot, dialogue.chat_id(), NO_EMAIL_FOR_USER).await;
+            };
+
+            send_page(
+                &bot,
+                dialogue.chat_id(),
+                Some(dialogue_message),
+                page,
+                format,
+                Delivery::Email(smtp),
+                ScanInfo::new(DEFAULT_SINGLE_PAGE_NAME, &user, format_extension(format)),
+            )
+            .await?;
+
+            dialogue.update(BotState::Empty).await?;
+        }
+        RenameDocumentAction::NextFormat => {
+            let format = next_format(&PAGE_FORMATS, format);
+            show_rename_page_dialog(
+                &globals,
+                bot,
+                dialogue,
+                dialogue_message,
+                page,
+                format,
+                destination,
+            )
+            .await?;
+        }
+        RenameDocumentAction::NextDestination => {
+            let destination = next_destination(&globals.config, destination.as_deref());
+            show_rename_page_dialog(
+                &globals,
+                bot,
+                dialogue,
+                dialogue_message,
+                page,
+                format,
+                destination,
+            )
+            .await?;
+        }
+        RenameDocumentAction::Cancel => {
+            page.delete();
+            edit_msg(&bot, &dialogue_message, SCAN_CANCELLED).await?;
+            dialogue.update(BotState::Empty).await?;
+        }
+        RenameDocumentAction::ToggleArchival => return ignore_stale_button(&bot, &q.id).await,
+    }
+
+    Ok(())
+}
 
-    let Ok(mode) = ScanMode::from_str(&mode) else {
-        panic!("Invalid scan mode '{mode}'");
+async fn send_page(
+    bot: &Bot,
+    chat_id: ChatId,
+    dialogue_message: Option<Message>,
+    page: Pages,
+    format: OutputFormat,
+    delivery: Delivery<'_>,
+    scan: ScanInfo,
+) -> anyhow::Result<()> {
+    let bytes = tokio::task::spawn_blocking({
+        let page = page.clone();
+        move || -> anyhow::Result<Vec<u8>> {
+            let page = page.get(0).context("page is not spooled")?;
+            // JPEG и PNG уже лежат в очереди, их не нужно кодировать заново.
+            match format {
+                OutputFormat::Jpeg => Ok(page.load()?.bytes),
+                OutputFormat::Png => Ok(page.load_png()?),
+                _ => encode_page(&page.load_scanned()?, format),
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    let (text, bytes) = match bytes {
+        Ok(bytes) => (SINGLE_PAGE_SCAN_RESULT, Some(bytes)),
+        Err(err) => {
+            log::error!("Failed to encode page to {format}: {err:#}");
+            (SCAN_ERROR, None)
+        }
+    };
+
+    if let Some(dialogue_message) = dialogue_message {
+        edit_msg(bot, &dialogue_message, text).await?;
+    } else {
+        send_msg(bot, chat_id, text).await?;
+    }
+
+    let Some(bytes) = bytes else {
+        page.delete();
+        return Ok(());
     };
 
-    show_scan_action_selector(bot, dialogue, Some(dialogue_message), mode).await?;
+    deliver_scan(bot, chat_id, delivery, scan, Content::Memory(bytes)).await?;
+
+    // Если отправить не удалось, диалог остаётся в прежнем состоянии и страницу
+    // можно отправить ещё раз.
+    page.delete();
 
     Ok(())
 }
 
-async fn show_scan_action_selector(
+async fn show_document_action_selector(
+    globals: &Globals,
     bot: Bot,
     dialogue: BotDialogue,
     dialogue_message: Option<Message>,
-    mode: ScanMode,
+    pages: Pages,
+    settings: ScanSettings,
 ) -> anyhow::Result<()> {
+    // Без шрифта текстовый слой не собрать, поэтому кнопка скрывается.
+    let buttons: Vec<_> = DOCUMENT_ACTION_BUTTONS
+        .iter()
+        .copied()
+        .filter(|&(action, _)| {
+            globals.text_layer || action != <&str>::from(ScanAction::DoneWithText)
+        })
+        .collect();
+
     let dialogue_message = if let Some(message) = dialogue_message {
-        edit_interative(&bot, &message, SELECT_SCAN_ACTION, &*SCAN_ACTIONS_BUTTONS).await?
+        let mut text = if pages.blank_count() > 0 {
+            SELECT_DOCUMENT_ACTION_WITH_BLANK(pages.len(), pages.blank_count())
+        } else {
+            SELECT_DOCUMENT_ACTION(pages.len())
+        };
+
+        let rotation = pages.iter().last().map(|page| page.rotation);
+        if let Some(rotation) = rotation.filter(|rotation| *rotation != Rotation::None) {
+            text = format!("{}\n{text}", LAST_PAGE_ROTATION(rotation.degrees()));
+        }
+
+        edit_interative(&bot, &message, &text, &buttons).await?
     } else {
-        send_interative(&bot, &dialogue, SELECT_SCAN_ACTION, &*SCAN_ACTIONS_BUTTONS).await?
+        send_interative(&bot, &dialogue, SELECT_SCAN_ACTION, &buttons).await?
     };
 
     dialogue
-        .update(BotState::SelectFirstScanAction {
+        .update(BotState::SelectDocumentAction {
             dialogue_message,
-            mode,
+            pages,
+            settings,
         })
         .await?;
 
     Ok(())
 }
 
-async fn first_scan_action_selected(
+async fn receive_multipage_scan_action_selection(
     globals: Arc<Globals>,
     bot: Bot,
     dialogue: BotDialogue,
     q: CallbackQuery,
-    (dialogue_message, mode): (Message, ScanMode), // From `State::SelectFirstScanAction`.
+    (dialogue_message, mut pages, settings): (Message, Pages, ScanSettings), // From `State::SelectDocumentAction`.
 ) -> anyhow::Result<()> {
     let Some(action) = q.data else {
         return Ok(());
     };
 
     let Ok(action) = ScanAction::from_str(&action) else {
-        panic!("Invalid scan action '{action}'");
+        return ignore_stale_button(&bot, &q.id).await;
     };
 
+    let needs_pages = !matches!(
+        action,
+        ScanAction::Scan | ScanAction::Preview | ScanAction::Cancel
+    );
+    if needs_pages && pages.is_empty() {
+        // Без страниц эти действия ничего не меняют, а Telegram не даёт
+        // отредактировать сообщение без изменений.
+        return Ok(());
+    }
+
     match action {
+        ScanAction::Done | ScanAction::DoneWithText if settings.copy => {
+            let options = PrintOptions::from(&globals.config.print);
+            show_copy_options(bot, dialogue, dialogue_message, (pages, settings), options).await?;
+        }
+        ScanAction::Done | ScanAction::DoneWithText => {
+            let with_text = matches!(action, ScanAction::DoneWithText);
+
+            show_enhancement_selector(
+                &globals,
+                bot,
+                dialogue,
+                dialogue_message,
+                pages,
+                settings,
+                with_text,
+            )
+            .await?;
+        }
         ScanAction::Scan => {
-            scan_first_page(globals, bot, dialogue, dialogue_message, mode).await?;
+            scan_document_page(
+                globals,
+                bot,
+                dialogue,
+                (dialogue_message, pages, settings),
+                None,
+            )
+            .await?;
         }
         ScanAction::Preview => {
-            scan_first_page_preview(globals, bot, dialogue, dialogue_message, mode).await?;
+            scan_document_page_preview(globals, bot, dialogue, (dialogue_message, pages, settings))
+                .await?;
         }
-        ScanAction::Cancel => {
-            edit_msg(&bot, &dialogue_message, SCAN_CANCELLED).await?;
-            dialogue.update(BotState::Empty).await?;
+        ScanAction::RotateLast => {
+            pages.rotate(pages.len() - 1);
+            show_document_action_selector(
+                &globals,
+                bot,
+                dialogue,
+                Some(dialogue_message),
+                pages,
+                settings,
+            )
+            .await?;
         }
-        _ => unreachable!(),
-    }
-
-    Ok(())
-}
-
-async fn scan_first_page(
-    globals: Arc<Globals>,
-    bot: Bot,
-    dialogue: BotDialogue,
-    dialogue_message: Message,
-    mode: ScanMode,
-) -> anyhow::Result<()> {
-    let (cancel_tx, cancel_rx) = oneshot::channel();
-
-    dialogue
-        .update(BotState::ScanningPage {
-            cancel: Arc::new(Mutex::new(Some(cancel_tx))),
-        })
-        .await?;
-
-    tokio::spawn(async move {
-        if let Err(err) =
-            scan_first_page_task(globals, bot, dialogue, dialogue_message, mode, cancel_rx).await
-        {
-            log::error!("Telegram error: {err:#}");
+        ScanAction::DeleteLast => {
+            pages.remove(pages.len() - 1);
+            show_document_action_selector(
+                &globals,
+                bot,
+                dialogue,
+                Some(dialogue_message),
+                pages,
+                settings,
+            )
+            .await?;
         }
-    });
-
-    Ok(())
-}
-
-async fn scan_first_page_task(
-    globals: Arc<Globals>,
-    bot: Bot,
-    dialogue: BotDialogue,
-    dialogue_message: Message,
-    mode: ScanMode,
-    cancel: oneshot::Receiver<()>,
-) -> anyhow::Result<()> {
-    let dpi = globals.config.scan.page_dpi;
-    let scan_result = scan_page(globals, &bot, &dialogue_message, dpi, cancel).await?;
-    match scan_result {
-        ScanResult::Done(page) => match mode {
-            ScanMode::SinglePage => {
-                show_rename_page_dialog(bot, dialogue, dialogue_message, page).await?;
-            }
-            ScanMode::Document => {
-                show_document_action_selector(bot, dialogue, Some(dialogue_message), vec![page])
-                    .await?;
-            }
-        },
-        ScanResult::Cancelled => {
-            show_scan_action_selector(bot, dialogue, Some(dialogue_message), mode).await?;
+        ScanAction::Pages => {
+            show_page_selector(bot, dialogue, dialogue_message, pages, settings).await?;
         }
-        ScanResult::Error(err) => {
-            // TODO: Отправка человекочитаемой ошибки в сообщении.
-            log::error!("Ошибка сканирования: {err:#}");
-            edit_msg(&bot, &dialogue_message, SCAN_ERROR).await?;
-            show_scan_action_selector(bot, dialogue, None, mode).await?;
+        ScanAction::ContactSheet => {
+            send_contact_sheet(globals, bot, dialogue, dialogue_message, pages, settings).await?;
+        }
+        ScanAction::Settings => return ignore_stale_button(&bot, &q.id).await,
+        ScanAction::Cancel => {
+            ask_scan_cancel_confirmation(bot, dialogue, (dialogue_message, pages, settings))
+                .await?;
         }
     }
 
     Ok(())
 }
 
-async fn scan_first_page_preview(
+/// Сканирует новую страницу документа или заново сканирует страницу `replace`.
+async fn scan_document_page(
     globals: Arc<Globals>,
     bot: Bot,
     dialogue: BotDialogue,
-    dialogue_message: Message,
-    scan_mode: ScanMode,
+    (dialogue_message, pages, settings): (Message, Pages, ScanSettings), // From `State::SelectDocumentAction`.
+    replace: Option<usize>,
 ) -> anyhow::Result<()> {
     let (cancel_tx, cancel_rx) = oneshot::channel();
 
     dialogue
         .update(BotState::ScanningPage {
             cancel: Arc::new(Mutex::new(Some(cancel_tx))),
+            document: Some((pages.clone(), settings.clone())),
         })
         .await?;
 
     tokio::spawn(async move {
-        if let Err(err) = preview_page_task(
+        if let Err(err) = scan_document_page_task(
             globals,
             bot,
             dialogue,
-            dialogue_message,
+            (dialogue_message, pages, settings),
             cancel_rx,
-            move |bot, dialogue, message| {
-                show_scan_action_selector(bot, dialogue, message, scan_mode)
-            },
+            replace,
         )
         .await
         {
@@ -454,310 +2842,366 @@ async fn scan_first_page_preview(
     Ok(())
 }
 
-async fn preview_page_task<Fn, F>(
+async fn scan_document_page_task(
     globals: Arc<Globals>,
     bot: Bot,
     dialogue: BotDialogue,
-    dialogue_message: Message,
+    (dialogue_message, mut pages, settings): (Message, Pages, ScanSettings),
     cancel: oneshot::Receiver<()>,
-    update_message: Fn,
-) -> anyhow::Result<()>
-where
-    Fn: FnOnce(Bot, BotDialogue, Option<Message>) -> F,
-    F: Future<Output = anyhow::Result<()>>,
-{
-    let dpi = globals.config.scan.preview_dpi;
-    let scan_result = scan_page(globals, &bot, &dialogue_message, dpi, cancel).await?;
+    replace: Option<usize>,
+) -> anyhow::Result<()> {
+    let config = scan_config(&globals, &settings);
+    let dpi = settings.resolution.unwrap_or(config.scan.page_dpi);
+    let processing = PageProcessing {
+        deskew: config.scan.deskew,
+        // Страницу пересканируют осознанно, поэтому она не пропускается.
+        skip_blank: replace.is_none(),
+    };
+    let scan_result = scan_page(
+        config.clone(),
+        &bot,
+        &dialogue_message,
+        dpi,
+        processing,
+        settings.clone(),
+        cancel,
+    )
+    .await?;
     match scan_result {
-        ScanResult::Done(jpeg) => {
-            edit_msg(&bot, &dialogue_message, SCAN_PREVIEW_DONE).await?;
+        ScanResult::Done(page) => {
+            let rotation =
+                detect_page_rotation(&config.scan, &bot, &dialogue_message, &page.jpeg, dpi)
+                    .await?;
 
-            bot.send_photo(dialogue.chat_id(), InputFile::memory(jpeg.bytes))
-                .await?;
+            let (pages, spooled) = tokio::task::spawn_blocking(move || {
+                let spooled = match replace {
+                    Some(index) => pages.replace(index, page, rotation),
+                    None => pages.push(page, rotation),
+                };
+                (pages, spooled)
+            })
+            .await
+            .unwrap();
+
+            if let Err(err) = spooled {
+                log::error!("Failed to spool page: {err:#}");
+                edit_msg(&bot, &dialogue_message, SCAN_ERROR).await?;
+
+                return show_document_action_selector(
+                    &globals, bot, dialogue, None, pages, settings,
+                )
+                .await;
+            }
 
-            update_message(bot, dialogue, None).await?
+            show_document_action_selector(
+                &globals,
+                bot,
+                dialogue,
+                Some(dialogue_message),
+                pages,
+                settings,
+            )
+            .await?;
+        }
+        ScanResult::Blank => {
+            log::debug!("Skip blank page");
+            pages.skip_blank();
+
+            show_document_action_selector(
+                &globals,
+                bot,
+                dialogue,
+                Some(dialogue_message),
+                pages,
+                settings,
+            )
+            .await?;
         }
         ScanResult::Cancelled => {
-            update_message(bot, dialogue, Some(dialogue_message)).await?;
+            show_document_action_selector(
+                &globals,
+                bot,
+                dialogue,
+                Some(dialogue_message),
+                pages,
+                settings,
+            )
+            .await?;
         }
         ScanResult::Error(err) => {
             // TODO: Отправка человекочитаемой ошибки в сообщении.
             log::error!("Ошибка сканирования: {err:#}");
             edit_msg(&bot, &dialogue_message, SCAN_ERROR).await?;
 
-            update_message(bot, dialogue, None).await?;
+            show_document_action_selector(&globals, bot, dialogue, None, pages, settings).await?;
         }
     }
 
     Ok(())
 }
 
-/// Читает изображение из сканера, отображая состояние сканирования в сообщении.
-///
-/// Возвращает ошибку только в случае сбоя Telegram.
-async fn scan_page(
-    globals: Arc<Globals>,
-    bot: &Bot,
-    message: &Message,
-    dpi: u16,
-    cancel: oneshot::Receiver<()>,
-) -> anyhow::Result<ScanResult> {
-    let mut state_receiver = scan::start(globals.config.clone(), dpi, cancel);
-    while let Some(state) = state_receiver.recv().await {
-        match state {
-            ScanState::Prepair => {
-                edit_interative(bot, message, SCAN_PREPAIR, &*SCAN_CANCEL).await?;
-            }
-            ScanState::Progress => {
-                edit_interative(bot, message, SCAN_PROGRESS, &*SCAN_CANCEL).await?;
-            }
-            ScanState::Stop => {
-                edit_msg(bot, message, STOP_SCANNER).await?;
-            }
-            ScanState::CompressToJpeg => {
-                edit_msg(bot, message, SCAN_COMPRESS_JPEG).await?;
-            }
-            ScanState::Done(jpeg) => {
-                return Ok(ScanResult::Done(jpeg));
-            }
-            ScanState::Error(err) => {
-                return Ok(ScanResult::Error(err));
-            }
-            ScanState::Cancelled => {
-                return Ok(ScanResult::Cancelled);
-            }
-        };
-    }
-
-    Ok(ScanResult::Cancelled)
-}
-
-async fn receive_scan_cancellation(
-    q: CallbackQuery,
-    cancel: ScanCancellationToken, // From `State::ScanningPage`.
-) -> anyhow::Result<()> {
-    if q.data.is_none() {
-        return Ok(());
-    };
-
-    let Some(cancel) = cancel.lock().await.take() else {
-        return Ok(());
-    };
-
-    _ = cancel.send(());
-
-    Ok(())
-}
-
-async fn show_rename_page_dialog(
+async fn show_page_selector(
     bot: Bot,
     dialogue: BotDialogue,
     dialogue_message: Message,
-    page: Page,
+    pages: Pages,
+    settings: ScanSettings,
 ) -> anyhow::Result<()> {
-    edit_interative(
-        &bot,
-        &dialogue_message,
-        RENAME_DOCUMENT,
-        &*RENAME_DOCUMENT_BUTTONS,
-    )
-    .await?;
+    let numbers: Vec<String> = (1..=pages.len()).map(|number| number.to_string()).collect();
+
+    let mut buttons: Vec<(&str, (usize, &str))> = numbers
+        .iter()
+        .enumerate()
+        .map(|(i, number)| (number.as_str(), (i / PAGES_PER_ROW, number.as_str())))
+        .collect();
+
+    let back_row = numbers.len().div_ceil(PAGES_PER_ROW);
+    buttons.push((
+        PageAction::Back.into(),
+        (back_row, PAGE_SELECTOR_BACK_BUTTON),
+    ));
+
+    let dialogue_message =
+        edit_interative(&bot, &dialogue_message, SELECT_DOCUMENT_PAGE, &buttons).await?;
 
     dialogue
-        .update(BotState::ReceiveScannedPageName {
+        .update(BotState::SelectDocumentPage {
             dialogue_message,
-            page,
+            pages,
+            settings,
         })
         .await?;
 
     Ok(())
 }
 
-async fn receive_page_name(
+async fn receive_document_page(
+    globals: Arc<Globals>,
     bot: Bot,
     dialogue: BotDialogue,
-    msg: Message,
-    (dialogue_message, page): (Message, Page), // From `State::ReceiveScannedPageName`.
+    q: CallbackQuery,
+    (dialogue_message, pages, settings): (Message, Pages, ScanSettings), // From `State::SelectDocumentPage`.
 ) -> anyhow::Result<()> {
-    let Some(name) = msg.text() else {
-        return send_msg(&bot, msg.chat.id, INVALID_DOCUMENT_NAME).await;
+    let Some(data) = q.data else {
+        return Ok(());
     };
 
-    edit_msg(&bot, &dialogue_message, RENAME_DOCUMENT).await?;
-
-    send_page(&bot, dialogue.chat_id(), None, name, page).await?;
+    if let Ok(number) = data.parse::<usize>() {
+        if number == 0 || number > pages.len() {
+            return ignore_stale_button(&bot, &q.id).await;
+        }
 
-    dialogue.update(BotState::Empty).await?;
+        return show_page_action_selector(
+            bot,
+            dialogue,
+            dialogue_message,
+            pages,
+            settings,
+            number - 1,
+        )
+        .await;
+    }
 
-    Ok(())
-}
+    let Ok(PageAction::Back) = PageAction::from_str(&data) else {
+        return ignore_stale_button(&bot, &q.id).await;
+    };
 
-async fn receive_page_rename_cancel(
-    bot: Bot,
-    dialogue: BotDialogue,
-    (dialogue_message, page): (Message, Page), // From `State::ReceiveScannedPageName`.
-) -> anyhow::Result<()> {
-    send_page(
-        &bot,
-        dialogue.chat_id(),
+    show_document_action_selector(
+        &globals,
+        bot,
+        dialogue,
         Some(dialogue_message),
-        DEFAULT_SINGLE_PAGE_NAME,
-        page,
+        pages,
+        settings,
     )
-    .await?;
-
-    dialogue.update(BotState::Empty).await?;
-
-    Ok(())
-}
-
-async fn send_page(
-    bot: &Bot,
-    chat_id: ChatId,
-    dialogue_message: Option<Message>,
-    name: &str,
-    page: Page,
-) -> anyhow::Result<()> {
-    if let Some(dialogue_message) = dialogue_message {
-        edit_msg(bot, &dialogue_message, SINGLE_PAGE_SCAN_RESULT).await?;
-    } else {
-        send_msg(bot, chat_id, SINGLE_PAGE_SCAN_RESULT).await?;
-    }
-
-    let document = InputFile::memory(page.bytes.to_owned()).file_name(format!("{name}.jpg"));
-    bot.send_document(chat_id, document).await?;
-
-    Ok(())
+    .await
 }
 
-async fn show_document_action_selector(
+async fn show_page_action_selector(
     bot: Bot,
     dialogue: BotDialogue,
-    dialogue_message: Option<Message>,
-    pages: Vec<Jpeg>,
+    dialogue_message: Message,
+    pages: Pages,
+    settings: ScanSettings,
+    page: usize,
 ) -> anyhow::Result<()> {
-    let dialogue_message = if let Some(message) = dialogue_message {
-        edit_interative(
-            &bot,
-            &message,
-            &SELECT_DOCUMENT_ACTION(pages.len()),
-            &*DOCUMENT_ACTION_BUTTONS,
-        )
-        .await?
-    } else {
-        send_interative(
-            &bot,
-            &dialogue,
-            SELECT_SCAN_ACTION,
-            &*DOCUMENT_ACTION_BUTTONS,
-        )
-        .await?
-    };
+    let mut text = SELECT_PAGE_ACTION(page + 1, pages.len());
+
+    let rotation = pages.get(page).map(|page| page.rotation);
+    if let Some(rotation) = rotation.filter(|rotation| *rotation != Rotation::None) {
+        text = format!("{}\n{text}", PAGE_ROTATION(rotation.degrees()));
+    }
+
+    let dialogue_message =
+        edit_interative(&bot, &dialogue_message, &text, &*PAGE_ACTION_BUTTONS).await?;
 
     dialogue
-        .update(BotState::SelectDocumentAction {
+        .update(BotState::SelectPageAction {
             dialogue_message,
             pages,
+            settings,
+            page,
         })
         .await?;
 
     Ok(())
 }
 
-async fn receive_multipage_scan_action_selection(
+async fn receive_page_action(
     globals: Arc<Globals>,
     bot: Bot,
     dialogue: BotDialogue,
     q: CallbackQuery,
-    (dialogue_message, pages): (Message, Pages), // From `State::SelectDocumentAction`.
+    (dialogue_message, mut pages, settings, page): (Message, Pages, ScanSettings, usize), // From `State::SelectPageAction`.
 ) -> anyhow::Result<()> {
     let Some(action) = q.data else {
         return Ok(());
     };
 
-    let Ok(action) = ScanAction::from_str(&action) else {
-        panic!("Invalid scan action '{action}'");
+    let Ok(action) = PageAction::from_str(&action) else {
+        return ignore_stale_button(&bot, &q.id).await;
     };
 
     match action {
-        ScanAction::Done => {
-            show_rename_document_dialog(bot, dialogue, dialogue_message, pages).await?;
+        PageAction::MoveUp => {
+            if page == 0 {
+                return Ok(());
+            }
+
+            pages.move_page(page, page - 1);
+            show_page_action_selector(bot, dialogue, dialogue_message, pages, settings, page - 1)
+                .await?;
         }
-        ScanAction::Scan => {
-            scan_document_page(globals, bot, dialogue, (dialogue_message, pages)).await?;
+        PageAction::MoveDown => {
+            if page + 1 == pages.len() {
+                return Ok(());
+            }
+
+            pages.move_page(page, page + 1);
+            show_page_action_selector(bot, dialogue, dialogue_message, pages, settings, page + 1)
+                .await?;
         }
-        ScanAction::Preview => {
-            scan_document_page_preview(globals, bot, dialogue, (dialogue_message, pages)).await?;
+        PageAction::Rotate => {
+            pages.rotate(page);
+            show_page_action_selector(bot, dialogue, dialogue_message, pages, settings, page)
+                .await?;
         }
-        ScanAction::Cancel => {
-            ask_scan_cancel_confirmation(bot, dialogue, (dialogue_message, pages)).await?;
+        PageAction::Rescan => {
+            scan_document_page(
+                globals,
+                bot,
+                dialogue,
+                (dialogue_message, pages, settings),
+                Some(page),
+            )
+            .await?;
+        }
+        PageAction::Delete => {
+            pages.remove(page);
+            show_document_action_selector(
+                &globals,
+                bot,
+                dialogue,
+                Some(dialogue_message),
+                pages,
+                settings,
+            )
+            .await?;
+        }
+        PageAction::Back => {
+            show_document_action_selector(
+                &globals,
+                bot,
+                dialogue,
+                Some(dialogue_message),
+                pages,
+                settings,
+            )
+            .await?;
         }
     }
 
     Ok(())
 }
 
-async fn scan_document_page(
+/// Показывает миниатюры всех страниц документа вместо сообщения диалога,
+/// а выбор действия выводится в подписи к ним.
+async fn send_contact_sheet(
     globals: Arc<Globals>,
     bot: Bot,
     dialogue: BotDialogue,
-    (dialogue_message, pages): (Message, Pages), // From `State::SelectDocumentAction`.
+    dialogue_message: Message,
+    pages: Pages,
+    settings: ScanSettings,
 ) -> anyhow::Result<()> {
-    let (cancel_tx, cancel_rx) = oneshot::channel();
+    edit_msg(&bot, &dialogue_message, SCAN_PREPARE_CONTACT_SHEET).await?;
+
+    let config = scan_config(&globals, &settings);
+    let sheet = tokio::task::spawn_blocking({
+        let pages = pages.clone();
+        move || render_contact_sheet(&config.scan, &pages)
+    })
+    .await
+    .unwrap();
+
+    let message = match sheet {
+        Ok(sheet) => show_contact_sheet(&bot, &dialogue_message, sheet.bytes).await,
+        Err(err) => Err(err.context("rendering contact sheet")),
+    };
 
-    dialogue
-        .update(BotState::ScanningPage {
-            cancel: Arc::new(Mutex::new(Some(cancel_tx))),
-        })
-        .await?;
+    match message {
+        Ok(message) => {
+            show_document_action_selector(&globals, bot, dialogue, Some(message), pages, settings)
+                .await?;
+        }
+        Err(err) => {
+            log::error!("Failed to show contact sheet: {err:#}");
+            edit_msg(&bot, &dialogue_message, SCAN_ERROR).await?;
 
-    tokio::spawn(async move {
-        if let Err(err) =
-            scan_document_page_task(globals, bot, dialogue, dialogue_message, cancel_rx, pages)
-                .await
-        {
-            log::error!("Telegram error: {err:#}");
+            show_document_action_selector(&globals, bot, dialogue, None, pages, settings).await?;
         }
-    });
+    }
 
     Ok(())
 }
 
-async fn scan_document_page_task(
-    globals: Arc<Globals>,
-    bot: Bot,
-    dialogue: BotDialogue,
-    dialogue_message: Message,
-    cancel: oneshot::Receiver<()>,
-    mut pages: Pages,
-) -> anyhow::Result<()> {
-    let dpi = globals.config.scan.page_dpi;
-    let scan_result = scan_page(globals, &bot, &dialogue_message, dpi, cancel).await?;
-    match scan_result {
-        ScanResult::Done(page) => {
-            pages.push(page);
+/// Показывает миниатюры в сообщении диалога, чтобы кнопки остались под ними.
+///
+/// Возвращает сообщение диалога, которое теперь содержит фото.
+async fn show_contact_sheet(
+    bot: &Bot,
+    dialogue_message: &Message,
+    sheet: Vec<u8>,
+) -> anyhow::Result<Message> {
+    let chat_id = dialogue_message.chat.id;
+    let photo = InputFile::memory(sheet);
+
+    if dialogue_message.photo().is_some() {
+        let media = InputMedia::Photo(InputMediaPhoto::new(photo));
+        let message = bot
+            .edit_message_media(chat_id, dialogue_message.id, media)
+            .await?;
+
+        return Ok(message);
+    }
 
-            show_document_action_selector(bot, dialogue, Some(dialogue_message), pages).await?;
-        }
-        ScanResult::Cancelled => {
-            show_document_action_selector(bot, dialogue, Some(dialogue_message), pages).await?;
-        }
-        ScanResult::Error(err) => {
-            // TODO: Отправка человекочитаемой ошибки в сообщении.
-            log::error!("Ошибка сканирования: {err:#}");
-            edit_msg(&bot, &dialogue_message, SCAN_ERROR).await?;
+    // Текстовое сообщение нельзя превратить в фото, поэтому оно заменяется новым.
+    let message = bot
+        .send_photo(chat_id, photo)
+        .caption(SCAN_PREPARE_CONTACT_SHEET)
+        .await?;
 
-            show_document_action_selector(bot, dialogue, None, pages).await?;
-        }
+    if let Err(err) = bot.delete_message(chat_id, dialogue_message.id).await {
+        log::error!("Failed to delete dialogue message: {err:#}");
     }
 
-    Ok(())
+    Ok(message)
 }
 
 async fn ask_scan_cancel_confirmation(
     bot: Bot,
     dialogue: BotDialogue,
-    (dialogue_message, pages): (Message, Pages), // From `State::SelectDocumentAction`.
+    (dialogue_message, pages, settings): (Message, Pages, ScanSettings), // From `State::SelectDocumentAction`.
 ) -> anyhow::Result<()> {
     edit_interative(
         &bot,
@@ -771,6 +3215,7 @@ async fn ask_scan_cancel_confirmation(
         .update(BotState::ConfirmDropScannedDocument {
             dialogue_message,
             pages,
+            settings,
         })
         .await?;
 
@@ -778,26 +3223,36 @@ async fn ask_scan_cancel_confirmation(
 }
 
 async fn receive_scan_cancel_confirmation(
+    globals: Arc<Globals>,
     bot: Bot,
     dialogue: BotDialogue,
     q: CallbackQuery,
-    (dialogue_message, pages): (Message, Pages), // From `State::ConfirmDropScannedDocument`.
+    (dialogue_message, pages, settings): (Message, Pages, ScanSettings), // From `State::ConfirmDropScannedDocument`.
 ) -> anyhow::Result<()> {
     let Some(answer) = q.data else {
         return Ok(());
     };
 
     let Ok(answer) = ScanCancel::from_str(&answer) else {
-        panic!("Invalid scan mode '{answer}'");
+        return ignore_stale_button(&bot, &q.id).await;
     };
 
     match answer {
         ScanCancel::Forget => {
+            pages.delete();
             edit_msg(&bot, &dialogue_message, SCAN_CANCELLED).await?;
             dialogue.update(BotState::Empty).await?;
         }
         ScanCancel::Cancel => {
-            show_document_action_selector(bot, dialogue, Some(dialogue_message), pages).await?;
+            show_document_action_selector(
+                &globals,
+                bot,
+                dialogue,
+                Some(dialogue_message),
+                pages,
+                settings,
+            )
+            .await?;
         }
     }
 
@@ -808,25 +3263,35 @@ async fn scan_document_page_preview(
     globals: Arc<Globals>,
     bot: Bot,
     dialogue: BotDialogue,
-    (dialogue_message, pages): (Message, Pages), // From `State::SelectDocumentAction`.
+    (dialogue_message, pages, settings): (Message, Pages, ScanSettings), // From `State::SelectDocumentAction`.
 ) -> anyhow::Result<()> {
+    let deskew = scan_config(&globals, &settings).scan.deskew;
     let (cancel_tx, cancel_rx) = oneshot::channel();
 
     dialogue
         .update(BotState::ScanningPage {
             cancel: Arc::new(Mutex::new(Some(cancel_tx))),
+            document: Some((pages.clone(), settings.clone())),
         })
         .await?;
 
     tokio::spawn(async move {
         if let Err(err) = preview_page_task(
-            globals,
+            globals.clone(),
             bot,
             dialogue,
             dialogue_message,
+            (
+                PageProcessing {
+                    deskew,
+                    skip_blank: false,
+                },
+                settings.clone(),
+            ),
             cancel_rx,
-            move |bot, dialogue, message| {
-                show_document_action_selector(bot, dialogue, message, pages)
+            move |bot, dialogue, message| async move {
+                show_document_action_selector(&globals, bot, dialogue, message, pages, settings)
+                    .await
             },
         )
         .await
@@ -838,41 +3303,221 @@ async fn scan_document_page_preview(
     Ok(())
 }
 
-async fn show_rename_document_dialog(
+async fn show_enhancement_selector(
+    globals: &Globals,
     bot: Bot,
     dialogue: BotDialogue,
     dialogue_message: Message,
     pages: Pages,
+    settings: ScanSettings,
+    with_text: bool,
 ) -> anyhow::Result<()> {
-    edit_interative(
-        &bot,
-        &dialogue_message,
-        RENAME_DOCUMENT,
-        &*RENAME_DOCUMENT_BUTTONS,
+    // Обработка из пресета отмечается, чтобы её было проще выбрать.
+    let preset_enhancement = scan_preset(globals, &settings).and_then(|preset| preset.enhancement);
+    let titles: Vec<String> = ENHANCEMENT_BUTTONS
+        .iter()
+        .map(|&(action, (_, title))| {
+            if preset_enhancement.is_some_and(|enhancement| action == <&str>::from(enhancement)) {
+                PRESET_CHOICE_BUTTON(title)
+            } else {
+                title.to_owned()
+            }
+        })
+        .collect();
+    let buttons: Vec<(&str, (usize, &str))> = ENHANCEMENT_BUTTONS
+        .iter()
+        .zip(&titles)
+        .map(|(&(action, (row, _)), title)| (action, (row, title.as_str())))
+        .collect();
+
+    let dialogue_message =
+        edit_interative(&bot, &dialogue_message, SELECT_ENHANCEMENT, &buttons).await?;
+
+    dialogue
+        .update(BotState::SelectDocumentEnhancement {
+            dialogue_message,
+            pages,
+            settings,
+            with_text,
+        })
+        .await?;
+
+    Ok(())
+}
+
+async fn receive_document_enhancement(
+    globals: Arc<Globals>,
+    bot: Bot,
+    dialogue: BotDialogue,
+    q: CallbackQuery,
+    (dialogue_message, pages, settings, with_text): (Message, Pages, ScanSettings, bool), // From `State::SelectDocumentEnhancement`.
+) -> anyhow::Result<()> {
+    let Some(enhancement) = q.data else {
+        return Ok(());
+    };
+
+    let Ok(enhancement) = Enhancement::from_str(&enhancement) else {
+        return ignore_stale_button(&bot, &q.id).await;
+    };
+
+    let archival = scan_config(&globals, &settings).scan.pdf_conformance == PdfConformance::PdfA2b;
+
+    // Текстовый слой можно добавить только в PDF.
+    let format = if with_text {
+        OutputFormat::Pdf
+    } else {
+        initial_format(&globals, &settings, &DOCUMENT_FORMATS)
+    };
+
+    let choice = DocumentChoice {
+        with_text,
+        archival,
+        enhancement,
+        format,
+        destination: None,
+    };
+
+    show_rename_document_dialog(
+        &globals,
+        bot,
+        dialogue,
+        (dialogue_message, pages, settings),
+        choice,
     )
-    .await?;
+    .await
+}
+
+async fn show_rename_document_dialog(
+    globals: &Globals,
+    bot: Bot,
+    dialogue: BotDialogue,
+    (dialogue_message, pages, settings): (Message, Pages, ScanSettings),
+    choice: DocumentChoice,
+) -> anyhow::Result<()> {
+    let format_button = OUTPUT_FORMAT_BUTTON(choice.format);
+    let destination_button = DESTINATION_BUTTON(destination_title(
+        &globals.config,
+        choice.destination.as_deref(),
+    ));
+
+    let mut buttons: Vec<(&str, (usize, &str))> = vec![(
+        RenameDocumentAction::KeepDefault.into(),
+        (0, KEEP_DEFAULT_NAME_BUTTON),
+    )];
+
+    // Текстовый слой можно добавить только в PDF.
+    if !choice.with_text {
+        buttons.push((
+            RenameDocumentAction::NextFormat.into(),
+            (buttons.len(), &format_button),
+        ));
+    }
+
+    if choice.format == OutputFormat::Pdf {
+        buttons.push((
+            RenameDocumentAction::ToggleArchival.into(),
+            (
+                buttons.len(),
+                if choice.archival {
+                    ARCHIVAL_ON_BUTTON
+                } else {
+                    ARCHIVAL_OFF_BUTTON
+                },
+            ),
+        ));
+    }
+
+    if !globals.config.destinations.is_empty() {
+        buttons.push((
+            RenameDocumentAction::NextDestination.into(),
+            (buttons.len(), &destination_button),
+        ));
+    }
+
+    if globals.config.smtp.is_some() {
+        buttons.push((
+            RenameDocumentAction::SendEmail.into(),
+            (buttons.len(), EMAIL_BUTTON),
+        ));
+    }
+
+    let dialogue_message =
+        edit_interative(&bot, &dialogue_message, RENAME_DOCUMENT, &buttons).await?;
 
     dialogue
         .update(BotState::ReceiveScannedDocumentName {
             dialogue_message,
             pages,
+            settings,
+            choice,
         })
         .await?;
 
     Ok(())
 }
 
-async fn receive_document_rename_cancel(
+async fn receive_document_rename_action(
+    globals: Arc<Globals>,
     bot: Bot,
     dialogue: BotDialogue,
-    (dialogue_message, pages): (Message, Pages), // From `State::ReceiveScannedDocumentName`.
+    q: CallbackQuery,
+    (dialogue_message, pages, settings, mut choice): (Message, Pages, ScanSettings, DocumentChoice), // From `State::ReceiveScannedDocumentName`.
 ) -> anyhow::Result<()> {
-    send_pdf(
+    let Some(action) = q.data else {
+        return Ok(());
+    };
+
+    let Ok(action) = RenameDocumentAction::from_str(&action) else {
+        return ignore_stale_button(&bot, &q.id).await;
+    };
+
+    match action {
+        RenameDocumentAction::ToggleArchival => {
+            choice.archival = !choice.archival;
+        }
+        RenameDocumentAction::NextFormat => {
+            choice.format = next_format(&DOCUMENT_FORMATS, choice.format);
+        }
+        RenameDocumentAction::NextDestination => {
+            choice.destination = next_destination(&globals.config, choice.destination.as_deref());
+        }
+        RenameDocumentAction::SendEmail => {
+            if email_of(&globals.config, &user_name(&q.from)).is_none() {
+                return send_msg(&bot, dialogue.chat_id(), NO_EMAIL_FOR_USER).await;
+            }
+        }
+        RenameDocumentAction::KeepDefault => {}
+        // Кнопка есть только в диалоге одной страницы.
+        RenameDocumentAction::Cancel => return ignore_stale_button(&bot, &q.id).await,
+    }
+
+    let by_email = match action {
+        RenameDocumentAction::KeepDefault => false,
+        RenameDocumentAction::SendEmail => true,
+        _ => {
+            return show_rename_document_dialog(
+                &globals,
+                bot,
+                dialogue,
+                (dialogue_message, pages, settings),
+                choice,
+            )
+            .await;
+        }
+    };
+
+    let options = DocumentOptions {
+        by_email,
+        ..DocumentOptions::new(DEFAULT_DOC_NAME, Some(&q.from), choice)
+    };
+
+    send_scanned_document(
+        scan_config(&globals, &settings),
         &bot,
         &dialogue,
         Some(dialogue_message),
-        DEFAULT_DOC_NAME,
         pages,
+        options,
     )
     .await?;
 
@@ -882,10 +3527,11 @@ async fn receive_document_rename_cancel(
 }
 
 async fn receive_document_name(
+    globals: Arc<Globals>,
     bot: Bot,
     dialogue: BotDialogue,
     msg: Message,
-    (dialogue_message, pages): (Message, Pages), // From `State::ReceiveScannedDocumentName`.
+    (dialogue_message, pages, settings, choice): (Message, Pages, ScanSettings, DocumentChoice), // From `State::ReceiveScannedDocumentName`.
 ) -> anyhow::Result<()> {
     let Some(name) = msg.text() else {
         return send_msg(&bot, msg.chat.id, INVALID_DOCUMENT_NAME).await;
@@ -893,54 +3539,419 @@ async fn receive_document_name(
 
     edit_msg(&bot, &dialogue_message, RENAME_DOCUMENT).await?;
 
-    send_pdf(&bot, &dialogue, None, name, pages).await?;
+    let options = DocumentOptions::new(name, msg.from(), choice);
+
+    send_scanned_document(
+        scan_config(&globals, &settings),
+        &bot,
+        &dialogue,
+        None,
+        pages,
+        options,
+    )
+    .await?;
 
     dialogue.update(BotState::Empty).await?;
 
     Ok(())
 }
 
-async fn send_pdf(
+async fn send_scanned_document(
+    config: Config,
     bot: &Bot,
     dialogue: &BotDialogue,
     dialogue_message: Option<Message>,
-    name: &str,
     pages: Pages,
+    options: DocumentOptions,
+) -> anyhow::Result<()> {
+    build_and_deliver_document(config, bot, dialogue, dialogue_message, &pages, options).await?;
+
+    // Если отправить не удалось, диалог остаётся в прежнем состоянии и документ
+    // можно отправить ещё раз.
+    pages.delete();
+
+    Ok(())
+}
+
+async fn build_and_deliver_document(
+    config: Config,
+    bot: &Bot,
+    dialogue: &BotDialogue,
+    dialogue_message: Option<Message>,
+    pages: &Pages,
+    options: DocumentOptions,
 ) -> anyhow::Result<()> {
+    let prepare_status = match options.format {
+        OutputFormat::Pdf => SCAN_PREPARE_PDF.to_owned(),
+        format => SCAN_PREPARE_DOCUMENT(format),
+    };
+
+    let first_status = if options.with_text {
+        SCAN_RECOGNIZE_TEXT
+    } else {
+        &prepare_status
+    };
+
     let dialogue_message = if let Some(dialogue_message) = dialogue_message {
-        bot.edit_message_text(dialogue.chat_id(), dialogue_message.id, SCAN_PREPARE_PDF)
-            .await?
+        edit_msg(bot, &dialogue_message, first_status).await?;
+        dialogue_message
     } else {
-        bot.send_message(dialogue.chat_id(), SCAN_PREPARE_PDF)
-            .await?
+        bot.send_message(dialogue.chat_id(), first_status).await?
     };
 
-    let pdf = tokio::task::spawn_blocking(|| convert_pages_to_document(pages))
+    let texts = if options.with_text {
+        let texts = tokio::task::spawn_blocking({
+            let config = config.clone();
+            let pages = pages.clone();
+            move || recognize_pages(&config.scan, &pages)
+        })
         .await
         .unwrap();
 
+        edit_msg(bot, &dialogue_message, &prepare_status).await?;
+
+        Some(texts)
+    } else {
+        None
+    };
+
+    let scan = ScanInfo::new(
+        &options.name,
+        &options.user,
+        format_extension(options.format),
+    );
+    let destination = options.destination.clone();
+    let by_email = options.by_email;
+
+    let document = tokio::task::spawn_blocking({
+        let config = config.clone();
+        let pages = pages.clone();
+        move || match options.format {
+            OutputFormat::Pdf => convert_pages_to_document(&config.scan, &options, &pages, texts),
+            OutputFormat::Tiff => convert_pages_to_tiff(&options, &pages),
+            OutputFormat::JpegZip => convert_pages_to_zip(&config.scan, &options, &pages),
+            OutputFormat::Jpeg | OutputFormat::Png => {
+                unreachable!("format is not for documents")
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    let document = match document {
+        Ok(document) => document,
+        Err(err) => {
+            log::error!("Failed to build document: {err:#}");
+            return edit_msg(bot, &dialogue_message, SCAN_ERROR).await;
+        }
+    };
+
     edit_msg(bot, &dialogue_message, MULTIPAGE_SCAN_RESULT).await?;
 
-    bot.send_document(
-        dialogue.chat_id(),
-        InputFile::memory(pdf).file_name(format!("{name}.pdf")),
-    )
-    .await?;
-    Ok(())
+    let delivery = match &config.smtp {
+        Some(smtp) if by_email => Delivery::Email(smtp),
+        _ => Delivery::Chat(find_destination(&config, destination.as_deref())),
+    };
+
+    let content = Content::File(document);
+    deliver_scan(bot, dialogue.chat_id(), delivery, scan, content).await
 }
 
-fn convert_pages_to_document(pages: Vec<Jpeg>) -> Vec<u8> {
-    // TODO: Remove hardcoded dpi
-    let pdf_builder = PdfBuilder::new("Document", 300.0);
+/// Распознаёт текст на всех страницах.
+///
+/// Если страницу не удалось распознать, то она попадёт в документ без текстового слоя.
+fn recognize_pages(config: &config::Scan, pages: &Pages) -> Vec<PageText> {
+    pages
+        .iter()
+        .enumerate()
+        .map(|(i, page)| {
+            load_page(config, Enhancement::Original, page)
+                .and_then(|jpeg| ocr::recognize(&jpeg, &config.ocr_languages, page.dpi))
+                .unwrap_or_else(|err| {
+                    log::error!("Failed to recognize text on page #{i}: {err:#}");
+                    PageText::default()
+                })
+        })
+        .collect()
+}
 
-    for page in pages {
-        pdf_builder.add_page(page).unwrap();
+fn convert_pages_to_document(
+    config: &config::Scan,
+    options: &DocumentOptions,
+    pages: &Pages,
+    texts: Option<Vec<PageText>>,
+) -> anyhow::Result<PathBuf> {
+    let mut pdf_builder = PdfBuilder::new(&options.name)
+        .author(&options.author)
+        .keywords(&config.pdf_keywords)
+        .outline(config.pdf_outline)
+        .archival(options.archival)
+        .buffer_in(pages.dir())
+        .context("creating image buffer")?;
+
+    match texts {
+        Some(texts) => {
+            let font = config.ocr_font.as_deref().ok_or_else(|| {
+                anyhow::anyhow!("font for text layer is not specified in the config")
+            })?;
+
+            pdf_builder = pdf_builder.text_font(font)?;
+
+            for (page, text) in pages.iter().zip(texts) {
+                let dpi = page.dpi as f32;
+                let page = load_pdf_page(config, options.enhancement, page)?;
+                pdf_builder.add_page_with_text(page, &text, dpi)?;
+            }
+        }
+        None => {
+            for page in pages.iter() {
+                let dpi = page.dpi as f32;
+                let page = load_pdf_page(config, options.enhancement, page)?;
+                pdf_builder.add_page(page, dpi)?;
+            }
+        }
     }
 
-    let mut pdf = Vec::new();
-    pdf_builder
-        .write_to(&mut io::BufWriter::with_capacity(128 * 1024, &mut pdf))
-        .unwrap();
+    let path = pages.output_path("pdf");
+    let file =
+        fs::File::create(&path).with_context(|| format!("creating file '{}'", path.display()))?;
+
+    pdf_builder.write_to(file)?;
+
+    Ok(path)
+}
+
+/// Собирает многостраничный TIFF из исходных пикселей страниц без потерь JPEG.
+fn convert_pages_to_tiff(options: &DocumentOptions, pages: &Pages) -> anyhow::Result<PathBuf> {
+    let path = pages.output_path("tiff");
+    let file =
+        fs::File::create(&path).with_context(|| format!("creating file '{}'", path.display()))?;
 
-    pdf
+    let mut tiff = TiffWriter::new(io::BufWriter::new(file))?;
+    for page in pages.iter() {
+        tiff.add_page(&load_raw_page(options.enhancement, page)?, page.dpi)?;
+    }
+    tiff.finish()?;
+
+    Ok(path)
+}
+
+/// Собирает ZIP архив со страницами в JPEG.
+fn convert_pages_to_zip(
+    config: &config::Scan,
+    options: &DocumentOptions,
+    pages: &Pages,
+) -> anyhow::Result<PathBuf> {
+    let path = pages.output_path("zip");
+    let file =
+        fs::File::create(&path).with_context(|| format!("creating file '{}'", path.display()))?;
+
+    // JPEG уже сжат, поэтому файлы хранятся в архиве как есть.
+    let file_options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+    let mut zip = ZipWriter::new(io::BufWriter::new(file));
+    for (i, page) in pages.iter().enumerate() {
+        let page = load_page(config, options.enhancement, page)?;
+        zip.start_file(
+            format!("{DEFAULT_SINGLE_PAGE_NAME} {:03}.jpg", i + 1),
+            file_options,
+        )?;
+        zip.write_all(&page.bytes)?;
+    }
+    zip.finish()?.flush()?;
+
+    Ok(path)
+}
+
+/// Сжимает отсканированную страницу в выбранный формат.
+fn encode_page(page: &Page, format: OutputFormat) -> anyhow::Result<Vec<u8>> {
+    match format {
+        OutputFormat::Jpeg => Ok(page.jpeg.bytes.clone()),
+        OutputFormat::Png => Ok(png::encode(&page.raw, page.dpi)),
+        OutputFormat::Tiff => {
+            let mut tiff = TiffWriter::new(Cursor::new(Vec::new()))?;
+            tiff.add_page(&page.raw, page.dpi)?;
+            Ok(tiff.finish()?.into_inner())
+        }
+        OutputFormat::Pdf | OutputFormat::JpegZip => unreachable!("format is not for single page"),
+    }
+}
+
+fn format_extension(format: OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::Pdf => "pdf",
+        OutputFormat::Jpeg => "jpg",
+        OutputFormat::Png => "png",
+        OutputFormat::Tiff => "tiff",
+        OutputFormat::JpegZip => "zip",
+    }
+}
+
+/// Имя пользователя Telegram или его id, если имени нет.
+fn user_name(user: &User) -> String {
+    user.username.clone().unwrap_or_else(|| user.id.to_string())
+}
+
+/// Место сохранения скана из конфига по его имени.
+fn find_destination<'a>(
+    config: &'a Config,
+    name: Option<&str>,
+) -> Option<(&'a str, &'a config::Destination)> {
+    let (name, destination) = config.destinations.get_key_value(name?)?;
+    Some((name.as_str(), destination))
+}
+
+/// Подпись места сохранения скана для кнопки.
+fn destination_title<'a>(config: &'a Config, name: Option<&str>) -> &'a str {
+    match find_destination(config, name) {
+        Some((name, destination)) => destination.title.as_deref().unwrap_or(name),
+        None => TELEGRAM_ONLY_DESTINATION,
+    }
+}
+
+/// Следующее место сохранения скана для переключения по кругу,
+/// после последнего из конфига скан только отправляется в чат.
+fn next_destination(config: &Config, name: Option<&str>) -> Option<String> {
+    let mut names = config.destinations.keys();
+    match name {
+        Some(name) => names.skip_while(|other| *other != name).nth(1).cloned(),
+        None => names.next().cloned(),
+    }
+}
+
+/// Настройки почты, если у пользователя указан адрес в конфиге.
+fn email_of<'a>(config: &'a Config, user: &str) -> Option<&'a config::Smtp> {
+    config
+        .smtp
+        .as_ref()
+        .filter(|smtp| smtp.recipients.contains_key(user))
+}
+
+/// Отправляет скан в чат или на почту и сообщает, куда он сохранён.
+async fn deliver_scan(
+    bot: &Bot,
+    chat_id: ChatId,
+    delivery: Delivery<'_>,
+    scan: ScanInfo,
+    content: Content,
+) -> anyhow::Result<()> {
+    match delivery {
+        Delivery::Chat(destination) => {
+            let file_name = format!("{}.{}", scan.name, scan.extension);
+            let document = match &content {
+                Content::Memory(bytes) => InputFile::memory(bytes.clone()),
+                Content::File(path) => InputFile::file(path),
+            };
+            bot.send_document(chat_id, document.file_name(file_name))
+                .await?;
+
+            let Some((name, destination)) = destination else {
+                return Ok(());
+            };
+
+            let title = destination.title.as_deref().unwrap_or(name);
+            match save_scan(destination::from_config(destination), scan, content).await {
+                Ok(path) => send_msg(bot, chat_id, &SCAN_SAVED(&path)).await,
+                Err(err) => {
+                    log::error!("Failed to save scan to '{name}': {err:#}");
+                    send_msg(bot, chat_id, &FAILED_TO_SAVE_SCAN(title)).await
+                }
+            }
+        }
+        Delivery::Email(smtp) => {
+            let sent = match destination::Email::new(smtp) {
+                Ok(email) => save_scan(Box::new(email), scan, content).await,
+                Err(err) => Err(err),
+            };
+
+            match sent {
+                Ok(address) => send_msg(bot, chat_id, &SCAN_SENT_BY_EMAIL(&address)).await,
+                Err(err) => {
+                    log::error!("Failed to send scan by email: {err:#}");
+                    send_msg(bot, chat_id, FAILED_TO_SEND_EMAIL).await
+                }
+            }
+        }
+    }
+}
+
+async fn save_scan(
+    destination: Box<dyn Destination>,
+    scan: ScanInfo,
+    content: Content,
+) -> anyhow::Result<String> {
+    tokio::task::spawn_blocking(move || destination.save(&scan, &content))
+        .await
+        .unwrap()
+}
+
+/// Следующий формат из списка для переключения по кругу.
+fn next_format(formats: &[OutputFormat], format: OutputFormat) -> OutputFormat {
+    let index = formats.iter().position(|&f| f == format).unwrap_or(0);
+    formats[(index + 1) % formats.len()]
+}
+
+/// Собирает миниатюры всех страниц документа в одно изображение.
+fn render_contact_sheet(config: &config::Scan, pages: &Pages) -> anyhow::Result<Jpeg> {
+    let mut sheet = ContactSheet::new(pages.len());
+
+    for (i, page) in pages.iter().enumerate() {
+        let image = libjpeg::decompress_jpeg(&page.load()?.bytes)?;
+        sheet.draw_page(i, &orientation::rotate(image, page.rotation));
+    }
+
+    Ok(scan::encode_jpeg(&sheet.finish(), config.page_quality))
+}
+
+/// Читает страницу с диска, поворачивает и обрабатывает её.
+///
+/// Повёрнутая или обработанная страница собирается из исходных пикселей,
+/// поэтому JPEG сжимается только один раз.
+fn load_page(
+    config: &config::Scan,
+    enhancement: Enhancement,
+    page: &SpooledPage,
+) -> anyhow::Result<Jpeg> {
+    if page.rotation == Rotation::None && matches!(enhancement, Enhancement::Original) {
+        return Ok(page.load()?);
+    }
+
+    let image = load_raw_page(enhancement, page)?;
+    Ok(scan::encode_jpeg(&image, config.page_quality))
+}
+
+/// Читает исходные пиксели страницы с диска, поворачивает и обрабатывает их.
+fn load_raw_page(enhancement: Enhancement, page: &SpooledPage) -> anyhow::Result<RawImage> {
+    let mut image = orientation::rotate(page.load_raw()?, page.rotation);
+    enhance_image(enhancement, &mut image);
+
+    Ok(image)
+}
+
+fn enhance_image(enhancement: Enhancement, image: &mut RawImage) {
+    match enhancement {
+        Enhancement::Original => {}
+        Enhancement::Document => enhance::document(image),
+        Enhancement::Photo => enhance::photo(image),
+    }
+}
+
+/// Готовит изображение страницы для PDF в соответствии с профилем сканирования.
+fn load_pdf_page(
+    config: &config::Scan,
+    enhancement: Enhancement,
+    page: &SpooledPage,
+) -> anyhow::Result<PageImage> {
+    match config.profile {
+        ScanProfile::Photo => Ok(load_page(config, enhancement, page)?.into()),
+        // Чёрно-белые пиксели сжимаются напрямую, без артефактов JPEG.
+        ScanProfile::Text => {
+            let mut image = load_raw_page(enhancement, page)?;
+            if !bilevel::is_bilevel(&image) {
+                bilevel::binarize(&mut image);
+            }
+
+            Ok(bilevel::compress_g4(&image)?.into())
+        }
+    }
 }
@@ -1,3 +1,9 @@
+use crate::{config::ScanProfile, print::ImageFit};
+use libcups::{
+    job::{JobId, JobState},
+    options::{ColorMode, MediaFormat, PrintQuality, Sides},
+    printer::PrinterState,
+};
 use once_cell::sync::Lazy;
 use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
 
@@ -7,7 +13,8 @@ pub const UNALLOWED_USER: &str =
 pub const HELLO: &str = "\
 👋 Добро пожаловать в бот для печати и сканирования!
 
-Чтобы распечатать документ просто отправьте отправьте PDF или DOCX файл в этот чат.
+Чтобы распечатать документ просто отправьте PDF или DOCX файл в этот чат.
+Изображения JPEG и PNG можно отправить файлом или как фото.
     
 Для сканирования документа отправьте команду /scan и следуйте инструкции на экране.
 
@@ -17,27 +24,328 @@ pub const HELLO: &str = "\
 pub const HELP: &str = "\
 🖨️ Этот бот предназначен для быстрого доступа к домашнему принтеру через Telegram.
 
-Чтобы распечатать документ просто отправьте отправьте PDF или DOCX файл в этот чат.
+Чтобы распечатать документ просто отправьте PDF или DOCX файл в этот чат.
+Изображения JPEG и PNG можно отправить файлом или как фото.
 
-Для сканирования документа отправьте команду /scan и следуйте инструкции на экране.";
+Для сканирования документа отправьте команду /scan и следуйте инструкции на экране.
+Чтобы сразу выбрать пресет сканирования из конфига, укажите его имя: /scan receipt
+
+Для копирования документа отправьте команду /copy: отсканированные страницы сразу отправятся на печать.
+
+Очередь печати принтера доступна по команде /queue, а состояние принтера и сканера — по команде /status";
+
+pub const SESSION_RESTORED: &str = "🔄 Бот был перезапущен, сканирование продолжается";
+
+pub const SCAN_INTERRUPTED: &str =
+    "⚠️ Сканирование прервано перезапуском бота. Отправьте команду /scan, чтобы начать заново";
 
 pub const BOT_BUSY: &str =
-    "🕓 Бот занят сканированием документа. Отправьте команду после завершения сканирования.";
+    "🕓 Бот занят печатью или сканированием документа. Отправьте команду после их завершения.";
 
 pub const PRINT_COMMAND_TEXT: &str =
-    "🖨️ Для печати документа просто отправьте PDF или DOCX файл в этот чат!";
+    "🖨️ Для печати документа просто отправьте PDF или DOCX файл, изображение JPEG или PNG в этот чат!";
 
 pub const NO_PRINTER_IN_CFG: &str =
     "🖨️ Принтер не указан в конфиге. Измените конфигурационный файл и перезапустите бота!";
 
 pub const UNSUPPORTED_DOCUMENT: &str = "😓 Извините, ваш документ не поддерживается.";
 
-pub const SUCCESSFUL_PRINT: &dyn Fn(&str) -> String =
-    &|doc_name| format!("📄 Документ \"{doc_name}\" успешно отправлен на печать!");
+pub const PRINT_OPTIONS: &dyn Fn(&str, usize, &str, &str, &str, &str, &str) -> String =
+    &|doc_name, copies, sides, color, paper, quality, pages| {
+        format!(
+            "🖨️ Печать документа \"{doc_name}\"\n\n\
+            Копий: {copies}\n\
+            Стороны: {sides}\n\
+            Цвет: {color}\n\
+            Бумага: {paper}\n\
+            Качество: {quality}\n\
+            Страницы: {pages}"
+        )
+    };
+
+#[rustfmt::skip]
+pub static PRINT_OPTION_BUTTONS: Lazy<[(&str, (usize, &str)); 9]> = Lazy::new(|| {
+    [
+        (PrintAction::LessCopies.into(), (0, "➖ Копия")),
+        (PrintAction::MoreCopies.into(), (0, "➕ Копия")),
+        (PrintAction::Sides.into(), (1, "📄 Стороны")),
+        (PrintAction::Color.into(), (1, "🎨 Цвет")),
+        (PrintAction::Paper.into(), (2, "📏 Бумага")),
+        (PrintAction::Quality.into(), (2, "✨ Качество")),
+        (PrintAction::Pages.into(), (3, "📑 Страницы")),
+        (PrintAction::Cancel.into(), (4, "⛔ Отмена")),
+        (PrintAction::Print.into(), (4, "🖨️ Печать")),
+    ]
+});
+
+/// Изображение печатается на одной странице, поэтому вместо выбора страниц выбирается размещение.
+#[rustfmt::skip]
+pub static PRINT_IMAGE_OPTION_BUTTONS: Lazy<[(&str, (usize, &str)); 9]> = Lazy::new(|| {
+    [
+        (PrintAction::LessCopies.into(), (0, "➖ Копия")),
+        (PrintAction::MoreCopies.into(), (0, "➕ Копия")),
+        (PrintAction::Sides.into(), (1, "📄 Стороны")),
+        (PrintAction::Color.into(), (1, "🎨 Цвет")),
+        (PrintAction::Paper.into(), (2, "📏 Бумага")),
+        (PrintAction::Quality.into(), (2, "✨ Качество")),
+        (PrintAction::ImageFit.into(), (3, "🖼️ Размещение")),
+        (PrintAction::Cancel.into(), (4, "⛔ Отмена")),
+        (PrintAction::Print.into(), (4, "🖨️ Печать")),
+    ]
+});
+
+#[derive(Clone, Copy, strum::Display, strum::IntoStaticStr, strum::EnumString)]
+pub enum PrintAction {
+    LessCopies,
+    MoreCopies,
+    Sides,
+    Color,
+    Paper,
+    Quality,
+    Pages,
+    AllPages,
+    ImageFit,
+    Cancel,
+    Print,
+}
+
+/// Наибольшее количество копий, которое можно выбрать.
+pub const MAX_COPIES: usize = 99;
+
+/// Значения настроек печати в порядке переключения.
+pub const SIDES_CHOICES: [Sides; 3] = [
+    Sides::OneSide,
+    Sides::TwoSidedPortrait,
+    Sides::TwoSidedLandscape,
+];
+
+pub const COLOR_CHOICES: [ColorMode; 3] =
+    [ColorMode::Auto, ColorMode::Color, ColorMode::Monochrome];
+
+pub const PAPER_CHOICES: [MediaFormat; 5] = [
+    MediaFormat::A4,
+    MediaFormat::A5,
+    MediaFormat::A3,
+    MediaFormat::Letter,
+    MediaFormat::Legal,
+];
+
+pub const PRINT_QUALITY_CHOICES: [PrintQuality; 3] = [
+    PrintQuality::Draft,
+    PrintQuality::Normal,
+    PrintQuality::High,
+];
+
+pub const IMAGE_FIT_CHOICES: [ImageFit; 3] = [ImageFit::Fit, ImageFit::Fill, ImageFit::ActualSize];
+
+pub const SIDES_NAME: &dyn Fn(Sides) -> &'static str = &|sides| match sides {
+    Sides::OneSide => "односторонняя",
+    Sides::TwoSidedPortrait => "двусторонняя, по длинному краю",
+    Sides::TwoSidedLandscape => "двусторонняя, по короткому краю",
+};
+
+pub const COLOR_NAME: &dyn Fn(ColorMode) -> &'static str = &|color| match color {
+    ColorMode::Auto => "авто",
+    ColorMode::Color => "цветная",
+    ColorMode::Monochrome => "чёрно-белая",
+};
+
+pub const QUALITY_NAME: &dyn Fn(PrintQuality) -> &'static str = &|quality| match quality {
+    PrintQuality::Draft => "черновик",
+    PrintQuality::Normal => "обычное",
+    PrintQuality::High => "высокое",
+};
+
+pub const IMAGE_FIT_NAME: &dyn Fn(ImageFit) -> &'static str = &|fit| match fit {
+    ImageFit::Fit => "по размеру листа",
+    ImageFit::Fill => "заполнить",
+    ImageFit::ActualSize => "оригинальный размер",
+};
+
+pub const PRINT_IMAGE_FIT: &dyn Fn(&str) -> String = &|fit| format!("Размещение: {fit}");
+
+/// Имя фото, отправленного не файлом.
+pub const DEFAULT_PHOTO_NAME: &str = "Фото.jpg";
+
+/// Значение настройки печати, которое берётся из настроек принтера.
+pub const PRINTER_DEFAULT_SETTING: &str = "как в принтере";
+
+pub const ALL_PAGES: &str = "все";
+
+pub const ENTER_PAGE_RANGES: &str = "📑 Введите номера страниц, например: 1-3, 5";
+
+pub const ALL_PAGES_BUTTON: &str = "📑 Все страницы";
+
+pub const INVALID_PAGE_RANGES: &str =
+    "⚠️ Неверный диапазон страниц. Введите номера страниц, например: 1-3, 5";
+
+pub const PRINT_PROGRESS: &dyn Fn(&str) -> String =
+    &|doc_name| format!("⏳ Отправка документа \"{doc_name}\" на печать...");
+
+pub const PRINT_CANCELLED: &str = "👍 Печать отменена";
+
+pub const PRINT_JOB_QUEUED: &dyn Fn(&str) -> String =
+    &|doc_name| format!("🕓 Документ \"{doc_name}\" в очереди на печать");
+
+pub const PRINT_JOB_PRINTING: &dyn Fn(&str) -> String =
+    &|doc_name| format!("🖨️ Документ \"{doc_name}\" печатается...");
+
+pub const PRINT_JOB_PROGRESS: &dyn Fn(&str, u32, u32) -> String =
+    &|doc_name, done, total| format!("🖨️ Документ \"{doc_name}\" печатается: {done}/{total}");
+
+pub const PRINT_JOB_PAGES: &dyn Fn(&str, u32) -> String =
+    &|doc_name, done| format!("🖨️ Документ \"{doc_name}\" печатается, страниц готово: {done}");
+
+pub const PRINT_JOB_DONE: &dyn Fn(&str) -> String =
+    &|doc_name| format!("✅ Документ \"{doc_name}\" напечатан");
+
+pub const PRINT_JOB_CANCELED: &dyn Fn(&str) -> String =
+    &|doc_name| format!("🚫 Печать документа \"{doc_name}\" отменена");
+
+pub const PRINT_JOB_ERROR: &dyn Fn(&str, &str) -> String =
+    &|doc_name, reason| format!("⚠️ Ошибка печати документа \"{doc_name}\": {reason}");
+
+pub const PRINT_JOB_UNTRACKED: &dyn Fn(&str) -> String = &|doc_name| {
+    format!("❔ Документ \"{doc_name}\" отправлен на печать, но его статус больше не отслеживается")
+};
+
+pub const CANCEL_PRINT_JOB_BUTTON: &str = "⛔ Отменить печать";
+
+/// Префикс данных кнопки отмены печати, после которого идёт номер задачи.
+pub const CANCEL_PRINT_JOB_PREFIX: &str = "CancelPrintJob:";
+
+pub const CANCEL_PRINT_JOB_FORBIDDEN: &str =
+    "🚫 Отменить печать может только отправивший документ пользователь или администратор";
+
+pub const FAILED_TO_CANCEL_PRINT_JOB: &str = "⚠️ Ошибка отмены печати";
+
+pub const PRINT_QUEUE: &dyn Fn(&str, &str) -> String = &|active, completed| {
+    format!("🖨️ Очередь печати:\n{active}\n\n✅ Недавно завершённые:\n{completed}")
+};
+
+pub const QUEUE_JOB: &dyn Fn(JobId, &str, &str, &str) -> String =
+    &|id, title, user, state| format!("#{id} \"{title}\" — {user}, {state}");
+
+pub const QUEUE_JOB_PAGES: &dyn Fn(&str, u32) -> String =
+    &|state, pages| format!("{state}, страниц: {pages}");
+
+pub const QUEUE_EMPTY: &str = "пусто";
+
+/// Количество задач каждого вида в ответе на `/queue`.
+pub const QUEUE_ACTIVE_JOBS: usize = 10;
+pub const QUEUE_COMPLETED_JOBS: usize = 5;
+
+pub const FAILED_TO_READ_QUEUE: &str = "⚠️ Ошибка получения очереди печати";
+
+pub const JOB_STATE_NAME: &dyn Fn(JobState) -> &'static str = &|state| match state {
+    JobState::Pending => "в очереди",
+    JobState::Held => "отложено",
+    JobState::Processing => "печатается",
+    JobState::Stopped => "остановлено",
+    JobState::Canceled => "отменено",
+    JobState::Aborted => "прервано",
+    JobState::Completed => "готово",
+};
+
+pub const DEVICES_STATUS: &dyn Fn(&str, &str) -> String = &|printer, scanner| {
+    format!("📊 Состояние устройств\n\n🖨️ Принтер: {printer}\n\n📠 Сканер: {scanner}")
+};
+
+pub const PRINTER_STATE_NAME: &dyn Fn(PrinterState) -> &'static str = &|state| match state {
+    PrinterState::Idle => "готов",
+    PrinterState::Processing => "печатает",
+    PrinterState::Stopped => "остановлен",
+};
+
+pub const PRINTER_NOT_ACCEPTING_JOBS: &str = "🚫 не принимает задания";
+
+pub const PRINTER_PROBLEM: &dyn Fn(&str) -> String = &|problem| format!("⚠️ {problem}");
+
+pub const PRINTER_MESSAGE: &dyn Fn(&str) -> String = &|message| format!("💬 {message}");
+
+pub const PRINTER_QUEUE_LENGTH: &dyn Fn(u32) -> String =
+    &|count| format!("Задач в очереди: {count}");
+
+pub const PRINTER_SUPPLIES: &str = "Расходные материалы:";
+
+pub const MARKER_LEVEL: &dyn Fn(&str, &str) -> String = &|name, level| format!("• {name}: {level}");
+
+pub const MARKER_LOW_LEVEL: &dyn Fn(&str, &str) -> String =
+    &|name, level| format!("• {name}: {level} ⚠️ заканчивается");
+
+pub const MARKER_LEVEL_UNKNOWN: &str = "нет данных";
+
+pub const COPY_MODE: &str = "📠 Копирование";
+
+pub const COPY_OPTIONS: &dyn Fn(usize, usize, &str) -> String = &|pages, copies, sides| {
+    format!(
+        "📠 Копирование\n\n\
+        Страниц: {pages}\n\
+        Копий: {copies}\n\
+        Стороны: {sides}"
+    )
+};
+
+#[rustfmt::skip]
+pub static COPY_OPTION_BUTTONS: Lazy<[(&str, (usize, &str)); 5]> = Lazy::new(|| {
+    [
+        (PrintAction::LessCopies.into(), (0, "➖ Копия")),
+        (PrintAction::MoreCopies.into(), (0, "➕ Копия")),
+        (PrintAction::Sides.into(), (1, "📄 Стороны")),
+        (PrintAction::Cancel.into(), (2, "⛔ Отмена")),
+        (PrintAction::Print.into(), (2, "🖨️ Печать")),
+    ]
+});
+
+pub const DEFAULT_COPY_NAME: &str = "Копия";
+
+pub const SUPPLY_LOW: &dyn Fn(&str, u8) -> String =
+    &|name, level| format!("🪫 В принтере заканчивается \"{name}\": осталось {level}%");
+
+pub const DEVICE_NOT_IN_CFG: &str = "не указан в конфиге";
+
+pub const DEVICE_AVAILABLE: &str = "доступен";
+
+pub const DEVICE_UNAVAILABLE: &str = "недоступен";
+
+pub const PRINT_JOB_ABORTED: &str = "задание прервано";
+
+pub const PRINTER_STOPPED: &str = "принтер остановлен";
+
+/// Причины из `printer-state-reasons` без суффиксов `-error`, `-warning` и `-report`.
+pub const PRINTER_STATE_REASONS: [(&str, &str); 13] = [
+    ("media-empty", "нет бумаги"),
+    ("media-needed", "нет бумаги"),
+    ("media-jam", "замятие бумаги"),
+    ("toner-empty", "закончился тонер"),
+    ("marker-supply-empty", "закончился тонер"),
+    ("door-open", "открыта дверца"),
+    ("cover-open", "открыта крышка"),
+    ("input-tray-missing", "нет лотка для бумаги"),
+    ("output-area-full", "лоток для вывода переполнен"),
+    ("offline", "принтер не в сети"),
+    ("connecting-to-device", "нет связи с принтером"),
+    ("paused", "принтер приостановлен"),
+    ("shutdown", "принтер выключен"),
+];
 
 pub const FAILED_TO_PRINT: &dyn Fn(&str) -> String =
     &|doc_name| format!("⚠️ Ошибка печати документа \"{doc_name}\"!");
 
+pub const SELECT_SCAN_PRESET: &str = "🗂️ Выберите пресет сканирования";
+
+pub const NO_SCAN_PRESET_BUTTON: &str = "⚙️ Без пресета";
+
+#[derive(Clone, Copy, strum::Display, strum::IntoStaticStr, strum::EnumString)]
+pub enum ScanPresetAction {
+    NoPreset,
+}
+
+pub const UNKNOWN_SCAN_PRESET: &dyn Fn(&str) -> String =
+    &|name| format!("😓 Пресет сканирования \"{name}\" не найден в конфиге");
+
+pub const SCAN_PRESET: &dyn Fn(&str) -> String = &|name| format!("🗂️ Пресет: {name}");
+
 pub const SELECT_SCAN_MODE: &str = "Выберите количество страниц в документе";
 
 #[rustfmt::skip]
@@ -48,7 +356,15 @@ pub static SCAN_MODE_BUTTONS: Lazy<[(&str, (usize, &str)); 2]> = Lazy::new(|| {
     ]
 });
 
-#[derive(Clone, Copy, strum::Display, strum::IntoStaticStr, strum::EnumString)]
+#[derive(
+    Clone,
+    Copy,
+    strum::Display,
+    strum::IntoStaticStr,
+    strum::EnumString,
+    serde::Serialize,
+    serde::Deserialize,
+)]
 pub enum ScanMode {
     SinglePage,
     Document,
@@ -57,53 +373,236 @@ pub enum ScanMode {
 pub const SELECT_SCAN_ACTION: &str = "Выберите действие";
 
 #[rustfmt::skip]
-pub static SCAN_ACTIONS_BUTTONS: Lazy<[(&str, (usize, &str)); 3]> = Lazy::new(|| {
+pub static SCAN_ACTIONS_BUTTONS: Lazy<[(&str, (usize, &str)); 4]> = Lazy::new(|| {
     [
         (ScanAction::Cancel.into(), (0, "⛔ Прервать сканирование")),
         (ScanAction::Scan.into(), (1, "🚀 Начать")),
         (ScanAction::Preview.into(), (1, "👀 Превью")),
+        (ScanAction::Settings.into(), (2, "⚙️ Настройки")),
     ]
 });
 
 pub const SELECT_DOCUMENT_ACTION: &dyn Fn(usize) -> String =
     &|count| format!("📄 Страниц в документе: {count}. Выберите действие");
 
+pub const LAST_PAGE_ROTATION: &dyn Fn(u16) -> String =
+    &|degrees| format!("🔄 Последняя страница будет повёрнута на {degrees}°");
+
+pub const SELECT_DOCUMENT_ACTION_WITH_BLANK: &dyn Fn(usize, usize) -> String =
+    &|count, blank| format!("📄 Страниц: {count}, пустых пропущено: {blank}. Выберите действие");
+
 #[rustfmt::skip]
-pub static DOCUMENT_ACTION_BUTTONS: Lazy<[(&str, (usize, &str)); 4]> = Lazy::new(|| {
+pub static DOCUMENT_ACTION_BUTTONS: Lazy<[(&str, (usize, &str)); 9]> = Lazy::new(|| {
     [
         (ScanAction::Cancel.into(), (0, "⛔ Прервать сканирование")),
         (ScanAction::Scan.into(), (1, "🚀 Добавить страницу")),
         (ScanAction::Preview.into(), (1, "👀 Превью страницы")),
-        (ScanAction::Done.into(), (2, "📥 Завершить")),
+        (ScanAction::RotateLast.into(), (2, "🔄 Повернуть последнюю")),
+        (ScanAction::DeleteLast.into(), (2, "🗑️ Удалить последнюю")),
+        (ScanAction::Pages.into(), (3, "📑 Страницы")),
+        (ScanAction::ContactSheet.into(), (3, "🖼️ Миниатюры")),
+        (ScanAction::Done.into(), (4, "📥 Завершить")),
+        (ScanAction::DoneWithText.into(), (4, "🔎 PDF с текстом")),
     ]
 });
 
 #[derive(Clone, Copy, strum::Display, strum::IntoStaticStr, strum::EnumString)]
 pub enum ScanAction {
     Done,
+    DoneWithText,
     Scan,
     Preview,
+    RotateLast,
+    DeleteLast,
+    Pages,
+    ContactSheet,
+    Settings,
     Cancel,
 }
 
+pub const SCAN_SETTINGS_LOADING: &str = "⚙️ Чтение настроек сканера...";
+
+pub const SCAN_SETTINGS: &dyn Fn(&str, &str, &str, &str, &str) -> String =
+    &|resolution, mode, source, quality, profile| {
+        format!(
+            "⚙️ Настройки сканирования\n\n\
+            Разрешение: {resolution}\n\
+            Режим: {mode}\n\
+            Источник: {source}\n\
+            Качество JPEG: {quality}\n\
+            Профиль: {profile}"
+        )
+    };
+
+/// Значение настройки, которое берётся из конфига.
+pub const DEFAULT_SETTING: &str = "по умолчанию";
+
+#[rustfmt::skip]
+pub static SCAN_SETTING_BUTTONS: Lazy<[(&str, (usize, &str)); 6]> = Lazy::new(|| {
+    [
+        (ScanSetting::Resolution.into(), (0, "🔍 Разрешение")),
+        (ScanSetting::Mode.into(), (0, "🎨 Режим")),
+        (ScanSetting::Source.into(), (1, "📥 Источник")),
+        (ScanSetting::Quality.into(), (1, "🗜️ Качество")),
+        (ScanSetting::Profile.into(), (2, "📄 Профиль")),
+        (ScanSetting::Back.into(), (3, "↩️ Назад")),
+    ]
+});
+
+#[derive(
+    Clone,
+    Copy,
+    strum::Display,
+    strum::IntoStaticStr,
+    strum::EnumString,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub enum ScanSetting {
+    Resolution,
+    Mode,
+    Source,
+    Quality,
+    Profile,
+    Back,
+}
+
+pub const SELECT_SCAN_SETTING_VALUE: &str = "⚙️ Выберите значение";
+
+/// Количество кнопок со значениями настройки в одном ряду.
+pub const SETTING_VALUES_PER_ROW: usize = 3;
+
+/// Качество JPEG, из которого выбирает пользователь.
+pub const QUALITY_CHOICES: [u8; 5] = [50, 70, 80, 90, 95];
+
+pub const PROFILE_CHOICES: [ScanProfile; 2] = [ScanProfile::Photo, ScanProfile::Text];
+
+pub const PROFILE_NAME: &dyn Fn(ScanProfile) -> &'static str = &|profile| match profile {
+    ScanProfile::Photo => "фото (JPEG)",
+    ScanProfile::Text => "текст (чёрно-белый)",
+};
+
+pub const DEFAULT_SETTING_BUTTON: &str = "♻️ По умолчанию";
+
+#[derive(Clone, Copy, strum::Display, strum::IntoStaticStr, strum::EnumString)]
+pub enum SettingValue {
+    Default,
+    Back,
+}
+
+pub const SELECT_DOCUMENT_PAGE: &str = "📑 Выберите страницу";
+
+/// Количество кнопок с номерами страниц в одном ряду.
+pub const PAGES_PER_ROW: usize = 5;
+
+pub const SELECT_PAGE_ACTION: &dyn Fn(usize, usize) -> String =
+    &|number, count| format!("📄 Страница {number} из {count}. Выберите действие");
+
+pub const PAGE_ROTATION: &dyn Fn(u16) -> String =
+    &|degrees| format!("🔄 Страница будет повёрнута на {degrees}°");
+
+#[rustfmt::skip]
+pub static PAGE_ACTION_BUTTONS: Lazy<[(&str, (usize, &str)); 6]> = Lazy::new(|| {
+    [
+        (PageAction::MoveUp.into(), (0, "⬆️ Раньше")),
+        (PageAction::MoveDown.into(), (0, "⬇️ Позже")),
+        (PageAction::Rotate.into(), (1, "🔄 Повернуть")),
+        (PageAction::Rescan.into(), (1, "🔁 Пересканировать")),
+        (PageAction::Delete.into(), (2, "🗑️ Удалить")),
+        (PageAction::Back.into(), (2, "↩️ Назад")),
+    ]
+});
+
+#[derive(Clone, Copy, strum::Display, strum::IntoStaticStr, strum::EnumString)]
+pub enum PageAction {
+    MoveUp,
+    MoveDown,
+    Rotate,
+    Rescan,
+    Delete,
+    Back,
+}
+
+pub const PAGE_SELECTOR_BACK_BUTTON: &str = "↩️ Назад";
+
+pub const SELECT_ENHANCEMENT: &str = "🎨 Выберите обработку страниц";
+
+/// Кнопка с вариантом, который задан в пресете сканирования.
+pub const PRESET_CHOICE_BUTTON: &dyn Fn(&str) -> String = &|title| format!("{title} ⭐");
+
+#[rustfmt::skip]
+pub static ENHANCEMENT_BUTTONS: Lazy<[(&str, (usize, &str)); 3]> = Lazy::new(|| {
+    [
+        (Enhancement::Original.into(), (0, "📄 Оригинал")),
+        (Enhancement::Document.into(), (0, "📝 Документ")),
+        (Enhancement::Photo.into(), (0, "🖼️ Фото")),
+    ]
+});
+
+/// В конфиге обработка записывается в нижнем регистре, например `enhancement = "document"`.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    strum::Display,
+    strum::IntoStaticStr,
+    strum::EnumString,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub enum Enhancement {
+    #[serde(alias = "original")]
+    Original,
+    #[serde(alias = "document")]
+    Document,
+    #[serde(alias = "photo")]
+    Photo,
+}
+
 pub const SCAN_PREPAIR: &str = "⚙️ Подготовка к сканированию...";
 
 pub const SCAN_PROGRESS: &str = "⏳ Сканирование страницы...";
 
 pub const STOP_SCANNER: &str = "⚙️ Остановка сканера...";
 
+pub const SCAN_DESKEW: &str = "⚙️ Выравнивание страницы...";
+
 pub const SCAN_COMPRESS_JPEG: &str = "⚙️ Подготовка JPEG...";
 
+pub const SCAN_DETECT_ORIENTATION: &str = "⚙️ Определение ориентации страницы...";
+
+pub const SCAN_PREPARE_CONTACT_SHEET: &str = "⚙️ Подготовка миниатюр...";
+
 pub const SCAN_PREVIEW_DONE: &str = "👀 Превью страницы:";
 
 pub const SINGLE_PAGE_SCAN_RESULT: &str = "📄 Отсканированная страница:";
 
 pub const SCAN_ERROR: &str = "⚠️ Ошибка сканирования";
 
+pub const SCAN_RECOGNIZE_TEXT: &str = "⚙️ Распознавание текста...";
+
 pub const SCAN_PREPARE_PDF: &str = "⚙️ Подготовка PDF документа...";
 
+pub const SCAN_PREPARE_DOCUMENT: &dyn Fn(OutputFormat) -> String =
+    &|format| format!("⚙️ Подготовка документа {format}...");
+
 pub const MULTIPAGE_SCAN_RESULT: &str = "📕 Отсканированный документ:";
 
+pub const SCAN_SAVED: &dyn Fn(&str) -> String = &|path| format!("💾 Скан сохранён: {path}");
+
+pub const SCAN_SENT_BY_EMAIL: &dyn Fn(&str) -> String =
+    &|address| format!("📧 Скан отправлен на {address}");
+
+pub const FAILED_TO_SEND_EMAIL: &str = "⚠️ Ошибка отправки скана на почту";
+
+pub const NO_EMAIL_FOR_USER: &str =
+    "📧 Ваш адрес почты не указан в конфиге. Обратитесь к администратору";
+
+pub const FAILED_TO_SAVE_SCAN: &dyn Fn(&str) -> String =
+    &|destination| format!("⚠️ Ошибка сохранения скана в \"{destination}\"");
+
 #[rustfmt::skip]
 pub static SCAN_CANCEL: Lazy<[(&str, (usize, &str)); 1]> = Lazy::new(|| {
     [
@@ -131,12 +630,63 @@ pub enum ScanCancel {
 
 pub const RENAME_DOCUMENT: &str = "🏷️ Введите имя документа:";
 
-#[rustfmt::skip]
-pub static RENAME_DOCUMENT_BUTTONS: Lazy<[(&str, (usize, &str)); 1]> = Lazy::new(|| {
-    [
-        ("-", (0, "📥 Оставить по-умолчанию")),
-    ]
-});
+pub const KEEP_DEFAULT_NAME_BUTTON: &str = "📥 Оставить по-умолчанию";
+
+pub const ARCHIVAL_OFF_BUTTON: &str = "🗄️ PDF/A для архива: выкл";
+
+pub const ARCHIVAL_ON_BUTTON: &str = "🗄️ PDF/A для архива: вкл";
+
+pub const OUTPUT_FORMAT_BUTTON: &dyn Fn(OutputFormat) -> String =
+    &|format| format!("📎 Формат: {format}");
+
+pub const DESTINATION_BUTTON: &dyn Fn(&str) -> String = &|title| format!("📤 Сохранить: {title}");
+
+pub const EMAIL_BUTTON: &str = "📧 На почту";
+
+pub const CANCEL_BUTTON: &str = "⛔ Отмена";
+
+/// Подпись кнопки, когда скан только отправляется в чат.
+pub const TELEGRAM_ONLY_DESTINATION: &str = "только в чат";
+
+#[derive(Clone, Copy, strum::Display, strum::IntoStaticStr, strum::EnumString)]
+pub enum RenameDocumentAction {
+    KeepDefault,
+    ToggleArchival,
+    NextFormat,
+    NextDestination,
+    SendEmail,
+    Cancel,
+}
+
+/// В конфиге формат записывается как расширение файла, например `format = "pdf"`.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, strum::Display, serde::Serialize, serde::Deserialize,
+)]
+pub enum OutputFormat {
+    #[strum(to_string = "PDF")]
+    #[serde(alias = "pdf")]
+    Pdf,
+    #[strum(to_string = "JPEG")]
+    #[serde(alias = "jpeg")]
+    Jpeg,
+    #[strum(to_string = "PNG")]
+    #[serde(alias = "png")]
+    Png,
+    #[strum(to_string = "TIFF")]
+    #[serde(alias = "tiff")]
+    Tiff,
+    #[strum(to_string = "ZIP с JPEG")]
+    #[serde(alias = "zip")]
+    JpegZip,
+}
+
+/// Форматы отсканированной страницы в порядке переключения.
+pub const PAGE_FORMATS: [OutputFormat; 3] =
+    [OutputFormat::Jpeg, OutputFormat::Png, OutputFormat::Tiff];
+
+/// Форматы отсканированного документа в порядке переключения.
+pub const DOCUMENT_FORMATS: [OutputFormat; 3] =
+    [OutputFormat::Pdf, OutputFormat::Tiff, OutputFormat::JpegZip];
 
 pub const INVALID_DOCUMENT_NAME: &str = "🏷️ Введите имя документа:";
 
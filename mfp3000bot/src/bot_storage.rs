@@ -0,0 +1,164 @@
+use crate::bot::BotState;
+use anyhow::Context;
+use libcups::job::JobId;
+use sqlx::sqlite::SqlitePool;
+use std::{collections::HashMap, path::Path, sync::Arc};
+use teloxide::{
+    dispatching::dialogue::{
+        serializer::Json, ErasedStorage, InMemStorage, SqliteStorage, Storage,
+    },
+    types::ChatId,
+};
+use tokio::sync::{Mutex, MutexGuard};
+
+pub type DialogueStorage = ErasedStorage<BotState>;
+
+/// Open the storage of dialogues and read chats, which have stored dialogues.
+///
+/// Dialogues are kept in memory and lost on restart, if `path` is not specified.
+pub async fn open(path: Option<&Path>) -> anyhow::Result<(Arc<DialogueStorage>, Vec<ChatId>)> {
+    let Some(path) = path else {
+        return Ok((InMemStorage::<BotState>::new().erase(), Vec::new()));
+    };
+
+    let dpath = path.display();
+    let path_str = utf8_path(path)?;
+
+    let storage = SqliteStorage::open(path_str, Json)
+        .await
+        .with_context(|| format!("opening database '{dpath}'"))?;
+
+    let chats = stored_chats(path_str)
+        .await
+        .with_context(|| format!("reading dialogues from '{dpath}'"))?;
+
+    log::debug!(
+        "Open dialogue storage '{dpath}' with {} dialogues",
+        chats.len()
+    );
+
+    Ok((storage.erase(), chats))
+}
+
+fn utf8_path(path: &Path) -> anyhow::Result<&str> {
+    path.to_str()
+        .with_context(|| format!("path '{}' is not valid UTF-8", path.display()))
+}
+
+/// Telegram users, who sent print jobs, so only they can cancel them.
+///
+/// Owners are kept in the same database as dialogues, so they survive restarts.
+/// Without the database they are kept only in memory.
+pub struct PrintJobOwners {
+    owners: Mutex<HashMap<JobId, String>>,
+    pool: Option<SqlitePool>,
+}
+
+impl PrintJobOwners {
+    /// Open the table of owners in the dialogue database and read it.
+    pub async fn open(path: Option<&Path>) -> anyhow::Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self {
+                owners: Mutex::default(),
+                pool: None,
+            });
+        };
+
+        let dpath = path.display();
+        let pool = SqlitePool::connect(&format!("sqlite:{}", utf8_path(path)?))
+            .await
+            .with_context(|| format!("opening database '{dpath}'"))?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS print_job_owners (
+                job_id INTEGER PRIMARY KEY,
+                user TEXT NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await
+        .with_context(|| format!("creating print job owners table in '{dpath}'"))?;
+
+        let owners =
+            sqlx::query_as::<_, (i32, String)>("SELECT job_id, user FROM print_job_owners")
+                .fetch_all(&pool)
+                .await
+                .with_context(|| format!("reading print job owners from '{dpath}'"))?;
+
+        Ok(Self {
+            owners: Mutex::new(
+                owners
+                    .into_iter()
+                    .map(|(job, user)| (JobId(job), user))
+                    .collect(),
+            ),
+            pool: Some(pool),
+        })
+    }
+
+    /// Owners of all known jobs.
+    pub async fn lock(&self) -> MutexGuard<'_, HashMap<JobId, String>> {
+        self.owners.lock().await
+    }
+
+    pub async fn get(&self, job: JobId) -> Option<String> {
+        self.owners.lock().await.get(&job).cloned()
+    }
+
+    pub async fn insert(&self, job: JobId, user: String) {
+        let mut owners = self.owners.lock().await;
+
+        if let Some(pool) = &self.pool {
+            let result =
+                sqlx::query("INSERT OR REPLACE INTO print_job_owners (job_id, user) VALUES (?, ?)")
+                    .bind(job.0)
+                    .bind(&user)
+                    .execute(pool)
+                    .await;
+
+            if let Err(err) = result {
+                log::error!("Failed to store owner of print job #{job}: {err:#}");
+            }
+        }
+
+        owners.insert(job, user);
+    }
+
+    pub async fn remove(&self, job: JobId) {
+        self.retain(|&id| id != job).await;
+    }
+
+    /// Forget owners of jobs, which don't match the predicate.
+    pub async fn retain(&self, mut keep: impl FnMut(&JobId) -> bool) {
+        let mut owners = self.owners.lock().await;
+
+        let removed: Vec<JobId> = owners.keys().copied().filter(|job| !keep(job)).collect();
+        for job in removed {
+            owners.remove(&job);
+
+            if let Some(pool) = &self.pool {
+                let result = sqlx::query("DELETE FROM print_job_owners WHERE job_id = ?")
+                    .bind(job.0)
+                    .execute(pool)
+                    .await;
+
+                if let Err(err) = result {
+                    log::error!("Failed to remove owner of print job #{job}: {err:#}");
+                }
+            }
+        }
+    }
+}
+
+/// Teloxide doesn't list stored dialogues, so chats are read from its table directly.
+async fn stored_chats(path: &str) -> anyhow::Result<Vec<ChatId>> {
+    let pool = SqlitePool::connect(&format!("sqlite:{path}")).await?;
+
+    let chats = sqlx::query_scalar::<_, i64>("SELECT chat_id FROM teloxide_dialogues")
+        .fetch_all(&pool)
+        .await?;
+
+    pool.close().await;
+
+    Ok(chats.into_iter().map(ChatId).collect())
+}
@@ -7,9 +7,26 @@ pub async fn send_msg(bot: &Bot, chat_id: ChatId, text: &str) -> anyhow::Result<
     Ok(())
 }
 
+/// Text of the message with photo is its caption.
 #[inline(always)]
 pub async fn edit_msg(bot: &Bot, msg: &Message, text: &str) -> anyhow::Result<()> {
-    bot.edit_message_text(msg.chat.id, msg.id, text).await?;
+    if msg.photo().is_some() {
+        bot.edit_message_caption(msg.chat.id, msg.id)
+            .caption(text)
+            .await?;
+    } else {
+        bot.edit_message_text(msg.chat.id, msg.id, text).await?;
+    }
+
+    Ok(())
+}
+
+/// Answer the button, which doesn't belong to the current state of the dialogue.
+///
+/// Such buttons stay on old messages, e.g. after the bot is restarted.
+pub async fn ignore_stale_button(bot: &Bot, query_id: &str) -> anyhow::Result<()> {
+    log::debug!("Ignore button from a stale message");
+    bot.answer_callback_query(query_id).await?;
     Ok(())
 }
 
@@ -35,10 +52,18 @@ pub async fn edit_interative(
     text: &str,
     buttons: &[(&str, (usize, &str))],
 ) -> anyhow::Result<Message> {
-    let message = bot
-        .edit_message_text(message.chat.id, message.id, text)
-        .reply_markup(buttons_to_inline_keyboard(buttons))
-        .await?;
+    let keyboard = buttons_to_inline_keyboard(buttons);
+
+    let message = if message.photo().is_some() {
+        bot.edit_message_caption(message.chat.id, message.id)
+            .caption(text)
+            .reply_markup(keyboard)
+            .await?
+    } else {
+        bot.edit_message_text(message.chat.id, message.id, text)
+            .reply_markup(keyboard)
+            .await?
+    };
 
     Ok(message)
 }
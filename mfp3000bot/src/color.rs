@@ -0,0 +1,62 @@
+use libjpeg::{RawImage, RawImageFormat};
+
+/// Difference between the largest and the smallest channel of a colored pixel.
+/// Scanner noise and color fringes on the black text are below it.
+const MIN_CHROMA: u8 = 48;
+
+/// Minimal share of colored pixels on a color page.
+const MIN_COLOR_SHARE: f32 = 0.001;
+
+/// Check, if the page has meaningful color.
+pub fn has_color(image: &RawImage) -> bool {
+    let RawImageFormat::Rgb = image.format else {
+        return false;
+    };
+
+    let total = image.pixels.len() / 3;
+    if total == 0 {
+        return false;
+    }
+
+    let colored = image
+        .pixels
+        .chunks_exact(3)
+        .filter(|rgb| {
+            let max = rgb[0].max(rgb[1]).max(rgb[2]);
+            let min = rgb[0].min(rgb[1]).min(rgb[2]);
+            max - min >= MIN_CHROMA
+        })
+        .count();
+
+    let share = colored as f32 / total as f32;
+    log::debug!("Share of colored pixels {share:.5}");
+
+    share >= MIN_COLOR_SHARE
+}
+
+/// Convert RGB image to gray image.
+pub fn to_gray(image: &mut RawImage) {
+    if let RawImageFormat::Gray = image.format {
+        return;
+    }
+
+    image.pixels = image.pixels.chunks_exact(3).map(luminance).collect();
+    image.format = RawImageFormat::Gray;
+}
+
+/// Number of bytes per pixel.
+pub fn components(format: RawImageFormat) -> usize {
+    match format {
+        RawImageFormat::Rgb => 3,
+        RawImageFormat::Gray => 1,
+    }
+}
+
+/// Brightness of RGB or gray pixel.
+pub fn luminance(pixel: &[u8]) -> u8 {
+    match *pixel {
+        [r, g, b] => ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8,
+        [gray] => gray,
+        _ => unreachable!("pixel should be RGB or gray"),
+    }
+}
@@ -1,7 +1,15 @@
-use anyhow::Context;
+use crate::bot_data::{Enhancement, OutputFormat, ScanPresetAction};
+use anyhow::{bail, Context};
 use bstr::BString;
-use serde::Deserialize;
-use std::{collections::HashMap, fs, path::Path};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// Limit of Telegram for the data of the inline keyboard button.
+const MAX_CALLBACK_DATA_LEN: usize = 64;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
@@ -14,12 +22,34 @@ pub struct Config {
 
     #[serde(default = "Default::default")]
     pub scanner: HashMap<String, HashMap<BString, BString>>,
+
+    #[serde(default = "Default::default")]
+    pub scan_presets: BTreeMap<String, ScanPreset>,
+
+    #[serde(default = "Default::default")]
+    pub destinations: BTreeMap<String, Destination>,
+
+    pub smtp: Option<Smtp>,
+
+    pub supplies: Option<Supplies>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Telegram {
     pub token: String,
     pub allowed_users: Vec<String>,
+
+    /// Users, who can manage print jobs of other users.
+    #[serde(default = "Default::default")]
+    pub admins: Vec<String>,
+
+    /// Chats, which receive alerts about the printer, e.g. ids of admins.
+    #[serde(default = "Default::default")]
+    pub admin_chats: Vec<i64>,
+
+    /// SQLite database, which keeps dialogues across restarts of the bot.
+    /// Dialogues are kept in memory, if it's not specified.
+    pub dialogues: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -37,6 +67,18 @@ pub struct Print {
     pub quality: Option<libcups::options::PrintQuality>,
 }
 
+/// Monitoring of toner and ink levels of the printer.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Supplies {
+    /// How often levels are read from the printer.
+    #[serde(default = "default_supplies_interval")]
+    pub interval_minutes: u64,
+
+    /// Level in percents, at which admins are alerted.
+    /// The low level reported by the printer is used, if it's not specified.
+    pub threshold: Option<u8>,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Scan {
     pub preview_dpi: u16,
@@ -45,18 +87,277 @@ pub struct Scan {
 
     pub page_quality: u8,
 
+    #[serde(default = "Default::default")]
+    pub profile: ScanProfile,
+
+    #[serde(default = "Default::default")]
+    pub color_mode: ColorMode,
+
+    /// Detect orientation of document pages with tesseract and rotate them upright.
+    /// It runs tesseract for every page, which takes a few seconds on slow devices.
+    #[serde(default = "default_auto_rotate")]
+    pub auto_rotate: bool,
+
+    /// Straighten skewed document pages and crop them to the paper.
+    #[serde(default = "default_deskew")]
+    pub deskew: bool,
+
+    /// Maximum share of dark pixels on a blank page, e.g. `0.002`.
+    /// Blank pages of multi-page documents are dropped, if it's specified.
+    pub blank_page_threshold: Option<f32>,
+
+    #[serde(default = "Default::default")]
+    pub pdf_conformance: PdfConformance,
+
+    #[serde(default = "Default::default")]
+    pub pdf_keywords: Vec<String>,
+
+    #[serde(default = "Default::default")]
+    pub pdf_outline: bool,
+
+    #[serde(default = "Default::default")]
+    pub ocr_languages: Vec<String>,
+
+    pub ocr_font: Option<PathBuf>,
+
+    /// Directory for pages of multi-page documents.
+    /// By default it's `spool` in the state directory of the bot.
+    #[serde(default = "default_spool_dir")]
+    pub spool_dir: PathBuf,
+
     #[serde(default = "Default::default")]
     pub common_options: HashMap<BString, BString>,
 }
 
+/// Named set of scan settings, e.g. `[scan_presets.receipt]`.
+/// Settings, which are not specified, are taken from `[scan]`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScanPreset {
+    /// Text of the preset button. Name of the preset is used by default.
+    pub title: Option<String>,
+
+    pub page_dpi: Option<u16>,
+
+    pub page_quality: Option<u8>,
+
+    pub profile: Option<ScanProfile>,
+
+    pub color_mode: Option<ColorMode>,
+
+    pub auto_rotate: Option<bool>,
+
+    pub deskew: Option<bool>,
+
+    pub blank_page_threshold: Option<f32>,
+
+    pub pdf_conformance: Option<PdfConformance>,
+
+    /// Format, which is chosen first when the scan is saved, e.g. `"pdf"` or `"png"`.
+    pub format: Option<OutputFormat>,
+
+    /// Enhancement, which is marked when the document is finished.
+    pub enhancement: Option<Enhancement>,
+
+    /// Scanner options, which override `[scanner."..."]`, e.g. scan area or mode.
+    #[serde(default = "Default::default")]
+    pub options: HashMap<BString, BString>,
+}
+
+/// Place, where scans are saved besides Telegram, e.g. `[destinations.nas]`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Destination {
+    /// Text of the destination button. Name of the destination is used by default.
+    pub title: Option<String>,
+
+    #[serde(flatten)]
+    pub kind: DestinationKind,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum DestinationKind {
+    /// Local directory or mounted SMB/NFS share.
+    Folder {
+        path: PathBuf,
+
+        /// Path of the scan inside `path` with `{user}`, `{date}`, `{time}`,
+        /// `{name}` and `{ext}` placeholders.
+        #[serde(default = "default_path_template")]
+        template: String,
+    },
+}
+
+/// SMTP server for sending scans by email.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Smtp {
+    pub host: String,
+
+    /// Default port of the `tls` mode is used, if it's not specified.
+    pub port: Option<u16>,
+
+    #[serde(default = "Default::default")]
+    pub tls: SmtpTls,
+
+    pub username: Option<String>,
+
+    pub password: Option<String>,
+
+    /// Sender of emails, e.g. `Scanner <scanner@example.com>`.
+    pub from: String,
+
+    /// Email addresses of Telegram users, e.g. `username = "user@example.com"`.
+    #[serde(default = "Default::default")]
+    pub recipients: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Connection is upgraded to TLS with STARTTLS, port 587 by default.
+    #[default]
+    StartTls,
+
+    /// TLS from the start of the connection, port 465 by default.
+    Tls,
+
+    /// Plain connection, e.g. to a local SMTP server for testing, port 25 by default.
+    None,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanProfile {
+    /// Pages are compressed with JPEG as is.
+    #[default]
+    Photo,
+
+    /// Pages are binarized and compressed with CCITT G4 in PDF.
+    Text,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    /// Pages are stored in the mode of the scanner.
+    #[default]
+    Keep,
+
+    /// Pages without meaningful color are converted to grayscale.
+    Auto,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PdfConformance {
+    /// Smallest documents without embedded color profiles.
+    #[default]
+    Compact,
+
+    /// PDF/A-2b for long-term archiving.
+    #[serde(rename = "pdf/a-2b")]
+    PdfA2b,
+}
+
+fn default_auto_rotate() -> bool {
+    true
+}
+
+fn default_deskew() -> bool {
+    true
+}
+
+fn default_supplies_interval() -> u64 {
+    60
+}
+
+fn default_path_template() -> String {
+    "{user}/{date}/{name}.{ext}".to_owned()
+}
+
+/// Spooled pages must survive restarts together with the dialogues, so they are kept
+/// in the state directory rather than in `/tmp`, which is often in memory.
+fn default_spool_dir() -> PathBuf {
+    // Set by systemd for services with `StateDirectory=`.
+    let state_dir = env::var_os("STATE_DIRECTORY")
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os("XDG_STATE_HOME").map(|dir| PathBuf::from(dir).join(env!("CARGO_PKG_NAME")))
+        })
+        .or_else(|| {
+            env::var_os("HOME").map(|dir| {
+                PathBuf::from(dir)
+                    .join(".local/state")
+                    .join(env!("CARGO_PKG_NAME"))
+            })
+        });
+
+    match state_dir {
+        Some(dir) => dir.join("spool"),
+        None => env::temp_dir().join(env!("CARGO_PKG_NAME")),
+    }
+}
+
 impl Config {
     pub fn read_from<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
         let path = path.as_ref();
         let dpath = path.display();
 
         let raw = fs::read_to_string(path).with_context(|| format!("reading file '{dpath}'"))?;
-        let config = toml::from_str(&raw).with_context(|| format!("parsing file '{dpath}'"))?;
+        let config: Self =
+            toml::from_str(&raw).with_context(|| format!("parsing file '{dpath}'"))?;
+
+        config
+            .check_scan_presets()
+            .with_context(|| format!("checking file '{dpath}'"))?;
 
         Ok(config)
     }
+
+    /// Names of the presets are sent back by the buttons, so they must be valid callback data.
+    fn check_scan_presets(&self) -> anyhow::Result<()> {
+        let no_preset: &str = ScanPresetAction::NoPreset.into();
+
+        for name in self.scan_presets.keys() {
+            if name.is_empty() || name.len() > MAX_CALLBACK_DATA_LEN {
+                bail!("name of scan preset '{name}' must be 1-{MAX_CALLBACK_DATA_LEN} bytes long");
+            }
+
+            if name == no_preset {
+                bail!("name of scan preset '{name}' is reserved");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Config with settings of the scan preset `name` applied.
+    pub fn with_preset(&self, name: &str) -> Self {
+        let mut config = self.clone();
+
+        let Some(preset) = self.scan_presets.get(name) else {
+            log::warn!("Scan preset '{name}' not found");
+            return config;
+        };
+
+        let scan = &mut config.scan;
+        scan.page_dpi = preset.page_dpi.unwrap_or(scan.page_dpi);
+        scan.page_quality = preset.page_quality.unwrap_or(scan.page_quality);
+        scan.profile = preset.profile.unwrap_or(scan.profile);
+        scan.color_mode = preset.color_mode.unwrap_or(scan.color_mode);
+        scan.auto_rotate = preset.auto_rotate.unwrap_or(scan.auto_rotate);
+        scan.deskew = preset.deskew.unwrap_or(scan.deskew);
+        scan.blank_page_threshold = preset.blank_page_threshold.or(scan.blank_page_threshold);
+        scan.pdf_conformance = preset.pdf_conformance.unwrap_or(scan.pdf_conformance);
+
+        // Options of the preset are applied only to the scanner from `[devices]`.
+        if let Some(device_name) = &self.devices.scanner {
+            config
+                .scanner
+                .entry(device_name.clone())
+                .or_default()
+                .extend(preset.options.clone());
+        }
+
+        config
+    }
 }
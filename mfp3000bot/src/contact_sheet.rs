@@ -0,0 +1,163 @@
+use crate::color::components;
+use libjpeg::{RawImage, RawImageFormat};
+
+/// Largest size of the cell for the page thumbnail. Cells have proportions of A4 page.
+const CELL_WIDTH: usize = 240;
+const CELL_HEIGHT: usize = 340;
+
+const GAP: usize = 16;
+const MAX_COLUMNS: usize = 5;
+
+/// Telegram rejects photos, which width and height sum up to more pixels.
+const MAX_PHOTO_SIZE: usize = 10000;
+const BACKGROUND: u8 = 0x70;
+
+/// Digits of the page numbers as 3x5 bitmaps, rows from top to bottom.
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// Size of the digit pixel on the sheet.
+const DIGIT_SCALE: usize = 4;
+
+/// Grid of numbered page thumbnails.
+pub struct ContactSheet {
+    canvas: RawImage,
+    columns: usize,
+    cell_width: usize,
+    cell_height: usize,
+}
+
+impl ContactSheet {
+    pub fn new(pages_count: usize) -> Self {
+        // Large documents are laid out in a square grid, so the sheet stays readable.
+        let square_columns = (pages_count as f64).sqrt().ceil() as usize;
+        let columns = pages_count.clamp(1, MAX_COLUMNS.max(square_columns));
+        let rows = pages_count.div_ceil(columns).max(1);
+
+        // Cells are shrunk, if the sheet doesn't fit into the photo.
+        let gaps = (columns + rows + 2) * GAP;
+        let cells = columns * CELL_WIDTH + rows * CELL_HEIGHT;
+        let scale = (MAX_PHOTO_SIZE.saturating_sub(gaps) as f64 / cells as f64).min(1.0);
+
+        let cell_width = ((CELL_WIDTH as f64 * scale) as usize).max(1);
+        let cell_height = ((CELL_HEIGHT as f64 * scale) as usize).max(1);
+
+        let width = columns * (cell_width + GAP) + GAP;
+        let height = rows * (cell_height + GAP) + GAP;
+
+        Self {
+            canvas: RawImage {
+                pixels: vec![BACKGROUND; width * height * 3],
+                width,
+                height,
+                format: RawImageFormat::Rgb,
+            },
+            columns,
+            cell_width,
+            cell_height,
+        }
+    }
+
+    /// Draw the thumbnail of the page with its number to the cell at `index`.
+    pub fn draw_page(&mut self, index: usize, page: &RawImage) {
+        let cell_left = GAP + index % self.columns * (self.cell_width + GAP);
+        let cell_top = GAP + index / self.columns * (self.cell_height + GAP);
+
+        let scale = page
+            .width
+            .div_ceil(self.cell_width)
+            .max(page.height.div_ceil(self.cell_height))
+            .max(1);
+
+        let width = page.width / scale;
+        let height = page.height / scale;
+
+        let left = cell_left + (self.cell_width - width) / 2;
+        let top = cell_top + (self.cell_height - height) / 2;
+
+        let components = components(page.format);
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0u32; 3];
+                for dy in 0..scale {
+                    let row = (y * scale + dy) * page.width;
+                    for dx in 0..scale {
+                        let offset = (row + x * scale + dx) * components;
+                        let pixel = &page.pixels[offset..offset + components];
+                        for (c, sum) in sum.iter_mut().enumerate() {
+                            *sum += pixel[c.min(components - 1)] as u32;
+                        }
+                    }
+                }
+
+                let rgb = sum.map(|sum| (sum / (scale * scale) as u32) as u8);
+                self.put_pixel(left + x, top + y, rgb);
+            }
+        }
+
+        self.draw_number(index + 1, left, top);
+    }
+
+    pub fn finish(self) -> RawImage {
+        self.canvas
+    }
+
+    /// Draw black number on the white plate at the top left corner of the thumbnail.
+    fn draw_number(&mut self, number: usize, left: usize, top: usize) {
+        let digits: Vec<usize> = number
+            .to_string()
+            .bytes()
+            .map(|digit| (digit - b'0') as usize)
+            .collect();
+
+        let digit_width = 4 * DIGIT_SCALE;
+        let plate_width = digits.len() * digit_width + DIGIT_SCALE;
+        let plate_height = 7 * DIGIT_SCALE;
+
+        for y in 0..plate_height {
+            for x in 0..plate_width {
+                self.put_pixel(left + x, top + y, [u8::MAX; 3]);
+            }
+        }
+
+        for (i, &digit) in digits.iter().enumerate() {
+            let digit_left = left + DIGIT_SCALE + i * digit_width;
+            let digit_top = top + DIGIT_SCALE;
+
+            for (row, bits) in DIGITS[digit].iter().enumerate() {
+                for column in 0..3 {
+                    if bits >> (2 - column) & 1 == 0 {
+                        continue;
+                    }
+
+                    for dy in 0..DIGIT_SCALE {
+                        for dx in 0..DIGIT_SCALE {
+                            let x = digit_left + column * DIGIT_SCALE + dx;
+                            let y = digit_top + row * DIGIT_SCALE + dy;
+                            self.put_pixel(x, y, [0; 3]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn put_pixel(&mut self, x: usize, y: usize, rgb: [u8; 3]) {
+        if x >= self.canvas.width || y >= self.canvas.height {
+            return;
+        }
+
+        let offset = (y * self.canvas.width + x) * 3;
+        self.canvas.pixels[offset..offset + 3].copy_from_slice(&rgb);
+    }
+}
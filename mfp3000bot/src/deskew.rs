@@ -0,0 +1,301 @@
+use crate::color::{components, luminance};
+use libjpeg::RawImage;
+
+/// Maximum skew of the page on the glass in degrees.
+const MAX_SKEW: f32 = 5.0;
+
+/// Pages are analyzed in a downscaled copy, which is at least this wide.
+const ANALYSIS_WIDTH: usize = 800;
+
+/// Minimal share of bright pixels in a row or column of the page.
+/// Rows and columns of the lid border are mostly gray.
+const PAGE_BRIGHT_SHARE: f32 = 0.5;
+
+/// Straighten the page and crop it to the page edges.
+pub fn deskew_and_crop(image: &mut RawImage) {
+    let gray = GrayImage::downscaled(image);
+
+    let page = detect_page(&gray).unwrap_or(Rect::full(&gray));
+    let skew = detect_skew(&gray, page);
+
+    log::debug!("Detected page skew {skew:.2}°");
+
+    if skew != 0.0 {
+        rotate(image, skew.to_radians());
+    }
+
+    let gray = GrayImage::downscaled(image);
+    match detect_page(&gray) {
+        Some(page) => crop(image, page.scaled(gray.scale)),
+        None => log::debug!("Page edges are not found, skip crop"),
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Rect {
+    left: usize,
+    top: usize,
+    right: usize,
+    bottom: usize,
+}
+
+impl Rect {
+    fn full(image: &GrayImage) -> Self {
+        Self {
+            left: 0,
+            top: 0,
+            right: image.width,
+            bottom: image.height,
+        }
+    }
+
+    fn width(&self) -> usize {
+        self.right - self.left
+    }
+
+    fn height(&self) -> usize {
+        self.bottom - self.top
+    }
+
+    fn scaled(&self, scale: usize) -> Self {
+        Self {
+            left: self.left * scale,
+            top: self.top * scale,
+            right: self.right * scale,
+            bottom: self.bottom * scale,
+        }
+    }
+}
+
+/// Downscaled gray copy of the image for the analysis.
+struct GrayImage {
+    pixels: Vec<u8>,
+    width: usize,
+    height: usize,
+    scale: usize,
+}
+
+impl GrayImage {
+    fn downscaled(image: &RawImage) -> Self {
+        let components = components(image.format);
+        let scale = (image.width / ANALYSIS_WIDTH).max(1);
+
+        let width = image.width / scale;
+        let height = image.height / scale;
+
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = 0u32;
+                for dy in 0..scale {
+                    let row = (y * scale + dy) * image.width;
+                    for dx in 0..scale {
+                        let offset = (row + x * scale + dx) * components;
+                        sum += luminance(&image.pixels[offset..offset + components]) as u32;
+                    }
+                }
+
+                pixels.push((sum / (scale * scale) as u32) as u8);
+            }
+        }
+
+        Self {
+            pixels,
+            width,
+            height,
+            scale,
+        }
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+}
+
+/// Find page edges by the share of the paper colored pixels in rows and columns.
+///
+/// Returns `None`, if the found page is too small to trust it.
+fn detect_page(image: &GrayImage) -> Option<Rect> {
+    let paper = paper_level(&image.pixels);
+    let bright_threshold = (paper as f32 * 0.85) as u8;
+
+    let is_page_line = |bright: usize, total: usize| {
+        total > 0 && bright as f32 / total as f32 >= PAGE_BRIGHT_SHARE
+    };
+
+    let row_is_page = |y: usize| {
+        let bright = (0..image.width)
+            .filter(|&x| image.pixel(x, y) >= bright_threshold)
+            .count();
+        is_page_line(bright, image.width)
+    };
+
+    let top = (0..image.height).find(|&y| row_is_page(y))?;
+    let bottom = (top..image.height).rev().find(|&y| row_is_page(y))? + 1;
+
+    let column_is_page = |x: usize| {
+        let bright = (top..bottom)
+            .filter(|&y| image.pixel(x, y) >= bright_threshold)
+            .count();
+        is_page_line(bright, bottom - top)
+    };
+
+    let left = (0..image.width).find(|&x| column_is_page(x))?;
+    let right = (left..image.width).rev().find(|&x| column_is_page(x))? + 1;
+
+    let page = Rect {
+        left,
+        top,
+        right,
+        bottom,
+    };
+
+    // Page is placed on the glass by hand, so it can't be much smaller than the scan area.
+    if page.width() * page.height() * 2 < image.width * image.height {
+        return None;
+    }
+
+    Some(page)
+}
+
+/// Brightness of the paper, which is the brightest large part of the image.
+fn paper_level(pixels: &[u8]) -> u8 {
+    let mut histogram = [0usize; 256];
+    for &pixel in pixels {
+        histogram[pixel as usize] += 1;
+    }
+
+    let mut count = 0;
+    for level in (0..256).rev() {
+        count += histogram[level];
+        if count * 10 >= pixels.len() {
+            return level as u8;
+        }
+    }
+
+    0
+}
+
+/// Find skew angle of the text lines in degrees with the projection profile method:
+/// the sum of squared counts of dark pixels in lines is maximal when lines follow the text.
+fn detect_skew(image: &GrayImage, page: Rect) -> f32 {
+    let paper = paper_level(&image.pixels);
+    let dark_threshold = paper / 2;
+
+    // Skip a few pixels near the edges to not take the page border for the text.
+    let margin = 4;
+    let mut dark_pixels = Vec::new();
+    for y in page.top + margin..page.bottom.saturating_sub(margin) {
+        for x in page.left + margin..page.right.saturating_sub(margin) {
+            if image.pixel(x, y) < dark_threshold {
+                dark_pixels.push((x as f32, y as f32));
+            }
+        }
+    }
+
+    if dark_pixels.len() < 100 {
+        return 0.0;
+    }
+
+    let score = |angle: f32| {
+        let tan = angle.to_radians().tan();
+        let offset = image.width as f32 * MAX_SKEW.to_radians().tan();
+
+        let mut lines = vec![0u32; image.height + 2 * offset.ceil() as usize + 1];
+        for &(x, y) in &dark_pixels {
+            let line = (y - x * tan + offset).round() as usize;
+            lines[line] += 1;
+        }
+
+        lines.iter().map(|&n| (n as u64).pow(2)).sum::<u64>()
+    };
+
+    let best_angle = |from: f32, to: f32, step: f32| {
+        let steps = ((to - from) / step).round() as i32;
+        (0..=steps)
+            .map(|i| from + i as f32 * step)
+            .max_by_key(|&angle| score(angle))
+            .unwrap_or(0.0)
+    };
+
+    let coarse = best_angle(-MAX_SKEW, MAX_SKEW, 0.5);
+    // Lines are counted only for angles up to `MAX_SKEW`.
+    let fine = best_angle(
+        (coarse - 0.5).max(-MAX_SKEW),
+        (coarse + 0.5).min(MAX_SKEW),
+        0.05,
+    );
+
+    if fine.abs() < 0.05 {
+        0.0
+    } else {
+        fine
+    }
+}
+
+/// Rotate image around its center with bilinear interpolation.
+/// Uncovered corners are filled with the nearest edge pixels, so they look like the lid border.
+fn rotate(image: &mut RawImage, angle: f32) {
+    let components = components(image.format);
+    let (width, height) = (image.width, image.height);
+
+    let (sin, cos) = angle.sin_cos();
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+
+    let (max_x, max_y) = ((width - 1) as f32, (height - 1) as f32);
+
+    let mut pixels = vec![0u8; image.pixels.len()];
+
+    for y in 0..height {
+        let dy = y as f32 - cy;
+        for x in 0..width {
+            let dx = x as f32 - cx;
+
+            let sx = (cx + dx * cos - dy * sin).clamp(0.0, max_x);
+            let sy = (cy + dx * sin + dy * cos).clamp(0.0, max_y);
+
+            let (x0, y0) = (sx as usize, sy as usize);
+            let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+
+            let (fx, fy) = (sx - x0 as f32, sy - y0 as f32);
+
+            let offset = |x: usize, y: usize| (y * width + x) * components;
+            let (p00, p10) = (offset(x0, y0), offset(x1, y0));
+            let (p01, p11) = (offset(x0, y1), offset(x1, y1));
+
+            let target = offset(x, y);
+            for c in 0..components {
+                let top =
+                    image.pixels[p00 + c] as f32 * (1.0 - fx) + image.pixels[p10 + c] as f32 * fx;
+                let bottom =
+                    image.pixels[p01 + c] as f32 * (1.0 - fx) + image.pixels[p11 + c] as f32 * fx;
+
+                pixels[target + c] = (top * (1.0 - fy) + bottom * fy).round() as u8;
+            }
+        }
+    }
+
+    image.pixels = pixels;
+}
+
+fn crop(image: &mut RawImage, rect: Rect) {
+    let components = components(image.format);
+
+    let rect = Rect {
+        right: rect.right.min(image.width),
+        bottom: rect.bottom.min(image.height),
+        ..rect
+    };
+
+    let mut pixels = Vec::with_capacity(rect.width() * rect.height() * components);
+    for y in rect.top..rect.bottom {
+        let row = y * image.width;
+        pixels.extend_from_slice(
+            &image.pixels[(row + rect.left) * components..(row + rect.right) * components],
+        );
+    }
+
+    image.pixels = pixels;
+    image.width = rect.width();
+    image.height = rect.height();
+}
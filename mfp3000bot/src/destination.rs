@@ -0,0 +1,245 @@
+use crate::config::{self, SmtpTls};
+use anyhow::Context;
+use lettre::{
+    message::{header::ContentType, Attachment, Mailbox, MultiPart},
+    transport::smtp::authentication::Credentials,
+    Message, SmtpTransport, Transport,
+};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+use time::OffsetDateTime;
+
+/// Place, where scans are saved besides Telegram.
+pub trait Destination: Send + Sync {
+    /// Save the scan and return its location for the user.
+    fn save(&self, scan: &ScanInfo, content: &Content) -> anyhow::Result<String>;
+}
+
+/// Description of the scan, which is used to build its location.
+pub struct ScanInfo {
+    pub name: String,
+    pub user: String,
+    pub extension: &'static str,
+    pub time: OffsetDateTime,
+}
+
+/// Bytes of the scan, which are either in memory or in the spool.
+pub enum Content {
+    Memory(Vec<u8>),
+    File(PathBuf),
+}
+
+impl ScanInfo {
+    pub fn new(name: &str, user: &str, extension: &'static str) -> Self {
+        Self {
+            name: name.to_owned(),
+            user: user.to_owned(),
+            extension,
+            // Offset may be unknown in multithreaded process, so UTC is used then.
+            time: OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc()),
+        }
+    }
+}
+
+impl Content {
+    fn reader(&self) -> io::Result<Box<dyn Read + '_>> {
+        match self {
+            Content::Memory(bytes) => Ok(Box::new(bytes.as_slice())),
+            Content::File(path) => Ok(Box::new(fs::File::open(path)?)),
+        }
+    }
+}
+
+pub fn from_config(config: &config::Destination) -> Box<dyn Destination> {
+    match &config.kind {
+        config::DestinationKind::Folder { path, template } => Box::new(Folder {
+            root: path.clone(),
+            template: template.clone(),
+        }),
+    }
+}
+
+/// Local directory or mounted SMB/NFS share.
+pub struct Folder {
+    root: PathBuf,
+    template: String,
+}
+
+impl Destination for Folder {
+    fn save(&self, scan: &ScanInfo, content: &Content) -> anyhow::Result<String> {
+        let path = unique_path(&self.root.join(render_template(&self.template, scan)));
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("creating directory '{}'", dir.display()))?;
+        }
+
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .with_context(|| format!("creating file '{}'", path.display()))?;
+
+        io::copy(&mut content.reader()?, &mut file)
+            .with_context(|| format!("writing file '{}'", path.display()))?;
+
+        file.sync_all()?;
+
+        log::debug!("Scan saved to '{}'", path.display());
+
+        Ok(path.display().to_string())
+    }
+}
+
+/// Mailbox of the user, which is specified in `[smtp.recipients]`.
+pub struct Email {
+    transport: SmtpTransport,
+    from: Mailbox,
+    recipients: HashMap<String, String>,
+}
+
+impl Email {
+    pub fn new(config: &config::Smtp) -> anyhow::Result<Self> {
+        let mut transport = match config.tls {
+            SmtpTls::StartTls => SmtpTransport::starttls_relay(&config.host)?,
+            SmtpTls::Tls => SmtpTransport::relay(&config.host)?,
+            SmtpTls::None => SmtpTransport::builder_dangerous(&config.host),
+        };
+
+        if let Some(port) = config.port {
+            transport = transport.port(port);
+        }
+
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            transport = transport.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        let from = config
+            .from
+            .parse()
+            .with_context(|| format!("parsing sender address '{}'", config.from))?;
+
+        Ok(Self {
+            transport: transport.build(),
+            from,
+            recipients: config.recipients.clone(),
+        })
+    }
+}
+
+impl Destination for Email {
+    fn save(&self, scan: &ScanInfo, content: &Content) -> anyhow::Result<String> {
+        let address = self
+            .recipients
+            .get(&scan.user)
+            .with_context(|| format!("email address of user '{}' is not specified", scan.user))?;
+
+        let to = address
+            .parse()
+            .with_context(|| format!("parsing recipient address '{address}'"))?;
+
+        let mut bytes = Vec::new();
+        content.reader()?.read_to_end(&mut bytes)?;
+
+        let file_name = format!("{}.{}", scan.name, scan.extension);
+        let attachment = Attachment::new(file_name)
+            .body(bytes, ContentType::parse(content_type(scan.extension))?);
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(&scan.name)
+            .multipart(MultiPart::mixed().singlepart(attachment))
+            .context("building email")?;
+
+        self.transport
+            .send(&message)
+            .with_context(|| format!("sending email to '{address}'"))?;
+
+        log::debug!("Scan sent to '{address}'");
+
+        Ok(address.clone())
+    }
+}
+
+/// MIME type of the scan by its extension.
+fn content_type(extension: &str) -> &'static str {
+    match extension {
+        "pdf" => "application/pdf",
+        "jpg" => "image/jpeg",
+        "png" => "image/png",
+        "tiff" => "image/tiff",
+        "zip" => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Substitute `{user}`, `{date}`, `{time}`, `{name}` and `{ext}` in the template.
+fn render_template(template: &str, scan: &ScanInfo) -> PathBuf {
+    let time = scan.time;
+    let date = format!(
+        "{:04}-{:02}-{:02}",
+        time.year(),
+        u8::from(time.month()),
+        time.day()
+    );
+    let clock = format!(
+        "{:02}-{:02}-{:02}",
+        time.hour(),
+        time.minute(),
+        time.second()
+    );
+
+    let path = template
+        .replace("{user}", &path_component(&scan.user))
+        .replace("{date}", &date)
+        .replace("{time}", &clock)
+        .replace("{name}", &path_component(&scan.name))
+        .replace("{ext}", scan.extension);
+
+    PathBuf::from(path)
+}
+
+/// Replace characters, which are not allowed in file names or escape the directory.
+fn path_component(value: &str) -> String {
+    let value: String = value
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    let value = value.trim().trim_start_matches('.');
+    if value.is_empty() {
+        "_".to_owned()
+    } else {
+        value.to_owned()
+    }
+}
+
+/// Path, which doesn't overwrite existing file, e.g. `Документ (2).pdf`.
+fn unique_path(path: &Path) -> PathBuf {
+    if !path.exists() {
+        return path.to_owned();
+    }
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().map(|ext| ext.to_string_lossy());
+
+    (2..)
+        .map(|i| {
+            let name = match &extension {
+                Some(ext) => format!("{stem} ({i}).{ext}"),
+                None => format!("{stem} ({i})"),
+            };
+            path.with_file_name(name)
+        })
+        .find(|path| !path.exists())
+        .expect("free file name should exist")
+}
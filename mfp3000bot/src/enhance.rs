@@ -0,0 +1,155 @@
+use crate::color::{components, luminance};
+use libjpeg::RawImage;
+
+/// Size of the block, where the paper brightness is estimated.
+const BLOCK_SIZE: usize = 64;
+
+/// Normalized brightness, above which document pixels become white.
+const WHITE_POINT: f32 = 0.88;
+
+/// Clean up the document like the "document mode" of phone cameras:
+/// whiten the paper background, boost contrast of the text and sharpen it.
+pub fn document(image: &mut RawImage) {
+    normalize_background(image);
+    apply_curve(image, |v| (v / WHITE_POINT).min(1.0).powf(1.6));
+    sharpen(image, 0.6);
+}
+
+/// Stretch levels of the photo to the full range and slightly sharpen it.
+pub fn photo(image: &mut RawImage) {
+    let (black, white) = levels(image);
+    if white - black > 0.1 {
+        apply_curve(image, |v| ((v - black) / (white - black)).clamp(0.0, 1.0));
+    }
+
+    sharpen(image, 0.3);
+}
+
+/// Divide pixels by the local paper brightness, so uneven lighting and
+/// yellowish paper become white.
+fn normalize_background(image: &mut RawImage) {
+    let components = components(image.format);
+
+    let blocks_x = image.width.div_ceil(BLOCK_SIZE);
+    let blocks_y = image.height.div_ceil(BLOCK_SIZE);
+
+    let mut background = Vec::with_capacity(blocks_x * blocks_y);
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            let mut histogram = [0usize; 256];
+            for y in by * BLOCK_SIZE..((by + 1) * BLOCK_SIZE).min(image.height) {
+                let row = y * image.width;
+                for x in bx * BLOCK_SIZE..((bx + 1) * BLOCK_SIZE).min(image.width) {
+                    let offset = (row + x) * components;
+                    histogram[luminance(&image.pixels[offset..offset + components]) as usize] += 1;
+                }
+            }
+
+            background.push(percentile(&histogram, 0.9) as f32);
+        }
+    }
+
+    // Blocks inside pictures are dark, don't turn them into paper.
+    let mut sorted = background.clone();
+    sorted.sort_by(f32::total_cmp);
+    let paper = sorted[sorted.len() / 2];
+    for level in &mut background {
+        *level = level.max(paper * 0.6).max(1.0);
+    }
+
+    let block_level = |bx: usize, by: usize| background[by * blocks_x + bx];
+
+    for y in 0..image.height {
+        let fy = ((y as f32 + 0.5) / BLOCK_SIZE as f32 - 0.5).clamp(0.0, (blocks_y - 1) as f32);
+        let (by0, wy) = (fy as usize, fy.fract());
+        let by1 = (by0 + 1).min(blocks_y - 1);
+
+        for x in 0..image.width {
+            let fx = ((x as f32 + 0.5) / BLOCK_SIZE as f32 - 0.5).clamp(0.0, (blocks_x - 1) as f32);
+            let (bx0, wx) = (fx as usize, fx.fract());
+            let bx1 = (bx0 + 1).min(blocks_x - 1);
+
+            let top = block_level(bx0, by0) * (1.0 - wx) + block_level(bx1, by0) * wx;
+            let bottom = block_level(bx0, by1) * (1.0 - wx) + block_level(bx1, by1) * wx;
+            let scale = u8::MAX as f32 / (top * (1.0 - wy) + bottom * wy);
+
+            let offset = (y * image.width + x) * components;
+            for pixel in &mut image.pixels[offset..offset + components] {
+                *pixel = (*pixel as f32 * scale).min(u8::MAX as f32) as u8;
+            }
+        }
+    }
+}
+
+/// Darkest and brightest levels of the image without outliers in range `0.0..=1.0`.
+fn levels(image: &RawImage) -> (f32, f32) {
+    let mut histogram = [0usize; 256];
+    for pixel in image.pixels.chunks_exact(components(image.format)) {
+        histogram[luminance(pixel) as usize] += 1;
+    }
+
+    let black = percentile(&histogram, 0.005);
+    let white = percentile(&histogram, 0.995);
+
+    (black as f32 / u8::MAX as f32, white as f32 / u8::MAX as f32)
+}
+
+/// Level, which is brighter than `share` of pixels.
+fn percentile(histogram: &[usize; 256], share: f32) -> u8 {
+    let total: usize = histogram.iter().sum();
+    let target = (total as f32 * share) as usize;
+
+    let mut count = 0;
+    for (level, &pixels) in histogram.iter().enumerate() {
+        count += pixels;
+        if count > target {
+            return level as u8;
+        }
+    }
+
+    u8::MAX
+}
+
+/// Map every channel through the curve, which takes and returns brightness in range `0.0..=1.0`.
+fn apply_curve(image: &mut RawImage, curve: impl Fn(f32) -> f32) {
+    let table: Vec<u8> = (0..=u8::MAX)
+        .map(|level| {
+            let value = curve(level as f32 / u8::MAX as f32);
+            (value * u8::MAX as f32).round() as u8
+        })
+        .collect();
+
+    for pixel in &mut image.pixels {
+        *pixel = table[*pixel as usize];
+    }
+}
+
+/// Unsharp mask with 3x3 box blur.
+fn sharpen(image: &mut RawImage, amount: f32) {
+    let components = components(image.format);
+    let (width, height) = (image.width, image.height);
+    let source = &image.pixels;
+
+    let mut pixels = Vec::with_capacity(source.len());
+    for y in 0..height {
+        let rows = [y.saturating_sub(1), y, (y + 1).min(height - 1)];
+        for x in 0..width {
+            let columns = [x.saturating_sub(1), x, (x + 1).min(width - 1)];
+            for c in 0..components {
+                let mut sum = 0u32;
+                for row in rows {
+                    for column in columns {
+                        sum += source[(row * width + column) * components + c] as u32;
+                    }
+                }
+
+                let pixel = source[(y * width + x) * components + c] as f32;
+                let blur = sum as f32 / 9.0;
+
+                pixels.push((pixel + (pixel - blur) * amount).clamp(0.0, u8::MAX as f32) as u8);
+            }
+        }
+    }
+
+    image.pixels = pixels;
+}
@@ -0,0 +1,176 @@
+use crate::{
+    orientation::{self, Rotation},
+    pdf_builder::PageImage,
+    png,
+    scan::{Jpeg, JpegFormat},
+};
+use anyhow::bail;
+
+/// Images with more pixels are not printed, so they can't exhaust the memory.
+///
+/// Decoded RGB image takes 3 bytes per pixel, and rotation needs a second copy of it.
+const MAX_PIXELS: usize = 25_000_000;
+
+/// JPEG markers, which are not followed by a segment.
+const JPEG_RST_MARKERS: std::ops::RangeInclusive<u8> = 0xd0..=0xd7;
+const JPEG_TEM: u8 = 0x01;
+
+const JPEG_SOI: u8 = 0xd8;
+const JPEG_SOS: u8 = 0xda;
+const JPEG_APP0: u8 = 0xe0;
+
+/// Start of frame markers. Other markers in the range are DHT, JPG and DAC.
+const JPEG_SOF_MARKERS: std::ops::RangeInclusive<u8> = 0xc0..=0xcf;
+const JPEG_NOT_SOF_MARKERS: [u8; 3] = [0xc4, 0xc8, 0xcc];
+
+/// Image sent by the user for printing.
+pub struct Image {
+    pub page: PageImage,
+    pub width: usize,
+    pub height: usize,
+
+    /// Resolution from the image metadata, if it's set.
+    pub dpi: Option<f32>,
+}
+
+impl Image {
+    /// Read size and resolution of JPEG image. Image itself is embedded to PDF as is.
+    pub fn from_jpeg(bytes: Vec<u8>) -> anyhow::Result<Self> {
+        let Some(header) = read_jpeg_header(&bytes) else {
+            bail!("image is not a valid JPEG");
+        };
+
+        let format = match header.components {
+            1 => JpegFormat::Gray,
+            3 => JpegFormat::Rgb,
+            components => bail!("JPEG with {components} color components is not supported"),
+        };
+
+        if header.precision != 8 {
+            bail!(
+                "JPEG with {}-bit samples is not supported",
+                header.precision
+            );
+        }
+
+        let (width, height) = (header.width, header.height);
+        if width == 0 || height == 0 || width * height > MAX_PIXELS {
+            bail!("JPEG image {width}x{height} is too large or empty");
+        }
+
+        let jpeg = Jpeg {
+            bytes,
+            format,
+            width,
+            height,
+        };
+
+        Ok(Self {
+            page: PageImage::Jpeg(jpeg),
+            width,
+            height,
+            dpi: header.dpi,
+        })
+    }
+
+    pub fn from_png(bytes: &[u8]) -> anyhow::Result<Self> {
+        let (image, dpi) = png::decode(bytes, MAX_PIXELS)?;
+
+        Ok(Self {
+            width: image.width,
+            height: image.height,
+            page: PageImage::Raw(image),
+            dpi,
+        })
+    }
+
+    /// Rotate the image by 90 degrees clockwise.
+    ///
+    /// JPEG is decoded and embedded as raw pixels, so it's not compressed with loss again,
+    /// but the PDF gets larger.
+    pub fn rotate(self) -> anyhow::Result<Self> {
+        let image = match self.page {
+            PageImage::Jpeg(jpeg) => libjpeg::decompress_jpeg(&jpeg.bytes)?,
+            PageImage::Raw(image) => image,
+            PageImage::Bilevel(_) => unreachable!("bilevel images are not printed"),
+        };
+
+        let image = orientation::rotate(image, Rotation::Clockwise90);
+
+        Ok(Self {
+            width: image.width,
+            height: image.height,
+            page: PageImage::Raw(image),
+            dpi: self.dpi,
+        })
+    }
+}
+
+struct JpegHeader {
+    width: usize,
+    height: usize,
+    components: u8,
+    precision: u8,
+    dpi: Option<f32>,
+}
+
+/// Read image parameters from the frame header and resolution from JFIF segment.
+fn read_jpeg_header(jpeg: &[u8]) -> Option<JpegHeader> {
+    let mut rest = jpeg.strip_prefix(&[0xff, JPEG_SOI])?;
+    let mut dpi = None;
+
+    loop {
+        // Markers can be padded with any number of 0xff.
+        let marker_start = rest.iter().position(|&byte| byte != 0xff)?;
+        if marker_start == 0 {
+            return None;
+        }
+
+        let marker = rest[marker_start];
+        rest = &rest[marker_start + 1..];
+
+        if marker == JPEG_TEM || JPEG_RST_MARKERS.contains(&marker) {
+            continue;
+        }
+
+        // Segment length includes the length itself.
+        let len = u16::from_be_bytes([*rest.first()?, *rest.get(1)?]) as usize;
+        let segment = rest.get(2..len)?;
+
+        match marker {
+            JPEG_APP0 => {
+                if let Some(jfif) = segment.strip_prefix(b"JFIF\0") {
+                    let density = u16::from_be_bytes([*jfif.get(3)?, *jfif.get(4)?]) as f32;
+                    dpi = match jfif.get(2)? {
+                        // Dots per inch and per centimeter. Otherwise only aspect ratio is set.
+                        1 => Some(density),
+                        2 => Some(density * 2.54),
+                        _ => None,
+                    }
+                    .filter(|&dpi| dpi > 0.0);
+                }
+            }
+            marker
+                if JPEG_SOF_MARKERS.contains(&marker)
+                    && !JPEG_NOT_SOF_MARKERS.contains(&marker) =>
+            {
+                let &[precision, h0, h1, w0, w1, components, ..] = segment else {
+                    return None;
+                };
+
+                return Some(JpegHeader {
+                    width: u16::from_be_bytes([w0, w1]) as usize,
+                    height: u16::from_be_bytes([h0, h1]) as usize,
+                    components,
+                    precision,
+                    dpi,
+                });
+            }
+            // Image data starts, but there was no frame header.
+            JPEG_SOS => return None,
+            _ => {}
+        }
+
+        rest = rest.get(len..)?;
+    }
+}
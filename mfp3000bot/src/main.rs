@@ -1,10 +1,27 @@
+mod bilevel;
+mod blank;
 mod bot;
 mod bot_data;
+mod bot_storage;
 mod bot_utils;
+mod color;
 mod config;
+mod contact_sheet;
+mod deskew;
+mod destination;
+mod enhance;
+mod image;
+mod ocr;
+mod orientation;
 mod pdf_builder;
+mod pdf_writer;
+mod pdfa;
+mod png;
 mod print;
 mod scan;
+mod spool;
+mod supplies;
+mod tiff;
 
 use argh::FromArgs;
 use config::Config;
@@ -36,7 +53,10 @@ async fn main() {
     let config = read_config(&args);
 
     log::info!("Start telegram bot");
-    bot::start(config).await;
+    if let Err(err) = bot::start(config).await {
+        log::error!("Failed to start telegram bot: {err:#}");
+        process::exit(1);
+    }
 }
 
 fn setup_logger(args: &Args) {
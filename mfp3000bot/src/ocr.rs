@@ -0,0 +1,142 @@
+use crate::{orientation::Rotation, scan::Jpeg};
+use anyhow::{anyhow, bail, Context};
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    thread,
+};
+
+/// Recognized text of the page.
+#[derive(Clone, Default)]
+pub struct PageText {
+    pub words: Vec<Word>,
+}
+
+/// Recognized word with bounding box in pixels of the source image.
+#[derive(Clone)]
+pub struct Word {
+    pub text: String,
+    pub left: usize,
+    pub top: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Level of the word in the tesseract's TSV output.
+const TSV_WORD_LEVEL: &str = "5";
+
+/// Orientation with lower confidence is not reliable, e.g. on pages without text.
+const MIN_ORIENTATION_CONFIDENCE: f32 = 2.0;
+
+/// Recognize text on the page with local tesseract.
+///
+/// Tesseract is called through CLI, so it must be installed with the required language data.
+pub fn recognize(jpeg: &Jpeg, languages: &[String], dpi: u16) -> anyhow::Result<PageText> {
+    let languages = if languages.is_empty() {
+        "eng".to_owned()
+    } else {
+        languages.join("+")
+    };
+
+    log::debug!("Start tesseract with languages '{languages}'");
+
+    let tsv = run_tesseract(jpeg, &["-l", &languages, "--dpi", &dpi.to_string(), "tsv"])?;
+
+    Ok(parse_tsv(&tsv))
+}
+
+/// Detect orientation of the page with tesseract's orientation and script detection.
+///
+/// Returns rotation, which makes the page upright. Tesseract must be installed with `osd` data.
+pub fn detect_rotation(jpeg: &Jpeg, dpi: u16) -> anyhow::Result<Rotation> {
+    log::debug!("Start tesseract orientation detection");
+
+    let osd = run_tesseract(jpeg, &["--psm", "0", "--dpi", &dpi.to_string()])?;
+
+    let value = |key: &str| {
+        osd.lines()
+            .find_map(|line| line.strip_prefix(key)?.strip_prefix(':'))
+            .map(str::trim)
+            .ok_or_else(|| anyhow!("'{key}' is not found in tesseract output"))
+    };
+
+    let degrees: u16 = value("Rotate")?.parse().context("parsing rotation")?;
+    let confidence: f32 = value("Orientation confidence")?
+        .parse()
+        .context("parsing orientation confidence")?;
+
+    log::debug!("Detected rotation {degrees}° with confidence {confidence}");
+
+    if confidence < MIN_ORIENTATION_CONFIDENCE {
+        return Ok(Rotation::None);
+    }
+
+    Rotation::from_degrees(degrees).ok_or_else(|| anyhow!("invalid rotation {degrees}°"))
+}
+
+/// Run tesseract with the page on stdin and return its stdout.
+fn run_tesseract(jpeg: &Jpeg, args: &[&str]) -> anyhow::Result<String> {
+    let mut tesseract = Command::new("tesseract")
+        .args(["stdin", "stdout"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("starting tesseract")?;
+
+    // Write image from the separate thread, because tesseract can fill up stdout pipe
+    // before it reads the whole image.
+    let mut stdin = tesseract.stdin.take().expect("stdin should be piped");
+    let image = jpeg.bytes.clone();
+    let writer = thread::spawn(move || stdin.write_all(&image));
+
+    let output = tesseract
+        .wait_with_output()
+        .context("waiting for tesseract")?;
+
+    writer
+        .join()
+        .expect("writer thread should not panic")
+        .context("writing image to tesseract")?;
+
+    if !output.status.success() {
+        bail!(
+            "tesseract exited with {status}: {stderr}",
+            status = output.status,
+            stderr = String::from_utf8_lossy(&output.stderr).trim(),
+        );
+    }
+
+    String::from_utf8(output.stdout).context("reading tesseract output")
+}
+
+/// Parse tesseract's TSV output. Columns are:
+/// `level page_num block_num par_num line_num word_num left top width height conf text`.
+fn parse_tsv(tsv: &str) -> PageText {
+    let words = tsv
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.splitn(12, '\t').collect();
+            let [level, _, _, _, _, _, left, top, width, height, _, text] = columns[..] else {
+                return None;
+            };
+
+            let text = text.trim();
+            if level != TSV_WORD_LEVEL || text.is_empty() {
+                return None;
+            }
+
+            Some(Word {
+                text: text.to_owned(),
+                left: left.parse().ok()?,
+                top: top.parse().ok()?,
+                width: width.parse().ok()?,
+                height: height.parse().ok()?,
+            })
+        })
+        .collect();
+
+    PageText { words }
+}
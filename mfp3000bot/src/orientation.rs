@@ -0,0 +1,79 @@
+use crate::color::components;
+use libjpeg::RawImage;
+use serde::{Deserialize, Serialize};
+
+/// Clockwise rotation of the page.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rotation {
+    #[default]
+    None,
+    Clockwise90,
+    Clockwise180,
+    Clockwise270,
+}
+
+impl Rotation {
+    pub fn from_degrees(degrees: u16) -> Option<Self> {
+        match degrees {
+            0 => Some(Self::None),
+            90 => Some(Self::Clockwise90),
+            180 => Some(Self::Clockwise180),
+            270 => Some(Self::Clockwise270),
+            _ => None,
+        }
+    }
+
+    pub fn degrees(self) -> u16 {
+        match self {
+            Self::None => 0,
+            Self::Clockwise90 => 90,
+            Self::Clockwise180 => 180,
+            Self::Clockwise270 => 270,
+        }
+    }
+
+    /// Rotation by another 90 degrees clockwise.
+    pub fn next(self) -> Self {
+        match self {
+            Self::None => Self::Clockwise90,
+            Self::Clockwise90 => Self::Clockwise180,
+            Self::Clockwise180 => Self::Clockwise270,
+            Self::Clockwise270 => Self::None,
+        }
+    }
+}
+
+/// Rotate the image by a multiple of 90 degrees.
+pub fn rotate(image: RawImage, rotation: Rotation) -> RawImage {
+    let components = components(image.format);
+    let (width, height) = (image.width, image.height);
+
+    // Size of the rotated image and position of its pixel in the source image.
+    let (new_width, new_height) = match rotation {
+        Rotation::None => return image,
+        Rotation::Clockwise180 => (width, height),
+        Rotation::Clockwise90 | Rotation::Clockwise270 => (height, width),
+    };
+    let source = |x: usize, y: usize| match rotation {
+        Rotation::None => (x, y),
+        Rotation::Clockwise90 => (y, height - 1 - x),
+        Rotation::Clockwise180 => (width - 1 - x, height - 1 - y),
+        Rotation::Clockwise270 => (width - 1 - y, x),
+    };
+
+    let mut pixels = Vec::with_capacity(image.pixels.len());
+    for y in 0..new_height {
+        for x in 0..new_width {
+            let (sx, sy) = source(x, y);
+            let offset = (sy * width + sx) * components;
+            pixels.extend_from_slice(&image.pixels[offset..offset + components]);
+        }
+    }
+
+    RawImage {
+        pixels,
+        width: new_width,
+        height: new_height,
+        format: image.format,
+    }
+}
@@ -1,66 +1,531 @@
-use crate::scan::{Jpeg, JpegFormat};
+use crate::{
+    bilevel::Bilevel,
+    ocr::PageText,
+    pdf_writer, pdfa,
+    scan::{Jpeg, JpegFormat},
+};
+use anyhow::{bail, Context};
+use flate2::{write::ZlibEncoder, Compression};
+use libjpeg::{RawImage, RawImageFormat};
 use printpdf::*;
-use std::io;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Seek, SeekFrom, Write},
+    path::Path,
+};
 
+/// Keys of the document info and outline items, which printpdf fills with our text.
+const TEXT_STRING_KEYS: [&[u8]; 6] = [
+    b"Title",
+    b"Author",
+    b"Subject",
+    b"Keywords",
+    b"Creator",
+    b"Producer",
+];
+
+/// Document is built page by page: encoded images are written to the image store right away
+/// and printpdf only gets empty placeholders, so pages of a long scan are not kept in memory.
 pub struct PdfBuilder {
     doc: PdfDocumentReference,
-    dpi: f32,
+    outline: bool,
+    pages_count: usize,
+    text_font: Option<IndirectFontRef>,
+    archival: bool,
+    images: ImageStore,
+
+    /// Image of every page in the order of pages.
+    page_images: Vec<StoredImage>,
+}
+
+/// Encoded images of the pages.
+enum ImageStore {
+    Memory(Vec<u8>),
+    File(fs::File),
+}
+
+/// Location of the encoded image in the store and its PDF filter.
+struct StoredImage {
+    offset: u64,
+    len: u64,
+    filter: &'static str,
+    params: Option<lopdf::Dictionary>,
+}
+
+/// Image of the page.
+pub enum PageImage {
+    Jpeg(Jpeg),
+    Bilevel(Bilevel),
+
+    /// Uncompressed pixels, e.g. decoded PNG. They are compressed with Flate, when stored.
+    Raw(RawImage),
+}
+
+impl From<Jpeg> for PageImage {
+    fn from(jpeg: Jpeg) -> Self {
+        PageImage::Jpeg(jpeg)
+    }
+}
+
+impl From<Bilevel> for PageImage {
+    fn from(bilevel: Bilevel) -> Self {
+        PageImage::Bilevel(bilevel)
+    }
+}
+
+impl From<RawImage> for PageImage {
+    fn from(image: RawImage) -> Self {
+        PageImage::Raw(image)
+    }
 }
 
 impl PdfBuilder {
-    pub fn new(title: &str, dpi: f32) -> Self {
+    pub fn new(title: &str) -> Self {
+        let producer = format!(
+            "{bin} {version} (commit {commit})",
+            bin = env!("CARGO_PKG_NAME"),
+            version = env!("CARGO_PKG_VERSION"),
+            commit = env!("GIT_COMMIT_HASH"),
+        );
+
+        let now = OffsetDateTime::now_utc();
+
         Self {
-            doc: PdfDocument::empty(title).with_conformance(PdfConformance::Custom(
-                CustomPdfConformance {
+            doc: PdfDocument::empty(title)
+                .with_conformance(PdfConformance::Custom(CustomPdfConformance {
                     // ICC profile bloats file:
                     // https://github.com/fschutt/printpdf/issues/174#issuecomment-2000091741
                     requires_icc_profile: false,
+                    // Only PDF/A requires XMP, which is written by `pdfa::convert`.
                     requires_xmp_metadata: false,
                     ..Default::default()
-                },
-            )),
-            dpi,
+                }))
+                .with_creator(env!("CARGO_PKG_NAME"))
+                .with_producer(producer)
+                .with_creation_date(now)
+                .with_mod_date(now),
+            outline: false,
+            pages_count: 0,
+            text_font: None,
+            archival: false,
+            images: ImageStore::Memory(Vec::new()),
+            page_images: Vec::new(),
+        }
+    }
+
+    /// Keep images in a temporary file in the `dir` instead of memory until the document
+    /// is written.
+    pub fn buffer_in(mut self, dir: &Path) -> io::Result<Self> {
+        self.images = ImageStore::File(tempfile::tempfile_in(dir)?);
+        Ok(self)
+    }
+
+    /// Set document author.
+    pub fn author(mut self, author: &str) -> Self {
+        self.doc = self.doc.with_author(author);
+        self
+    }
+
+    /// Set document keywords.
+    pub fn keywords(mut self, keywords: &[String]) -> Self {
+        self.doc = self.doc.with_keywords(keywords.to_vec());
+        self
+    }
+
+    /// Add a bookmark for every page to the document outline.
+    pub fn outline(mut self, enabled: bool) -> Self {
+        self.outline = enabled;
+        self
+    }
+
+    /// Produce PDF/A-2b document for long-term archiving.
+    ///
+    /// It embeds ICC profiles and uncompressed XMP metadata, so the document is a bit larger.
+    pub fn archival(mut self, enabled: bool) -> Self {
+        self.archival = enabled;
+        self
+    }
+
+    /// Load font for the text layer.
+    ///
+    /// Font must contain glyphs for all recognized languages, otherwise text can't be found.
+    pub fn text_font(mut self, path: &Path) -> anyhow::Result<Self> {
+        let font =
+            fs::File::open(path).with_context(|| format!("opening font '{}'", path.display()))?;
+
+        self.text_font = Some(self.doc.add_external_font(font)?);
+
+        Ok(self)
+    }
+
+    /// Add page of the image size, `dpi` is the resolution of the image.
+    pub fn add_page(&mut self, image: impl Into<PageImage>, dpi: f32) -> io::Result<()> {
+        self.add_image_page(image.into(), dpi)?;
+        Ok(())
+    }
+
+    /// Add page with invisible text layer over the image, so the document becomes searchable.
+    pub fn add_page_with_text(
+        &mut self,
+        image: impl Into<PageImage>,
+        text: &PageText,
+        dpi: f32,
+    ) -> anyhow::Result<()> {
+        let Some(font) = self.text_font.clone() else {
+            anyhow::bail!("font for text layer is not loaded");
+        };
+
+        let image = image.into();
+        let page_height = match &image {
+            PageImage::Jpeg(jpeg) => jpeg.height,
+            PageImage::Bilevel(bilevel) => bilevel.height,
+            PageImage::Raw(image) => image.height,
+        };
+
+        let layer = self.add_image_page(image, dpi)?;
+
+        layer.begin_text_section();
+        layer.set_text_rendering_mode(TextRenderingMode::Invisible);
+
+        for word in &text.words {
+            let font_size = Px(word.height).into_pt(dpi).0;
+            let width = Px(word.width).into_pt(dpi).0;
+
+            // Printpdf doesn't give access to glyph metrics, so assume that average glyph
+            // is half as wide as it is high. It's enough to select whole words in a viewer.
+            let natural_width = word.text.chars().count() as f32 * font_size * 0.5;
+
+            layer.set_font(&font, font_size);
+            layer.set_text_scaling(width / natural_width * 100.0);
+            layer.set_text_matrix(TextMatrix::Translate(
+                Px(word.left).into_pt(dpi),
+                Px(page_height.saturating_sub(word.top + word.height)).into_pt(dpi),
+            ));
+            layer.write_text(word.text.as_str(), &font);
         }
+
+        layer.end_text_section();
+
+        Ok(())
     }
 
-    pub fn add_page(&self, jpeg: Jpeg) -> io::Result<()> {
-        let width = Px(jpeg.width);
-        let height = Px(jpeg.height);
+    /// Add page of the given size with the image stretched to `image_size` and centered on it.
+    ///
+    /// Parts of the image outside of the page are cut off.
+    pub fn add_page_with_layout(
+        &mut self,
+        image: impl Into<PageImage>,
+        dpi: f32,
+        (page_width, page_height): (Mm, Mm),
+        (image_width, image_height): (Mm, Mm),
+    ) -> io::Result<()> {
+        let image = self.image_xobject(image.into())?;
+        let natural_width = Mm::from(image.width.into_pt(dpi));
+        let natural_height = Mm::from(image.height.into_pt(dpi));
 
-        let bits_per_component = ColorBits::Bit8;
-        let color_space = match jpeg.format {
-            JpegFormat::Rgb => ColorSpace::Rgb,
-            JpegFormat::Gray => ColorSpace::Greyscale,
+        let layer = self.add_empty_page(page_width, page_height);
+
+        let transform = ImageTransform {
+            translate_x: Some(Mm((page_width.0 - image_width.0) / 2.0)),
+            translate_y: Some(Mm((page_height.0 - image_height.0) / 2.0)),
+            scale_x: Some(image_width.0 / natural_width.0),
+            scale_y: Some(image_height.0 / natural_height.0),
+            dpi: Some(dpi),
+            ..Default::default()
         };
+        Image::from(image).add_to_layer(layer, transform);
 
-        let (page, layer) = self.doc.add_page(
-            Mm::from(width.into_pt(self.dpi)),
-            Mm::from(height.into_pt(self.dpi)),
-            "Image Layer",
-        );
+        Ok(())
+    }
 
-        Image::from(ImageXObject {
-            width,
-            height,
-            color_space,
-            bits_per_component,
-            interpolate: false,
-            image_data: jpeg.bytes,
-            image_filter: Some(ImageFilter::DCT),
-            smask: None,
-            clipping_bbox: None,
-        })
-        .add_to_layer(
-            self.doc.get_page(page).get_layer(layer),
-            ImageTransform::default(),
+    fn add_image_page(&mut self, image: PageImage, dpi: f32) -> io::Result<PdfLayerReference> {
+        let image = self.image_xobject(image)?;
+
+        let layer = self.add_empty_page(
+            Mm::from(image.width.into_pt(dpi)),
+            Mm::from(image.height.into_pt(dpi)),
         );
 
-        Ok(())
+        let transform = ImageTransform {
+            dpi: Some(dpi),
+            ..Default::default()
+        };
+        Image::from(image).add_to_layer(layer.clone(), transform);
+
+        Ok(layer)
+    }
+
+    fn add_empty_page(&mut self, width: Mm, height: Mm) -> PdfLayerReference {
+        let (page, layer) = self.doc.add_page(width, height, "Image Layer");
+
+        let layer = self.doc.get_page(page).get_layer(layer);
+
+        self.pages_count += 1;
+
+        if self.outline {
+            self.doc
+                .add_bookmark(format!("Страница {}", self.pages_count), page);
+        }
+
+        layer
+    }
+
+    /// Store the encoded image and return its placeholder for printpdf.
+    fn image_xobject(&mut self, image: PageImage) -> io::Result<ImageXObject> {
+        let (placeholder, data, filter, params) = match image {
+            PageImage::Jpeg(jpeg) => {
+                let color_space = match jpeg.format {
+                    JpegFormat::Rgb => ColorSpace::Rgb,
+                    JpegFormat::Gray => ColorSpace::Greyscale,
+                };
+                let placeholder =
+                    placeholder_xobject(jpeg.width, jpeg.height, color_space, ColorBits::Bit8);
+
+                (placeholder, jpeg.bytes, "DCTDecode", None)
+            }
+            PageImage::Bilevel(bilevel) => {
+                let params = lopdf::Dictionary::from_iter([
+                    ("K", lopdf::Object::from(-1)),
+                    ("Columns", lopdf::Object::from(bilevel.width as i64)),
+                    ("Rows", lopdf::Object::from(bilevel.height as i64)),
+                    ("BlackIs1", lopdf::Object::from(false)),
+                ]);
+                let placeholder = placeholder_xobject(
+                    bilevel.width,
+                    bilevel.height,
+                    ColorSpace::Greyscale,
+                    ColorBits::Bit1,
+                );
+
+                (placeholder, bilevel.bytes, "CCITTFaxDecode", Some(params))
+            }
+            PageImage::Raw(image) => {
+                let color_space = match image.format {
+                    RawImageFormat::Rgb => ColorSpace::Rgb,
+                    RawImageFormat::Gray => ColorSpace::Greyscale,
+                };
+                let placeholder =
+                    placeholder_xobject(image.width, image.height, color_space, ColorBits::Bit8);
+
+                (placeholder, deflate(&image.pixels)?, "FlateDecode", None)
+            }
+        };
+
+        let offset = self.images.append(&data)?;
+        self.page_images.push(StoredImage {
+            offset,
+            len: data.len() as u64,
+            filter,
+            params,
+        });
+
+        Ok(placeholder)
     }
 
     pub fn write_to<W: io::Write>(self, w: W) -> anyhow::Result<()> {
-        let mut writer = io::BufWriter::with_capacity(128 * 1024, w);
-        self.doc.save(&mut writer)?;
+        let Self {
+            doc,
+            archival,
+            mut images,
+            page_images,
+            ..
+        } = self;
+
+        // Without images the document is small, so it's easy to post-process it with lopdf.
+        let mut doc =
+            lopdf::Document::load_mem(&doc.save_to_bytes()?).context("reading generated PDF")?;
+
+        let image_ids = page_image_ids(&doc)?;
+        if image_ids.len() != page_images.len() {
+            bail!(
+                "generated PDF has {} images instead of {}",
+                image_ids.len(),
+                page_images.len()
+            );
+        }
+
+        let mut external = HashMap::new();
+        let mut locations = HashMap::new();
+
+        for (id, image) in image_ids.into_iter().zip(page_images) {
+            let lopdf::Object::Stream(stream) = doc.get_object_mut(id)? else {
+                bail!("image {id:?} is not a stream");
+            };
+
+            let dict = &mut stream.dict;
+
+            // Printpdf compresses placeholders and misspells the parameters of DCT filter.
+            dict.remove(b"DecodeParams");
+            dict.remove(b"DecodeParms");
+            dict.set("Filter", image.filter);
+            if let Some(params) = image.params {
+                dict.set("DecodeParms", params);
+            }
+
+            external.insert(id, image.len);
+            locations.insert(id, (image.offset, image.len));
+        }
+
+        for object in doc.objects.values_mut() {
+            convert_text_strings(object);
+        }
+
+        if archival {
+            pdfa::convert(&mut doc).context("converting to PDF/A")?;
+        }
+
+        let writer = io::BufWriter::with_capacity(128 * 1024, w);
+        pdf_writer::write(&doc, writer, &external, |id, w| {
+            let (offset, len) = locations[&id];
+            images.copy_to(offset, len, w)
+        })?;
+
         Ok(())
     }
 }
+
+impl ImageStore {
+    /// Append the data and return its offset.
+    fn append(&mut self, data: &[u8]) -> io::Result<u64> {
+        match self {
+            ImageStore::Memory(bytes) => {
+                let offset = bytes.len() as u64;
+                bytes.extend_from_slice(data);
+                Ok(offset)
+            }
+            ImageStore::File(file) => {
+                let offset = file.seek(SeekFrom::End(0))?;
+                file.write_all(data)?;
+                Ok(offset)
+            }
+        }
+    }
+
+    fn copy_to(&mut self, offset: u64, len: u64, w: &mut dyn Write) -> io::Result<()> {
+        match self {
+            ImageStore::Memory(bytes) => w.write_all(&bytes[offset as usize..][..len as usize]),
+            ImageStore::File(file) => {
+                file.seek(SeekFrom::Start(offset))?;
+                let copied = io::copy(&mut io::Read::take(&mut *file, len), w)?;
+                if copied != len {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Image without data, which is replaced by the stored image when the document is written.
+///
+/// Release build of printpdf drops empty streams, so the placeholder has a single byte.
+fn placeholder_xobject(
+    width: usize,
+    height: usize,
+    color_space: ColorSpace,
+    bits_per_component: ColorBits,
+) -> ImageXObject {
+    ImageXObject {
+        width: Px(width),
+        height: Px(height),
+        color_space,
+        bits_per_component,
+        interpolate: false,
+        image_data: vec![0],
+        image_filter: None,
+        smask: None,
+        clipping_bbox: None,
+    }
+}
+
+/// Images of the pages in the order of pages. Every page has exactly one image.
+fn page_image_ids(doc: &lopdf::Document) -> anyhow::Result<Vec<lopdf::ObjectId>> {
+    use lopdf::Object;
+
+    let mut ids = Vec::new();
+
+    for page_id in doc.get_pages().into_values() {
+        let (resources, resource_ids) = doc.get_page_resources(page_id);
+        let resources = resources.into_iter().chain(
+            resource_ids
+                .into_iter()
+                .filter_map(|id| doc.get_dictionary(id).ok()),
+        );
+
+        let mut page_images = Vec::new();
+
+        for resources in resources {
+            let Ok(xobjects) = resources
+                .get(b"XObject")
+                .and_then(|xobjects| doc.dereference(xobjects))
+                .and_then(|(_, xobjects)| xobjects.as_dict())
+            else {
+                continue;
+            };
+
+            for (_, xobject) in xobjects.iter() {
+                let Ok(id) = xobject.as_reference() else {
+                    continue;
+                };
+
+                if let Ok(Object::Stream(stream)) = doc.get_object(id) {
+                    if stream.dict.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Image")
+                    {
+                        page_images.push(id);
+                    }
+                }
+            }
+        }
+
+        match page_images[..] {
+            [id] => ids.push(id),
+            _ => bail!(
+                "page {page_id:?} has {} images instead of one",
+                page_images.len()
+            ),
+        }
+    }
+
+    Ok(ids)
+}
+
+/// Compress raw pixels, which would be several times larger than the whole scan otherwise.
+fn deflate(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+/// Printpdf writes text strings as UTF-8, so non-ASCII text (e.g. cyrillic document name)
+/// is read as PDFDocEncoding and looks broken. They are converted to UTF-16BE with a BOM,
+/// which is the only Unicode encoding of text strings before PDF 2.0.
+fn convert_text_strings(object: &mut lopdf::Object) {
+    let lopdf::Object::Dictionary(dict) = object else {
+        return;
+    };
+
+    for (key, value) in dict.iter_mut() {
+        if !TEXT_STRING_KEYS.contains(&key.as_slice()) {
+            continue;
+        }
+
+        let lopdf::Object::String(bytes, format) = value else {
+            continue;
+        };
+
+        if bytes.is_ascii() {
+            continue;
+        }
+
+        let Ok(text) = std::str::from_utf8(bytes) else {
+            continue;
+        };
+
+        *bytes = [0xfe, 0xff]
+            .into_iter()
+            .chain(text.encode_utf16().flat_map(u16::to_be_bytes))
+            .collect();
+        *format = lopdf::StringFormat::Hexadecimal;
+    }
+}
@@ -0,0 +1,247 @@
+use lopdf::{Dictionary, Document, Object, ObjectId, StringFormat};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, Write},
+};
+
+/// Object types, which are only valid in the file structure lopdf has read.
+const STRUCTURE_TYPES: [&str; 3] = ["ObjStm", "XRef", "Linearized"];
+
+/// Trailer keys of the cross-reference stream and of incremental updates.
+const STALE_TRAILER_KEYS: [&[u8]; 8] = [
+    b"Prev",
+    b"XRefStm",
+    b"Type",
+    b"W",
+    b"Index",
+    b"Filter",
+    b"DecodeParms",
+    b"Length",
+];
+
+/// Write the document with a classic cross-reference table.
+///
+/// Content of the `external` streams is not kept in the document: their lengths are known
+/// in advance, and the content is written by `write_external`, so large images can be
+/// copied from disk one by one.
+pub fn write(
+    doc: &Document,
+    w: impl Write,
+    external: &HashMap<ObjectId, u64>,
+    mut write_external: impl FnMut(ObjectId, &mut dyn Write) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut w = CountingWriter {
+        inner: w,
+        written: 0,
+    };
+
+    // Lopdf keeps the binary comment of PDF/A in the version, see `pdfa::PDF_VERSION`.
+    writeln!(w, "%PDF-{}", doc.version)?;
+
+    let mut offsets = BTreeMap::new();
+
+    for (&id, object) in &doc.objects {
+        let type_name = object.type_name().ok();
+        if type_name.is_some_and(|name| STRUCTURE_TYPES.contains(&name)) {
+            continue;
+        }
+
+        offsets.insert(id.0, (w.written, id.1));
+        writeln!(w, "{} {} obj", id.0, id.1)?;
+
+        match (object, external.get(&id)) {
+            (Object::Stream(stream), Some(&len)) => {
+                write_dictionary(&mut w, &stream.dict, Some(len))?;
+                w.write_all(b"\nstream\n")?;
+                write_external(id, &mut w)?;
+                w.write_all(b"\nendstream")?;
+            }
+            (Object::Stream(stream), None) => {
+                write_dictionary(&mut w, &stream.dict, Some(stream.content.len() as u64))?;
+                w.write_all(b"\nstream\n")?;
+                w.write_all(&stream.content)?;
+                w.write_all(b"\nendstream")?;
+            }
+            (object, _) => write_object(&mut w, object)?,
+        }
+
+        w.write_all(b"\nendobj\n")?;
+    }
+
+    let xref_start = w.written;
+    let size = doc.max_id.max(offsets.keys().last().copied().unwrap_or(0)) + 1;
+
+    // Every entry is exactly 20 bytes long.
+    writeln!(w, "xref\n0 {size}")?;
+    w.write_all(b"0000000000 65535 f\r\n")?;
+    for id in 1..size {
+        match offsets.get(&id) {
+            Some((offset, generation)) => writeln!(w, "{offset:010} {generation:05} n\r")?,
+            None => w.write_all(b"0000000000 65535 f\r\n")?,
+        }
+    }
+
+    let mut trailer = doc.trailer.clone();
+    for key in STALE_TRAILER_KEYS {
+        trailer.remove(key);
+    }
+    trailer.set("Size", size as i64);
+
+    w.write_all(b"trailer\n")?;
+    write_dictionary(&mut w, &trailer, None)?;
+    writeln!(w, "\nstartxref\n{xref_start}\n%%EOF")?;
+
+    w.flush()
+}
+
+fn write_object(w: &mut dyn Write, object: &Object) -> io::Result<()> {
+    match object {
+        Object::Null => w.write_all(b"null"),
+        Object::Boolean(value) => write!(w, "{value}"),
+        Object::Integer(value) => write!(w, "{value}"),
+        Object::Real(value) => write!(w, "{value}"),
+        Object::Name(name) => write_name(w, name),
+        Object::String(text, format) => write_string(w, text, format),
+        Object::Array(array) => {
+            w.write_all(b"[")?;
+            for (i, item) in array.iter().enumerate() {
+                if i > 0 {
+                    w.write_all(b" ")?;
+                }
+                write_object(w, item)?;
+            }
+            w.write_all(b"]")
+        }
+        Object::Dictionary(dict) => write_dictionary(w, dict, None),
+        Object::Stream(_) => Err(io::Error::other("stream can only be an indirect object")),
+        Object::Reference((id, generation)) => write!(w, "{id} {generation} R"),
+    }
+}
+
+/// Write the dictionary, replacing its `Length` with `length` for streams.
+fn write_dictionary(w: &mut dyn Write, dict: &Dictionary, length: Option<u64>) -> io::Result<()> {
+    w.write_all(b"<<")?;
+
+    for (key, value) in dict.iter() {
+        if length.is_some() && key.as_slice() == b"Length" {
+            continue;
+        }
+
+        write_name(w, key)?;
+        w.write_all(b" ")?;
+        write_object(w, value)?;
+        w.write_all(b"\n")?;
+    }
+
+    if let Some(length) = length {
+        write!(w, "/Length {length}")?;
+    }
+
+    w.write_all(b">>")
+}
+
+fn write_name(w: &mut dyn Write, name: &[u8]) -> io::Result<()> {
+    w.write_all(b"/")?;
+
+    for &byte in name {
+        // Delimiters, white-space and non-printable characters are written as `#xx`.
+        if b" \t\n\r\x0C()<>[]{}/%#".contains(&byte) || !(b'!'..=b'~').contains(&byte) {
+            write!(w, "#{byte:02X}")?;
+        } else {
+            w.write_all(&[byte])?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_string(w: &mut dyn Write, text: &[u8], format: &StringFormat) -> io::Result<()> {
+    match format {
+        StringFormat::Literal => {
+            w.write_all(b"(")?;
+            for &byte in text {
+                match byte {
+                    b'(' | b')' | b'\\' => w.write_all(&[b'\\', byte])?,
+                    // Bare carriage return would be read as a line feed.
+                    b'\r' => w.write_all(b"\\r")?,
+                    _ => w.write_all(&[byte])?,
+                }
+            }
+            w.write_all(b")")
+        }
+        StringFormat::Hexadecimal => {
+            w.write_all(b"<")?;
+            for &byte in text {
+                write!(w, "{byte:02X}")?;
+            }
+            w.write_all(b">")
+        }
+    }
+}
+
+/// Writer, which knows offsets of the objects for the cross-reference table.
+struct CountingWriter<W> {
+    inner: W,
+    written: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+
+    #[test]
+    fn document_is_read_back() {
+        let mut doc = Document::with_version("1.7");
+        let inline = doc.add_object(Stream::new(dictionary! {}, b"inline".to_vec()));
+        // Length of the external stream is replaced with the length of its content.
+        let external = doc.add_object(Stream::new(dictionary! { "Length" => 0 }, Vec::new()));
+        let info = doc.add_object(dictionary! {
+            "Title" => Object::String(b"(a)\\b\rc".to_vec(), StringFormat::Literal),
+            "Subject" => Object::String(b"\0\xff".to_vec(), StringFormat::Hexadecimal),
+            "Keywords" => vec![
+                Object::Name(b"a b#c".to_vec()),
+                Object::Integer(1),
+                Object::Boolean(true),
+            ],
+        });
+        doc.trailer.set("Info", info);
+
+        let mut pdf = Vec::new();
+        write(&doc, &mut pdf, &HashMap::from([(external, 8)]), |id, w| {
+            assert_eq!(id, external);
+            w.write_all(b"external")
+        })
+        .unwrap();
+
+        let read = Document::load_mem(&pdf).unwrap();
+
+        let content = |id| {
+            let stream = read.get_object(id).unwrap().as_stream().unwrap();
+            stream.content.clone()
+        };
+        assert_eq!(content(inline), b"inline");
+        assert_eq!(content(external), b"external");
+
+        let info = read.trailer.get(b"Info").unwrap().as_reference().unwrap();
+        let info = read.get_dictionary(info).unwrap();
+        assert_eq!(info.get(b"Title").unwrap().as_str().unwrap(), b"(a)\\b\rc");
+        assert_eq!(info.get(b"Subject").unwrap().as_str().unwrap(), b"\0\xff");
+        let keywords = info.get(b"Keywords").unwrap().as_array().unwrap();
+        assert_eq!(keywords[0].as_name().unwrap(), b"a b#c");
+        assert_eq!(keywords[1].as_i64().unwrap(), 1);
+        assert!(keywords[2].as_bool().unwrap());
+    }
+}
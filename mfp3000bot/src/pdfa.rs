@@ -0,0 +1,382 @@
+use anyhow::Context;
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+
+/// PDF/A requires a comment with binary characters right after the header. Lopdf writes
+/// the version right after `%PDF-`, so the comment is passed along with the version.
+const PDF_VERSION: &str = "1.7\n%\u{e2}\u{e3}\u{cf}\u{d3}";
+
+const OUTPUT_CONDITION: &str = "sRGB IEC61966-2.1";
+
+/// D50 illuminant of the profile connection space.
+const D50: [f64; 3] = [0.9642, 1.0, 0.8249];
+
+/// Convert document generated by printpdf to PDF/A-2b.
+///
+/// Printpdf writes PDF/X output intent with CMYK profile, so it's replaced with sRGB
+/// output intent, and XMP metadata with PDF/A identification is added.
+pub fn convert(doc: &mut Document) -> anyhow::Result<()> {
+    doc.version = PDF_VERSION.to_owned();
+
+    let info = read_info(doc).context("reading document info")?;
+
+    let metadata_id = doc.add_object(xmp_metadata(&info));
+    let rgb_profile_id = doc.add_object(icc_profile_stream(&srgb_profile(), 3)?);
+    let gray_profile_id = doc.add_object(icc_profile_stream(&sgray_profile(), 1)?);
+
+    for object in doc.objects.values_mut() {
+        if let Object::Stream(stream) = object {
+            convert_image(&mut stream.dict, gray_profile_id);
+        }
+    }
+
+    let catalog = doc.catalog_mut().context("reading catalog")?;
+
+    let old_metadata = catalog.get(b"Metadata").and_then(Object::as_reference).ok();
+
+    catalog.set("Metadata", metadata_id);
+    catalog.set(
+        "OutputIntents",
+        vec![Object::Dictionary(output_intent(rgb_profile_id))],
+    );
+
+    // Every optional content configuration must have a name.
+    if let Ok(config) = catalog
+        .get_mut(b"OCProperties")
+        .and_then(Object::as_dict_mut)
+        .and_then(|properties| properties.get_mut(b"D"))
+        .and_then(Object::as_dict_mut)
+    {
+        config.set("Name", Object::string_literal("Layers"));
+    }
+
+    if let Some(old_metadata) = old_metadata {
+        doc.objects.remove(&old_metadata);
+    }
+
+    Ok(())
+}
+
+/// Metadata of the document info dictionary, which must be repeated in XMP.
+#[derive(Default)]
+struct Info {
+    title: Option<String>,
+    author: Option<String>,
+    subject: Option<String>,
+    keywords: Option<String>,
+    creator: Option<String>,
+    producer: Option<String>,
+    creation_date: Option<String>,
+    modification_date: Option<String>,
+}
+
+fn read_info(doc: &mut Document) -> anyhow::Result<Info> {
+    let info_id = doc.trailer.get(b"Info").and_then(Object::as_reference)?;
+    let dict = doc.get_dictionary_mut(info_id)?;
+
+    // Printpdf always writes these keys, so remove the empty ones to keep XMP consistent.
+    dict.remove(b"GTS_PDFXVersion");
+    for key in [
+        b"Title".as_slice(),
+        b"Author",
+        b"Subject",
+        b"Keywords",
+        b"Creator",
+        b"Producer",
+        b"Identifier",
+    ] {
+        if dict.get(key).and_then(Object::as_str).ok() == Some(b"") {
+            dict.remove(key);
+        }
+    }
+
+    let text = |key: &[u8]| dict.get(key).and_then(Object::as_str).ok().map(decode_text);
+    let date = |key: &[u8]| {
+        dict.get(key)
+            .and_then(Object::as_str)
+            .ok()
+            .and_then(|date| xmp_date(&String::from_utf8_lossy(date)))
+    };
+
+    Ok(Info {
+        title: text(b"Title"),
+        author: text(b"Author"),
+        subject: text(b"Subject"),
+        keywords: text(b"Keywords"),
+        creator: text(b"Creator"),
+        producer: text(b"Producer"),
+        creation_date: date(b"CreationDate"),
+        modification_date: date(b"ModDate"),
+    })
+}
+
+fn decode_text(bytes: &[u8]) -> String {
+    if let Some(utf16) = bytes.strip_prefix(&[0xfe, 0xff]) {
+        let units: Vec<u16> = utf16
+            .chunks_exact(2)
+            .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+            .collect();
+
+        String::from_utf16_lossy(&units)
+    } else {
+        bytes.iter().map(|&b| b as char).collect()
+    }
+}
+
+/// Convert `D:YYYYMMDDHHmmSS+HH'mm'` to `YYYY-MM-DDTHH:mm:SS+HH:mm`.
+fn xmp_date(date: &str) -> Option<String> {
+    let date = date.strip_prefix("D:")?;
+
+    let (year, month, day) = (date.get(0..4)?, date.get(4..6)?, date.get(6..8)?);
+    let (hour, minute, second) = (date.get(8..10)?, date.get(10..12)?, date.get(12..14)?);
+
+    let offset = match date.get(14..)? {
+        "" | "Z" => "Z".to_owned(),
+        offset => {
+            let sign = offset.get(0..1)?;
+            let hours = offset.get(1..3)?;
+            let minutes = offset.get(4..6).unwrap_or("00");
+            format!("{sign}{hours}:{minutes}")
+        }
+    };
+
+    Some(format!(
+        "{year}-{month}-{day}T{hour}:{minute}:{second}{offset}"
+    ))
+}
+
+fn xmp_metadata(info: &Info) -> Stream {
+    let mut properties = String::new();
+
+    let mut add = |name: &str, value: &Option<String>, container: Option<&str>| {
+        let Some(value) = value else {
+            return;
+        };
+
+        let value = xml_escape(value);
+        let value = match container {
+            Some("Alt") => {
+                format!("<rdf:Alt><rdf:li xml:lang=\"x-default\">{value}</rdf:li></rdf:Alt>")
+            }
+            Some(container) => {
+                format!("<rdf:{container}><rdf:li>{value}</rdf:li></rdf:{container}>")
+            }
+            None => value,
+        };
+
+        properties.push_str(&format!("      <{name}>{value}</{name}>\n"));
+    };
+
+    add("dc:title", &info.title, Some("Alt"));
+    add("dc:creator", &info.author, Some("Seq"));
+    add("dc:description", &info.subject, Some("Alt"));
+    add("pdf:Keywords", &info.keywords, None);
+    add("pdf:Producer", &info.producer, None);
+    add("xmp:CreatorTool", &info.creator, None);
+    add("xmp:CreateDate", &info.creation_date, None);
+    add("xmp:ModifyDate", &info.modification_date, None);
+
+    let xmp = format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>
+<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">
+  <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">
+    <rdf:Description rdf:about=\"\"
+        xmlns:dc=\"http://purl.org/dc/elements/1.1/\"
+        xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\"
+        xmlns:pdfaid=\"http://www.aiim.org/pdfa/ns/id/\"
+        xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\">
+      <pdfaid:part>2</pdfaid:part>
+      <pdfaid:conformance>B</pdfaid:conformance>
+      <dc:format>application/pdf</dc:format>
+{properties}    </rdf:Description>
+  </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end=\"w\"?>"
+    );
+
+    let dict = Dictionary::from_iter([
+        ("Type", Object::from("Metadata")),
+        ("Subtype", Object::from("XML")),
+    ]);
+
+    // Metadata must stay readable without PDF parser, so it's not compressed.
+    Stream::new(dict, xmp.into_bytes()).with_compression(false)
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn output_intent(profile_id: ObjectId) -> Dictionary {
+    Dictionary::from_iter([
+        ("Type", Object::from("OutputIntent")),
+        ("S", Object::from("GTS_PDFA1")),
+        (
+            "OutputConditionIdentifier",
+            Object::string_literal(OUTPUT_CONDITION),
+        ),
+        ("Info", Object::string_literal(OUTPUT_CONDITION)),
+        (
+            "RegistryName",
+            Object::string_literal("http://www.color.org"),
+        ),
+        ("DestinationOutputProfile", Object::from(profile_id)),
+    ])
+}
+
+/// Use calibrated gray for gray images and remove empty soft masks,
+/// because transparency is not allowed.
+fn convert_image(dict: &mut Dictionary, gray_profile_id: ObjectId) {
+    if dict.get(b"Subtype").and_then(Object::as_name).ok() != Some(b"Image") {
+        return;
+    }
+
+    if let Ok(Object::Null) = dict.get(b"SMask") {
+        dict.remove(b"SMask");
+    }
+
+    if dict.get(b"ColorSpace").and_then(Object::as_name).ok() == Some(b"DeviceGray") {
+        dict.set(
+            "ColorSpace",
+            vec![Object::from("ICCBased"), Object::from(gray_profile_id)],
+        );
+    }
+}
+
+fn icc_profile_stream(profile: &[u8], components: i64) -> anyhow::Result<Stream> {
+    let dict = Dictionary::from_iter([("N", Object::from(components))]);
+
+    let mut stream = Stream::new(dict, profile.to_vec());
+    stream.compress()?;
+
+    Ok(stream)
+}
+
+/// ICC v2 display profile of sRGB with D50 adapted primaries.
+fn srgb_profile() -> Vec<u8> {
+    let trc = srgb_trc_tag();
+
+    icc_profile(
+        b"RGB ",
+        &[
+            (*b"desc", desc_tag("sRGB IEC61966-2.1")),
+            (*b"cprt", text_tag("No copyright, use freely")),
+            (*b"wtpt", xyz_tag(D50)),
+            (*b"rXYZ", xyz_tag([0.4361, 0.2225, 0.0139])),
+            (*b"gXYZ", xyz_tag([0.3851, 0.7169, 0.0971])),
+            (*b"bXYZ", xyz_tag([0.1431, 0.0606, 0.7141])),
+            (*b"rTRC", trc.clone()),
+            (*b"gTRC", trc.clone()),
+            (*b"bTRC", trc),
+        ],
+    )
+}
+
+/// ICC v2 display profile of gray with sRGB tone curve.
+fn sgray_profile() -> Vec<u8> {
+    icc_profile(
+        b"GRAY",
+        &[
+            (*b"desc", desc_tag("sGray")),
+            (*b"cprt", text_tag("No copyright, use freely")),
+            (*b"wtpt", xyz_tag(D50)),
+            (*b"kTRC", srgb_trc_tag()),
+        ],
+    )
+}
+
+fn icc_profile(color_space: &[u8; 4], tags: &[([u8; 4], Vec<u8>)]) -> Vec<u8> {
+    const HEADER_SIZE: usize = 128;
+    const TAG_ENTRY_SIZE: usize = 12;
+
+    let mut data = Vec::new();
+    let mut entries = Vec::new();
+
+    let data_offset = HEADER_SIZE + 4 + tags.len() * TAG_ENTRY_SIZE;
+
+    for (signature, tag) in tags {
+        entries.extend_from_slice(signature);
+        entries.extend_from_slice(&((data_offset + data.len()) as u32).to_be_bytes());
+        entries.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+
+        data.extend_from_slice(tag);
+        data.resize(data.len().next_multiple_of(4), 0);
+    }
+
+    let size = data_offset + data.len();
+
+    let mut profile = Vec::with_capacity(size);
+    profile.extend_from_slice(&(size as u32).to_be_bytes());
+    profile.extend_from_slice(&[0; 4]); // CMM
+    profile.extend_from_slice(&0x02100000u32.to_be_bytes()); // Version 2.1
+    profile.extend_from_slice(b"mntr");
+    profile.extend_from_slice(color_space);
+    profile.extend_from_slice(b"XYZ ");
+    for date_part in [2024u16, 1, 1, 0, 0, 0] {
+        profile.extend_from_slice(&date_part.to_be_bytes());
+    }
+    profile.extend_from_slice(b"acsp");
+    profile.extend_from_slice(&[0; 24]); // Platform, flags, device and attributes.
+    profile.extend_from_slice(&[0; 4]); // Perceptual rendering intent.
+    profile.extend_from_slice(&xyz_number(D50));
+    profile.resize(HEADER_SIZE, 0);
+
+    profile.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+    profile.extend_from_slice(&entries);
+    profile.extend_from_slice(&data);
+
+    profile
+}
+
+fn desc_tag(description: &str) -> Vec<u8> {
+    let mut tag = b"desc\0\0\0\0".to_vec();
+    tag.extend_from_slice(&(description.len() as u32 + 1).to_be_bytes());
+    tag.extend_from_slice(description.as_bytes());
+    tag.push(0);
+    // Empty unicode and ScriptCode descriptions.
+    tag.extend_from_slice(&[0; 8]);
+    tag.extend_from_slice(&[0; 3]);
+    tag.extend_from_slice(&[0; 67]);
+    tag
+}
+
+fn text_tag(text: &str) -> Vec<u8> {
+    let mut tag = b"text\0\0\0\0".to_vec();
+    tag.extend_from_slice(text.as_bytes());
+    tag.push(0);
+    tag
+}
+
+fn xyz_tag(xyz: [f64; 3]) -> Vec<u8> {
+    let mut tag = b"XYZ \0\0\0\0".to_vec();
+    tag.extend_from_slice(&xyz_number(xyz));
+    tag
+}
+
+fn xyz_number(xyz: [f64; 3]) -> Vec<u8> {
+    xyz.into_iter()
+        .flat_map(|value| ((value * 65536.0).round() as i32).to_be_bytes())
+        .collect()
+}
+
+fn srgb_trc_tag() -> Vec<u8> {
+    const POINTS: u32 = 1024;
+
+    let mut tag = b"curv\0\0\0\0".to_vec();
+    tag.extend_from_slice(&POINTS.to_be_bytes());
+
+    for i in 0..POINTS {
+        let v = i as f64 / (POINTS - 1) as f64;
+        let linear = if v <= 0.04045 {
+            v / 12.92
+        } else {
+            ((v + 0.055) / 1.055).powf(2.4)
+        };
+
+        tag.extend_from_slice(&((linear * 65535.0).round() as u16).to_be_bytes());
+    }
+
+    tag
+}
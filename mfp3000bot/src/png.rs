@@ -0,0 +1,151 @@
+use crate::color::components;
+use ::png::{
+    BitDepth, ColorType, DecodeOptions, Decoder, Encoder, PixelDimensions, Transformations, Unit,
+};
+use anyhow::{bail, Context};
+use libjpeg::{RawImage, RawImageFormat};
+
+const INCHES_PER_METER: f64 = 1.0 / 0.0254;
+
+/// Compress the image to lossless PNG with the resolution of the scan.
+pub fn encode(image: &RawImage, dpi: u16) -> Vec<u8> {
+    let mut png = Vec::new();
+
+    let mut encoder = Encoder::new(&mut png, image.width as u32, image.height as u32);
+    encoder.set_color(match image.format {
+        RawImageFormat::Gray => ColorType::Grayscale,
+        RawImageFormat::Rgb => ColorType::Rgb,
+    });
+    encoder.set_depth(BitDepth::Eight);
+
+    let pixels_per_meter = (dpi as f64 * INCHES_PER_METER).round() as u32;
+    encoder.set_pixel_dims(Some(PixelDimensions {
+        xppu: pixels_per_meter,
+        yppu: pixels_per_meter,
+        unit: Unit::Meter,
+    }));
+
+    let mut writer = encoder
+        .write_header()
+        .expect("writing to vector should not fail");
+    writer
+        .write_image_data(&image.pixels)
+        .expect("image should have pixels for its size");
+    writer.finish().expect("writing to vector should not fail");
+
+    png
+}
+
+/// Decode PNG to 8-bit gray or RGB image. Transparent pixels are blended with white paper.
+///
+/// Returns the image and its resolution from `pHYs` chunk, if it's set.
+///
+/// # Error
+///
+/// Return error if the image is malformed or has more than `max_pixels` pixels.
+pub fn decode(png: &[u8], max_pixels: usize) -> anyhow::Result<(RawImage, Option<f32>)> {
+    // By default damaged ancillary chunks are used as is, so the resolution could be garbage.
+    let mut options = DecodeOptions::default();
+    options.set_skip_ancillary_crc_failures(false);
+
+    let mut decoder = Decoder::new_with_options(png, options);
+    // Palette, low bit depths and transparency are expanded to 8-bit samples.
+    decoder.set_transformations(Transformations::normalize_to_color8());
+
+    let mut reader = decoder.read_info().context("reading PNG header")?;
+
+    let info = reader.info();
+    let (width, height) = (info.width as usize, info.height as usize);
+    if width
+        .checked_mul(height)
+        .filter(|&pixels| pixels <= max_pixels)
+        .is_none()
+    {
+        bail!("PNG image {width}x{height} is too large");
+    }
+
+    let dpi = info
+        .pixel_dims
+        .filter(|dims| dims.unit == Unit::Meter && dims.xppu > 0)
+        .map(|dims| (dims.xppu as f64 / INCHES_PER_METER) as f32);
+
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buffer).context("decoding PNG")?;
+    buffer.truncate(frame.buffer_size());
+
+    let (format, pixels) = match frame.color_type {
+        ColorType::Grayscale => (RawImageFormat::Gray, buffer),
+        ColorType::Rgb => (RawImageFormat::Rgb, buffer),
+        ColorType::GrayscaleAlpha => {
+            let pixels = buffer
+                .chunks_exact(2)
+                .map(|pixel| blend(pixel[0], pixel[1]))
+                .collect();
+            (RawImageFormat::Gray, pixels)
+        }
+        ColorType::Rgba => {
+            let pixels = buffer
+                .chunks_exact(4)
+                .flat_map(|pixel| (0..3).map(|c| blend(pixel[c], pixel[3])))
+                .collect();
+            (RawImageFormat::Rgb, pixels)
+        }
+        ColorType::Indexed => bail!("PNG palette is not expanded"),
+    };
+
+    debug_assert_eq!(pixels.len(), width * height * components(format));
+
+    let image = RawImage {
+        pixels,
+        width,
+        height,
+        format,
+    };
+
+    Ok((image, dpi))
+}
+
+/// Blend the color with white paper.
+fn blend(color: u8, alpha: u8) -> u8 {
+    ((color as u16 * alpha as u16 + 255 * (255 - alpha as u16)) / 255) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(format: RawImageFormat) -> RawImage {
+        let (width, height) = (7, 5);
+        RawImage {
+            pixels: (0..width * height * components(format))
+                .map(|i| i as u8)
+                .collect(),
+            width,
+            height,
+            format,
+        }
+    }
+
+    #[test]
+    fn round_trip_keeps_pixels_and_resolution() {
+        for format in [RawImageFormat::Gray, RawImageFormat::Rgb] {
+            let image = image(format);
+
+            let (decoded, dpi) = decode(&encode(&image, 300), usize::MAX).unwrap();
+
+            assert_eq!(decoded.pixels, image.pixels);
+            assert_eq!((decoded.width, decoded.height), (image.width, image.height));
+            assert_eq!(dpi.map(f32::round), Some(300.0));
+        }
+    }
+
+    #[test]
+    fn damaged_resolution_is_rejected() {
+        let mut png = encode(&image(RawImageFormat::Gray), 300);
+        // Only the checksum of the chunk shows, that the resolution is damaged.
+        let resolution = png.windows(4).position(|kind| kind == b"pHYs").unwrap() + 4;
+        png[resolution] ^= 0xff;
+
+        assert!(decode(&png, usize::MAX).is_err());
+    }
+}
@@ -1,24 +1,60 @@
-use crate::config;
+use crate::{config, image::Image, pdf_builder::PdfBuilder};
 use anyhow::{anyhow, bail, Context};
 use libcups::{
     document::{Document, DocumentName, DocumentType},
-    options::Options,
-    printer::{DeviceName, JobTitle, Printer},
+    job::{JobId, JobInfo, JobStatus, WhichJobs},
+    options::{ColorMode, MediaFormat, Options, Orientation, PrintQuality, Sides},
+    printer::{DeviceName, JobTitle, Printer, PrinterAttributes},
 };
+use printpdf::Mm;
 use reqwest::{
     blocking::{get, Client},
     Url,
 };
+use serde::{Deserialize, Serialize};
 use std::{
+    fs,
     io::{self, Read},
-    time::Instant,
+    path::Path,
+    time::{Duration, Instant},
 };
 
-#[derive(Clone, Copy, Debug)]
+/// How often the state of the print job is queried.
+pub const JOB_POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Jobs, which are stuck longer, are not tracked anymore.
+pub const JOB_TRACKING_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// How many times in a row the job state query can fail before the tracking stops.
+/// The poll interval is doubled after each failure.
+pub const JOB_STATUS_RETRIES: u32 = 5;
+
+/// Resolution of images without it in the metadata, like in browsers.
+const DEFAULT_IMAGE_DPI: f32 = 96.0;
+
+const MM_PER_INCH: f32 = 25.4;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum DocumentFormat {
     Text,
     Pdf,
     Word,
+    Jpeg,
+    Png,
+}
+
+/// How the image is placed on the paper sheet.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImageFit {
+    /// Whole image is scaled to the sheet keeping aspect ratio.
+    #[default]
+    Fit,
+
+    /// Image covers the whole sheet, its edges are cut off.
+    Fill,
+
+    /// Size of the image is taken from its resolution.
+    ActualSize,
 }
 
 impl DocumentFormat {
@@ -31,10 +67,18 @@ impl DocumentFormat {
             Some(DocumentFormat::Pdf)
         } else if doc.ends_with(".docx") || doc.ends_with(".doc") {
             Some(DocumentFormat::Word)
+        } else if doc.ends_with(".jpg") || doc.ends_with(".jpeg") {
+            Some(DocumentFormat::Jpeg)
+        } else if doc.ends_with(".png") {
+            Some(DocumentFormat::Png)
         } else {
             None
         }
     }
+
+    pub fn is_image(self) -> bool {
+        matches!(self, Self::Jpeg | Self::Png)
+    }
 }
 
 impl From<DocumentFormat> for DocumentType {
@@ -43,21 +87,104 @@ impl From<DocumentFormat> for DocumentType {
             DocumentFormat::Text => DocumentType::PlainText,
             DocumentFormat::Pdf => DocumentType::Pdf,
             DocumentFormat::Word => DocumentType::Pdf,
+            DocumentFormat::Jpeg | DocumentFormat::Png => DocumentType::Pdf,
+        }
+    }
+}
+
+/// Options of the print job, which are chosen by the user.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PrintOptions {
+    pub copies: usize,
+    pub paper_size: Option<MediaFormat>,
+    pub orientation: Option<Orientation>,
+    pub sides: Option<Sides>,
+    pub color_mode: Option<ColorMode>,
+    pub quality: Option<PrintQuality>,
+
+    /// Ranges of pages, e.g. `[(1, 3), (5, 5)]`. All pages are printed, if it's empty.
+    pub page_ranges: Vec<(usize, usize)>,
+
+    /// Placement of images on the sheet. Documents are printed as is.
+    #[serde(default)]
+    pub image_fit: ImageFit,
+}
+
+impl From<&config::Print> for PrintOptions {
+    fn from(config: &config::Print) -> Self {
+        Self {
+            copies: 1,
+            paper_size: config.paper_size,
+            orientation: config.orientation,
+            sides: config.sides,
+            color_mode: config.color_mode,
+            quality: config.quality,
+            page_ranges: Vec::new(),
+            image_fit: ImageFit::default(),
         }
     }
 }
 
+impl PrintOptions {
+    fn to_options(&self) -> Options {
+        let mut options = Options::default().copies(self.copies);
+
+        if let Some(paper_size) = self.paper_size {
+            options = options.media_format(paper_size);
+        }
+
+        if let Some(orientation) = self.orientation {
+            options = options.orientation(orientation);
+        }
+
+        if let Some(sides) = self.sides {
+            options = options.sides(sides);
+        }
+
+        if let Some(color_mode) = self.color_mode {
+            options = options.color_mode(color_mode);
+        }
+
+        if let Some(quality) = self.quality {
+            options = options.quality(quality);
+        }
+
+        if !self.page_ranges.is_empty() {
+            options = options.page_ranges(&self.page_ranges);
+        }
+
+        options
+    }
+}
+
+/// Parse ranges of pages like `1-3, 5`.
+///
+/// Returns None, if ranges are malformed or a range is reversed.
+pub fn parse_page_ranges(text: &str) -> Option<Vec<(usize, usize)>> {
+    text.split(',')
+        .map(|range| {
+            let (first, last) = match range.split_once('-') {
+                Some((first, last)) => (first.trim(), last.trim()),
+                None => (range.trim(), range.trim()),
+            };
+
+            let first: usize = first.parse().ok()?;
+            let last: usize = last.parse().ok()?;
+
+            (first >= 1 && first <= last).then_some((first, last))
+        })
+        .collect()
+}
+
 pub fn print_remote_file(
     printer: &str,
     document_name: &String,
     document_format: DocumentFormat,
     url: &Url,
-    config: &config::Print,
-) -> anyhow::Result<()> {
+    options: &PrintOptions,
+) -> anyhow::Result<JobId> {
     tokio::task::block_in_place(|| {
-        let Some(printer) = Printer::find_by_name(DeviceName::new(printer).unwrap()) else {
-            bail!("printer '{printer}' not found");
-        };
+        let printer = find_printer(printer)?;
 
         let document_reader = get(url.to_owned()).map_err(|err| {
             anyhow!(
@@ -66,12 +193,15 @@ pub fn print_remote_file(
             )
         })?;
 
-        // TODO: Support images.
         let document_type = document_format.into();
         let mut document_reader = match document_format {
             DocumentFormat::Word => {
                 Box::new(io::Cursor::new(docx_to_pdf(document_reader)?)) as Box<dyn io::Read>
             }
+            DocumentFormat::Jpeg | DocumentFormat::Png => {
+                let pdf = image_to_pdf(document_name, document_format, document_reader, options)?;
+                Box::new(io::Cursor::new(pdf))
+            }
             _ => Box::new(document_reader),
         };
 
@@ -81,42 +211,161 @@ pub fn print_remote_file(
             &mut document_reader,
         );
 
-        let options = config_to_options(config);
-
-        printer.print_documents(
+        let job = printer.print_documents(
             JobTitle::new(document_name).unwrap(),
-            options,
+            options.to_options(),
             vec![document],
         )?;
 
-        Ok(())
+        Ok(job)
     })
 }
 
-fn config_to_options(config: &config::Print) -> Options {
-    let mut options = Options::default();
+/// Print PDF file from the disk, e.g. a copy made with the scanner.
+pub fn print_pdf_file(
+    printer: &str,
+    document_name: &str,
+    path: &Path,
+    options: &PrintOptions,
+) -> anyhow::Result<JobId> {
+    let printer = find_printer(printer)?;
+
+    let mut file =
+        fs::File::open(path).with_context(|| format!("opening file '{}'", path.display()))?;
 
-    if let Some(paper_size) = config.paper_size {
-        options = options.media_format(paper_size);
-    }
+    let document = Document::new(
+        DocumentName::new(document_name).unwrap(),
+        DocumentType::Pdf,
+        &mut file,
+    );
 
-    if let Some(orientation) = config.orientation {
-        options = options.orientation(orientation);
-    }
+    let job = printer.print_documents(
+        JobTitle::new(document_name).unwrap(),
+        options.to_options(),
+        vec![document],
+    )?;
 
-    if let Some(sides) = config.sides {
-        options = options.sides(sides);
-    }
+    Ok(job)
+}
+
+pub fn job_status(printer: &str, job: JobId) -> anyhow::Result<JobStatus> {
+    let status = find_printer(printer)?
+        .job_status(job)
+        .with_context(|| format!("getting status of job #{job}"))?;
+
+    Ok(status)
+}
+
+pub fn printer_attributes(printer: &str) -> anyhow::Result<PrinterAttributes> {
+    let attributes = find_printer(printer)?
+        .attributes()
+        .context("getting printer attributes")?;
+
+    Ok(attributes)
+}
+
+pub fn list_jobs(printer: &str, which: WhichJobs, limit: usize) -> anyhow::Result<Vec<JobInfo>> {
+    let jobs = find_printer(printer)?
+        .jobs(which, limit)
+        .with_context(|| format!("listing {which:?} jobs"))?;
+
+    Ok(jobs)
+}
+
+pub fn cancel_job(printer: &str, job: JobId) -> anyhow::Result<()> {
+    find_printer(printer)?
+        .cancel_job(job)
+        .with_context(|| format!("cancelling job #{job}"))
+}
+
+fn find_printer(printer: &str) -> anyhow::Result<Printer> {
+    let Some(device_name) = DeviceName::new(printer) else {
+        bail!("invalid printer name '{printer}'");
+    };
+
+    let Some(printer) = Printer::find_by_name(device_name) else {
+        bail!("printer '{printer}' not found");
+    };
 
-    if let Some(color_mode) = config.color_mode {
-        options = options.color_mode(color_mode);
+    Ok(printer)
+}
+
+/// Render the image to a single page PDF of the chosen paper size.
+///
+/// Image is rotated, if its orientation doesn't match the page.
+fn image_to_pdf(
+    document_name: &str,
+    format: DocumentFormat,
+    mut reader: impl io::Read,
+    options: &PrintOptions,
+) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader
+        .read_to_end(&mut bytes)
+        .context("downloading image")?;
+
+    let mut image = match format {
+        DocumentFormat::Jpeg => Image::from_jpeg(bytes)?,
+        DocumentFormat::Png => Image::from_png(&bytes)?,
+        _ => unreachable!("format is not an image"),
+    };
+
+    // Orientation option doesn't work for PDF, so it's applied to the page itself.
+    let (mut page_width, mut page_height) =
+        paper_size_mm(options.paper_size.unwrap_or(MediaFormat::A4));
+    if options.orientation == Some(Orientation::Landscape) {
+        (page_width, page_height) = (page_height, page_width);
     }
 
-    if let Some(quality) = config.quality {
-        options = options.quality(quality);
+    if (image.width > image.height) != (page_width > page_height) {
+        image = image.rotate().context("rotating image")?;
     }
 
-    options
+    let dpi = image.dpi.unwrap_or(DEFAULT_IMAGE_DPI);
+    let width = image.width as f32 / dpi * MM_PER_INCH;
+    let height = image.height as f32 / dpi * MM_PER_INCH;
+
+    let scale = match options.image_fit {
+        ImageFit::Fit => (page_width / width).min(page_height / height),
+        ImageFit::Fill => (page_width / width).max(page_height / height),
+        ImageFit::ActualSize => 1.0,
+    };
+
+    let mut pdf_builder = PdfBuilder::new(document_name);
+    pdf_builder.add_page_with_layout(
+        image.page,
+        dpi,
+        (Mm(page_width), Mm(page_height)),
+        (Mm(width * scale), Mm(height * scale)),
+    )?;
+
+    let mut pdf = Vec::new();
+    pdf_builder.write_to(&mut pdf)?;
+
+    Ok(pdf)
+}
+
+/// Size of the paper in portrait orientation.
+fn paper_size_mm(paper: MediaFormat) -> (f32, f32) {
+    let inches = |width: f32, height: f32| (width * MM_PER_INCH, height * MM_PER_INCH);
+
+    match paper {
+        MediaFormat::F3X5 => inches(3.0, 5.0),
+        MediaFormat::F4X6 => inches(4.0, 6.0),
+        MediaFormat::F5X7 => inches(5.0, 7.0),
+        MediaFormat::F8X10 => inches(8.0, 10.0),
+        MediaFormat::A3 => (297.0, 420.0),
+        MediaFormat::A4 => (210.0, 297.0),
+        MediaFormat::A5 => (148.0, 210.0),
+        MediaFormat::A6 => (105.0, 148.0),
+        MediaFormat::Env10 => inches(4.125, 9.5),
+        MediaFormat::EnvDl => (110.0, 220.0),
+        MediaFormat::Legal => inches(8.5, 14.0),
+        MediaFormat::Letter => inches(8.5, 11.0),
+        MediaFormat::PhotoL => inches(3.5, 5.0),
+        MediaFormat::SuperBa3 => inches(13.0, 19.0),
+        MediaFormat::Tabloid => inches(11.0, 17.0),
+    }
 }
 
 fn docx_to_pdf(mut reader: impl io::Read) -> anyhow::Result<Vec<u8>> {
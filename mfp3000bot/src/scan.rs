@@ -1,9 +1,17 @@
-use crate::config::Config;
+use crate::{
+    bilevel, blank, color,
+    config::{ColorMode, Config, ScanProfile},
+    deskew,
+};
 use anyhow::{anyhow, bail, Context};
 use bstr::{BStr, BString};
 use lazy_static::lazy_static;
-use libsane::{Backend, FrameFormat, OptionValue, Parameters, Scanner, ScannerOption};
-use std::{collections::HashMap, io::Read, thread};
+use libsane::{
+    Backend, FrameFormat, OptionConstraint, OptionType, OptionValue, Parameters, Scanner,
+    ScannerOption,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, io::Read, sync::Arc, thread};
 use tokio::sync::{mpsc, oneshot};
 
 lazy_static! {
@@ -14,12 +22,22 @@ pub enum ScanState {
     Prepair,
     Progress,
     Stop,
+    Deskew,
     CompressToJpeg,
-    Done(Jpeg),
+    Done(ScannedPage),
+    Blank,
     Error(anyhow::Error),
     Cancelled,
 }
 
+/// Scanned page compressed with JPEG and its lossless pixels for other output formats.
+#[derive(Clone)]
+pub struct ScannedPage {
+    pub jpeg: Jpeg,
+    pub raw: Arc<libjpeg::RawImage>,
+    pub dpi: u16,
+}
+
 #[derive(Clone)]
 pub struct Jpeg {
     pub bytes: Vec<u8>,
@@ -28,15 +46,141 @@ pub struct Jpeg {
     pub height: usize,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub enum JpegFormat {
     Rgb,
     Gray,
 }
 
+/// Processing of the scanned page before JPEG compression.
+#[derive(Clone, Copy, Default)]
+pub struct PageProcessing {
+    /// Straighten the page and crop it to its edges.
+    pub deskew: bool,
+
+    /// Drop the page, if it's blank by `blank_page_threshold` from the config.
+    pub skip_blank: bool,
+}
+
+/// Options of the scan session chosen by the user, which override the config.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ScanSettings {
+    /// Name of the preset from `[scan_presets]`.
+    pub preset: Option<String>,
+    pub resolution: Option<u16>,
+    pub mode: Option<BString>,
+    pub source: Option<BString>,
+    pub quality: Option<u8>,
+
+    /// Profile, which overrides the profile of the config and the preset.
+    #[serde(default)]
+    pub profile: Option<ScanProfile>,
+
+    /// Pages are printed instead of sending them to the chat, see `/copy`.
+    #[serde(default)]
+    pub copy: bool,
+}
+
+/// Values of the scanner options, which the user can choose from.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct OptionChoices {
+    pub resolutions: Vec<u16>,
+    pub modes: Vec<BString>,
+    pub sources: Vec<BString>,
+}
+
+/// Resolutions, which are offered for scanners with a range of resolutions.
+const COMMON_RESOLUTIONS: [u16; 7] = [75, 100, 150, 200, 300, 600, 1200];
+
+/// Check that the scanner is found and can be opened.
+///
+/// Opening fails, while the scanner is busy with a scan.
+pub fn check_scanner(config: &Config) -> anyhow::Result<()> {
+    let device_name = config
+        .devices
+        .scanner
+        .as_deref()
+        .ok_or_else(|| anyhow!("scanner is not specified in the config"))?;
+
+    let device = BACKEND
+        .find_device_by_name(device_name)
+        .context("reading devices")?
+        .ok_or_else(|| anyhow!("device '{device_name}' not found"))?;
+
+    Scanner::new(device).context("opening device")?;
+
+    Ok(())
+}
+
+/// Read the values of the scanner options, which the user can choose from.
+pub fn read_option_choices(config: &Config) -> anyhow::Result<OptionChoices> {
+    let device_name = config
+        .devices
+        .scanner
+        .as_deref()
+        .ok_or_else(|| anyhow!("scanner is not specified in the config"))?;
+
+    let device = BACKEND
+        .find_device_by_name(device_name)
+        .context("reading devices")?
+        .ok_or_else(|| anyhow!("device '{device_name}' not found"))?;
+
+    let scanner = Scanner::new(device).context("opening device")?;
+
+    let mut choices = OptionChoices::default();
+
+    for option in scanner.options().iter() {
+        let Some(name) = option.name else {
+            continue;
+        };
+
+        let integer = |value: i32| match option.ty {
+            // Fixed point number with 16 bits of the fractional part.
+            OptionType::Fixed => value >> 16,
+            _ => value,
+        };
+
+        let name: &[u8] = name.as_ref();
+        match (name, &option.constraint) {
+            (b"resolution", OptionConstraint::WordList(values)) => {
+                choices.resolutions = values
+                    .iter()
+                    .map(|&value| integer(value))
+                    .filter_map(|value| u16::try_from(value).ok())
+                    .collect();
+            }
+            (b"resolution", OptionConstraint::Range { range, quant }) => {
+                let (min, max) = (integer(*range.start()), integer(*range.end()));
+                // Zero step means any value in the range.
+                let step = integer(*quant);
+
+                choices.resolutions = COMMON_RESOLUTIONS
+                    .into_iter()
+                    .filter(|&dpi| {
+                        let dpi = dpi as i32;
+                        (min..=max).contains(&dpi) && (step == 0 || (dpi - min) % step == 0)
+                    })
+                    .collect();
+            }
+            (b"mode", OptionConstraint::StringList(values)) => {
+                choices.modes = values.iter().map(|&value| value.to_owned()).collect();
+            }
+            (b"source", OptionConstraint::StringList(values)) => {
+                choices.sources = values.iter().map(|&value| value.to_owned()).collect();
+            }
+            _ => {}
+        }
+    }
+
+    Ok(choices)
+}
+
+/// Start scanning in a separate thread.
 pub fn start(
     config: Config,
     dpi: u16,
+    processing: PageProcessing,
+    settings: ScanSettings,
     mut cancel: oneshot::Receiver<()>,
 ) -> mpsc::Receiver<ScanState> {
     let (mut state_tx, state_rx) = mpsc::channel(4);
@@ -44,7 +188,14 @@ pub fn start(
     thread::Builder::new()
         .name("scan".to_owned())
         .spawn(move || {
-            let scan_result = scan_page(config, dpi, &mut state_tx, &mut cancel);
+            let scan_result = scan_page(
+                config,
+                dpi,
+                processing,
+                settings,
+                &mut state_tx,
+                &mut cancel,
+            );
             match scan_result {
                 Ok(true) => {}
                 Ok(false) => {
@@ -63,6 +214,8 @@ pub fn start(
 fn scan_page(
     config: Config,
     dpi: u16,
+    processing: PageProcessing,
+    settings: ScanSettings,
     state: &mut mpsc::Sender<ScanState>,
     cancel: &mut oneshot::Receiver<()>,
 ) -> anyhow::Result<bool> {
@@ -110,7 +263,7 @@ fn scan_page(
     check_cancellation!(cancel);
     let mut scanner = Scanner::new(device).context("opening device")?;
 
-    setup_scanner(&mut scanner, &config, dpi);
+    setup_scanner(&mut scanner, &config, dpi, &settings);
 
     check_cancellation!(cancel);
     let mut reader = scanner.start().context("starting scan")?;
@@ -158,19 +311,53 @@ fn scan_page(
     drop(reader);
     drop(scanner);
 
+    let mut raw_image = raw_image(parameters, pixels)?;
+
+    if processing.deskew {
+        check_cancellation!(cancel);
+        send_state!(ScanState::Deskew);
+
+        deskew::deskew_and_crop(&mut raw_image);
+    }
+
+    if let Some(threshold) = config.scan.blank_page_threshold {
+        if processing.skip_blank {
+            let coverage = blank::ink_coverage(&raw_image);
+            log::debug!("Page ink coverage {coverage:.5}");
+
+            if coverage <= threshold {
+                send_state!(ScanState::Blank);
+                return Ok(true);
+            }
+        }
+    }
+
     check_cancellation!(cancel);
     send_state!(ScanState::CompressToJpeg);
 
-    let raw_image = raw_image(parameters, pixels)?;
-    let jpeg = encode_jpeg(raw_image, config.scan.page_quality);
+    if config.scan.color_mode == ColorMode::Auto && !color::has_color(&raw_image) {
+        log::debug!("Convert page without color to grayscale");
+        color::to_gray(&mut raw_image);
+    }
 
-    send_state!(ScanState::Done(jpeg));
+    if config.scan.profile == ScanProfile::Text {
+        bilevel::binarize(&mut raw_image);
+    }
+
+    let quality = settings.quality.unwrap_or(config.scan.page_quality);
+    let jpeg = encode_jpeg(&raw_image, quality);
+
+    send_state!(ScanState::Done(ScannedPage {
+        jpeg,
+        raw: Arc::new(raw_image),
+        dpi,
+    }));
 
     Ok(true)
 }
 
 #[rustfmt::skip]
-fn setup_scanner(scanner: &mut Scanner<'_>, config: &Config, dpi: u16) {
+fn setup_scanner(scanner: &mut Scanner<'_>, config: &Config, dpi: u16, settings: &ScanSettings) {
     log::debug!("Start device setup");
 
     let device_name = scanner.get_device().name.to_string();
@@ -178,9 +365,17 @@ fn setup_scanner(scanner: &mut Scanner<'_>, config: &Config, dpi: u16) {
     let options = scanner.options();
     log::debug!("Device options: {options:#?}");
 
-    let values = get_options_values(&device_name, config);
+    let mut values = get_options_values(&device_name, config);
     log::debug!("Options values from config: {values:#?}");
 
+    // Settings of the session override the config.
+    if let Some(mode) = &settings.mode {
+        values.insert(BString::from("mode"), OptionValue::String(mode.as_ref()));
+    }
+    if let Some(source) = &settings.source {
+        values.insert(BString::from("source"), OptionValue::String(source.as_ref()));
+    }
+
     for (i, option) in options.into_iter().enumerate() {
         let Some(option_name) = option.name.filter(|name| !name.is_empty()) else {
             log::debug!("Skip unnamed option #{i}");
@@ -250,6 +445,9 @@ fn raw_image(parameters: Parameters, pixels: Vec<u8>) -> anyhow::Result<libjpeg:
     let height = parameters.lines;
 
     let format = match parameters.format {
+        FrameFormat::Gray if parameters.depth == 1 => {
+            return Ok(lineart_image(parameters, pixels));
+        }
         FrameFormat::Gray => libjpeg::RawImageFormat::Gray,
         FrameFormat::RGB => libjpeg::RawImageFormat::Rgb,
         format => bail!("unsupported image format '{format:?}'"),
@@ -263,8 +461,35 @@ fn raw_image(parameters: Parameters, pixels: Vec<u8>) -> anyhow::Result<libjpeg:
     })
 }
 
-fn encode_jpeg(image: libjpeg::RawImage, output_quality: u8) -> Jpeg {
-    let bytes = libjpeg::compress_to_jpeg(&image, output_quality);
+/// Expand 1-bit lineart image, where bit 1 is black, to 8-bit gray image.
+fn lineart_image(parameters: Parameters, pixels: Vec<u8>) -> libjpeg::RawImage {
+    let width = parameters.pixels_per_line;
+    let height = parameters.lines;
+
+    let pixels = pixels
+        .chunks_exact(parameters.bytes_per_line)
+        .flat_map(|line| {
+            (0..width).map(move |x| {
+                let bit = line[x / 8] >> (7 - x % 8) & 1;
+                if bit == 1 {
+                    0
+                } else {
+                    u8::MAX
+                }
+            })
+        })
+        .collect();
+
+    libjpeg::RawImage {
+        pixels,
+        width,
+        height,
+        format: libjpeg::RawImageFormat::Gray,
+    }
+}
+
+pub fn encode_jpeg(image: &libjpeg::RawImage, output_quality: u8) -> Jpeg {
+    let bytes = libjpeg::compress_to_jpeg(image, output_quality);
 
     Jpeg {
         bytes,
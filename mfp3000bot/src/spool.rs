@@ -0,0 +1,265 @@
+use crate::{
+    orientation::Rotation,
+    png,
+    scan::{Jpeg, JpegFormat, ScannedPage},
+};
+use anyhow::{bail, Context};
+use libjpeg::RawImage;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Prefix of the spool directories, so other files in the spool directory are not touched.
+const SPOOL_PREFIX: &str = "scan-";
+
+/// Pages of the multi-page document spooled to disk.
+///
+/// Pages are stored in a directory, which survives restarts of the bot together
+/// with the dialogue, and is removed with all pages and the output document
+/// by [`Pages::delete`].
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Pages {
+    dir: PathBuf,
+    pages: Vec<SpooledPage>,
+    next_page_id: usize,
+    blank_pages: usize,
+}
+
+/// Reference to the page on disk.
+///
+/// Page is stored twice: compressed with JPEG and as lossless PNG for lossless output formats.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SpooledPage {
+    path: PathBuf,
+    png_path: PathBuf,
+    pub format: JpegFormat,
+    pub width: usize,
+    pub height: usize,
+    pub dpi: u16,
+
+    /// Rotation, which is applied to the page in the document.
+    pub rotation: Rotation,
+}
+
+impl Pages {
+    /// Create an empty spool in the `spool_dir`.
+    pub fn new(spool_dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(spool_dir)?;
+
+        let dir = tempfile::Builder::new()
+            .prefix(SPOOL_PREFIX)
+            .tempdir_in(spool_dir)?
+            .into_path();
+
+        log::debug!("Create page spool '{}'", dir.display());
+
+        Ok(Self {
+            dir,
+            pages: Vec::new(),
+            next_page_id: 1,
+            blank_pages: 0,
+        })
+    }
+
+    /// Create a spool in the `spool_dir` with the first page.
+    pub fn with_page(spool_dir: &Path, page: ScannedPage, rotation: Rotation) -> io::Result<Self> {
+        let mut pages = Self::new(spool_dir)?;
+
+        if let Err(err) = pages.push(page, rotation) {
+            pages.delete();
+            return Err(err);
+        }
+
+        Ok(pages)
+    }
+
+    /// Remove the spool with all pages and the output document from disk.
+    pub fn delete(self) {
+        log::debug!("Remove page spool '{}'", self.dir.display());
+
+        if let Err(err) = fs::remove_dir_all(&self.dir) {
+            log::error!(
+                "Failed to remove page spool '{}': {err}",
+                self.dir.display()
+            );
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&SpooledPage> {
+        self.pages.get(index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SpooledPage> {
+        self.pages.iter()
+    }
+
+    /// Write the page to disk and append it to the document.
+    pub fn push(&mut self, page: ScannedPage, rotation: Rotation) -> io::Result<()> {
+        let page = self.write_page(page, rotation)?;
+        self.pages.push(page);
+
+        Ok(())
+    }
+
+    /// Write the new version of the page to disk and put it in place of the old one.
+    ///
+    /// Old version stays on disk until the spool is deleted, because clones
+    /// of the spool may still refer to it.
+    pub fn replace(
+        &mut self,
+        index: usize,
+        page: ScannedPage,
+        rotation: Rotation,
+    ) -> io::Result<()> {
+        self.pages[index] = self.write_page(page, rotation)?;
+
+        Ok(())
+    }
+
+    /// Remove the page from the document.
+    ///
+    /// Page stays on disk until the spool is deleted, like in [`Pages::replace`].
+    pub fn remove(&mut self, index: usize) {
+        self.pages.remove(index);
+    }
+
+    /// Move the page to the new position in the document.
+    pub fn move_page(&mut self, from: usize, to: usize) {
+        let page = self.pages.remove(from);
+        self.pages.insert(to, page);
+    }
+
+    /// Rotate the page by another 90 degrees clockwise.
+    pub fn rotate(&mut self, index: usize) {
+        let page = &mut self.pages[index];
+        page.rotation = page.rotation.next();
+    }
+
+    /// Count the dropped blank page.
+    pub fn skip_blank(&mut self) {
+        self.blank_pages += 1;
+    }
+
+    /// Number of dropped blank pages.
+    pub fn blank_count(&self) -> usize {
+        self.blank_pages
+    }
+
+    /// Directory of the spool, which is deleted with all its files.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Path for the output document with the `extension`, which is deleted with the spool.
+    pub fn output_path(&self, extension: &str) -> PathBuf {
+        self.dir.join(format!("document.{extension}"))
+    }
+
+    fn write_page(&mut self, page: ScannedPage, rotation: Rotation) -> io::Result<SpooledPage> {
+        let path = self.dir.join(format!("page-{:04}.jpg", self.next_page_id));
+        let png_path = path.with_extension("png");
+
+        // Raw pixels of A4 page at 300 DPI take 26 MB, so they are compressed losslessly.
+        fs::write(&path, &page.jpeg.bytes)?;
+        fs::write(&png_path, png::encode(&page.raw, page.dpi))?;
+
+        self.next_page_id += 1;
+
+        Ok(SpooledPage {
+            path,
+            png_path,
+            format: page.jpeg.format,
+            width: page.jpeg.width,
+            height: page.jpeg.height,
+            dpi: page.dpi,
+            rotation,
+        })
+    }
+}
+
+impl SpooledPage {
+    /// Read the page from disk as it was scanned, without rotation.
+    pub fn load(&self) -> io::Result<Jpeg> {
+        Ok(Jpeg {
+            bytes: fs::read(&self.path)?,
+            format: self.format,
+            width: self.width,
+            height: self.height,
+        })
+    }
+
+    /// Read both versions of the page from disk as it was scanned, without rotation.
+    pub fn load_scanned(&self) -> anyhow::Result<ScannedPage> {
+        Ok(ScannedPage {
+            jpeg: self.load()?,
+            raw: Arc::new(self.load_raw()?),
+            dpi: self.dpi,
+        })
+    }
+
+    /// Read the lossless copy of the page from disk as PNG file.
+    pub fn load_png(&self) -> io::Result<Vec<u8>> {
+        fs::read(&self.png_path)
+    }
+
+    /// Read raw pixels of the page from disk as it was scanned, without rotation.
+    pub fn load_raw(&self) -> anyhow::Result<RawImage> {
+        let png = fs::read(&self.png_path)
+            .with_context(|| format!("reading page '{}'", self.png_path.display()))?;
+
+        let (image, _) = png::decode(&png, self.width * self.height)?;
+        if (image.width, image.height) != (self.width, self.height) {
+            bail!("page '{}' has unexpected size", self.png_path.display());
+        }
+
+        Ok(image)
+    }
+}
+
+/// Remove spools in the `spool_dir`, which are not `used` by any dialogue.
+///
+/// Spools are left behind, when dialogues are lost on restart of the bot.
+pub fn remove_unused(spool_dir: &Path, used: &HashSet<PathBuf>) {
+    let entries = match fs::read_dir(spool_dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return,
+        Err(err) => {
+            log::error!(
+                "Failed to read spool directory '{}': {err}",
+                spool_dir.display()
+            );
+            return;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        let is_spool = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| name.starts_with(SPOOL_PREFIX))
+            && path.is_dir();
+        if !is_spool || used.contains(&path) {
+            continue;
+        }
+
+        log::info!("Remove unused page spool '{}'", path.display());
+
+        if let Err(err) = fs::remove_dir_all(&path) {
+            log::error!("Failed to remove page spool '{}': {err}", path.display());
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use libcups::printer::Marker;
+use std::collections::HashSet;
+
+/// Finds supplies, which became low since the previous check.
+///
+/// A supply is reported once and again only after its level rises above the threshold,
+/// e.g. after the cartridge is replaced.
+pub struct SupplyMonitor {
+    threshold: Option<u8>,
+    low: HashSet<String>,
+}
+
+impl SupplyMonitor {
+    pub fn new(threshold: Option<u8>) -> Self {
+        Self {
+            threshold,
+            low: HashSet::new(),
+        }
+    }
+
+    /// Returns supplies, which crossed the threshold since the previous check.
+    pub fn check<'m>(&mut self, markers: &'m [Marker]) -> Vec<&'m Marker> {
+        markers
+            .iter()
+            .filter(|marker| {
+                let (Some(level), Some(threshold)) =
+                    (marker.level, self.threshold.or(marker.low_level))
+                else {
+                    // Unknown level doesn't change the state of the supply.
+                    return false;
+                };
+
+                if level > threshold {
+                    self.low.remove(&marker.name);
+                    false
+                } else {
+                    self.low.insert(marker.name.clone())
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,213 @@
+use crate::bilevel;
+use anyhow::Context;
+use flate2::{write::ZlibEncoder, Compression};
+use libjpeg::{RawImage, RawImageFormat};
+use std::io::{Seek, SeekFrom, Write};
+
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+const RATIONAL: u16 = 5;
+
+const COMPRESSION_CCITT_G4: u32 = 4;
+const COMPRESSION_DEFLATE: u32 = 8;
+
+const PHOTOMETRIC_WHITE_IS_ZERO: u32 = 0;
+const PHOTOMETRIC_BLACK_IS_ZERO: u32 = 1;
+const PHOTOMETRIC_RGB: u32 = 2;
+
+const RESOLUTION_UNIT_INCH: u32 = 2;
+
+/// Number of entries in the directory of each page.
+const ENTRIES: u32 = 12;
+
+/// Writer of single or multi-page TIFF.
+///
+/// Black-and-white pages are compressed with CCITT G4, other pages with Deflate.
+pub struct TiffWriter<W: Write + Seek> {
+    writer: W,
+
+    /// Position of the offset to the next page directory.
+    next_ifd_position: u64,
+}
+
+impl<W: Write + Seek> TiffWriter<W> {
+    pub fn new(mut writer: W) -> anyhow::Result<Self> {
+        // Little-endian header with the offset of the first page directory, which
+        // is filled with the first page.
+        writer.write_all(b"II*\0")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+
+        Ok(Self {
+            writer,
+            next_ifd_position: 4,
+        })
+    }
+
+    pub fn add_page(&mut self, image: &RawImage, dpi: u16) -> anyhow::Result<()> {
+        let (data, compression, photometric, samples, bits) = if bilevel::is_bilevel(image) {
+            let data = bilevel::compress_g4(image)?.bytes;
+            (data, COMPRESSION_CCITT_G4, PHOTOMETRIC_WHITE_IS_ZERO, 1, 1)
+        } else {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&image.pixels)?;
+            let data = encoder.finish()?;
+
+            match image.format {
+                RawImageFormat::Gray => {
+                    (data, COMPRESSION_DEFLATE, PHOTOMETRIC_BLACK_IS_ZERO, 1, 8)
+                }
+                RawImageFormat::Rgb => (data, COMPRESSION_DEFLATE, PHOTOMETRIC_RGB, 3, 8),
+            }
+        };
+
+        let data_offset = self.position()?;
+        self.writer.write_all(&data)?;
+
+        // Directory must start on a word boundary.
+        if data.len() % 2 == 1 {
+            self.writer.write_all(&[0])?;
+        }
+
+        let ifd_offset = self.position()?;
+        self.writer.seek(SeekFrom::Start(self.next_ifd_position))?;
+        self.writer.write_all(&ifd_offset.to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(ifd_offset as u64))?;
+
+        // Values, which don't fit in the entry, are placed after the directory.
+        let mut extra = Vec::new();
+        let extra_offset = ifd_offset + 2 + ENTRIES * 12 + 4;
+
+        let bits_per_sample = if samples == 1 {
+            bits
+        } else {
+            let offset = extra_offset + extra.len() as u32;
+            for _ in 0..samples {
+                extra.extend((bits as u16).to_le_bytes());
+            }
+            offset
+        };
+
+        let resolution = extra_offset + extra.len() as u32;
+        extra.extend((dpi as u32).to_le_bytes());
+        extra.extend(1u32.to_le_bytes());
+
+        let entries: [(u16, u16, u32, u32); ENTRIES as usize] = [
+            (256, LONG, 1, image.width as u32),
+            (257, LONG, 1, image.height as u32),
+            (258, SHORT, samples, bits_per_sample),
+            (259, SHORT, 1, compression),
+            (262, SHORT, 1, photometric),
+            (273, LONG, 1, data_offset),
+            (277, SHORT, 1, samples),
+            (278, LONG, 1, image.height as u32),
+            (279, LONG, 1, data.len() as u32),
+            (282, RATIONAL, 1, resolution),
+            (283, RATIONAL, 1, resolution),
+            (296, SHORT, 1, RESOLUTION_UNIT_INCH),
+        ];
+
+        self.writer.write_all(&(ENTRIES as u16).to_le_bytes())?;
+        for (tag, ty, count, value) in entries {
+            self.writer.write_all(&tag.to_le_bytes())?;
+            self.writer.write_all(&ty.to_le_bytes())?;
+            self.writer.write_all(&count.to_le_bytes())?;
+            // Short values are left-justified, that is the same for little-endian.
+            self.writer.write_all(&value.to_le_bytes())?;
+        }
+
+        self.next_ifd_position = self.position()? as u64;
+        self.writer.write_all(&0u32.to_le_bytes())?;
+
+        self.writer.write_all(&extra)?;
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> anyhow::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn position(&mut self) -> anyhow::Result<u32> {
+        let position = self.writer.stream_position()?;
+        u32::try_from(position).context("TIFF is larger than 4 GiB")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::tiff::{
+        decoder::{ifd::Value, Decoder, DecodingResult},
+        tags::Tag,
+        ColorType,
+    };
+    use std::io::Cursor;
+
+    #[test]
+    fn pages_are_read_back() {
+        let (width, height) = (11, 3);
+        let bilevel = RawImage {
+            pixels: (0..width * height)
+                .map(|i| if i % 3 == 0 { 0 } else { 255 })
+                .collect(),
+            width,
+            height,
+            format: RawImageFormat::Gray,
+        };
+        let gray = RawImage {
+            pixels: (0..width * height).map(|i| i as u8).collect(),
+            width,
+            height,
+            format: RawImageFormat::Gray,
+        };
+        let rgb = RawImage {
+            pixels: (0..width * height * 3).map(|i| i as u8).collect(),
+            width,
+            height,
+            format: RawImageFormat::Rgb,
+        };
+
+        let mut tiff = TiffWriter::new(Cursor::new(Vec::new())).unwrap();
+        tiff.add_page(&bilevel, 300).unwrap();
+        tiff.add_page(&gray, 200).unwrap();
+        tiff.add_page(&rgb, 150).unwrap();
+        let tiff = tiff.finish().unwrap().into_inner();
+
+        let mut decoder = Decoder::new(Cursor::new(tiff)).unwrap();
+        for (i, (image, dpi, color_type)) in [
+            (&bilevel, 300, ColorType::Gray(1)),
+            (&gray, 200, ColorType::Gray(8)),
+            (&rgb, 150, ColorType::RGB(8)),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            if i > 0 {
+                decoder.next_image().unwrap();
+            }
+
+            assert_eq!(decoder.dimensions().unwrap(), (width as u32, height as u32));
+            assert_eq!(decoder.colortype().unwrap(), color_type);
+            for tag in [Tag::XResolution, Tag::YResolution] {
+                assert_eq!(decoder.get_tag(tag).unwrap(), Value::Rational(dpi, 1));
+            }
+
+            let DecodingResult::U8(pixels) = decoder.read_image().unwrap() else {
+                panic!("page {i} is not 8-bit");
+            };
+            let pixels = if color_type == ColorType::Gray(1) {
+                // Rows are packed to bits, where white is one.
+                pixels
+                    .chunks_exact(width.div_ceil(8))
+                    .flat_map(|row| (0..width).map(move |x| row[x / 8] << (x % 8) >> 7))
+                    .map(|bit| bit * 255)
+                    .collect()
+            } else {
+                pixels
+            };
+            assert_eq!(pixels, image.pixels, "page {i}");
+        }
+        assert!(!decoder.more_images());
+    }
+}